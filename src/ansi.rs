@@ -0,0 +1,267 @@
+use crate::model::{ClocData, Contributor, LanguageStats, LargestFile};
+use crate::svg::SvgTemplateData;
+use std::fmt::Write;
+
+/// Number of characters of the language bar in the terminal
+const ANSI_BAR_WIDTH: usize = 50;
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+
+/// Approximation of a card for a terminal with true color support
+pub fn render_ansi(title: &str, data: &SvgTemplateData) -> String {
+    let mut output = format!(
+        "{}Stats for {}{}\n{} lines of code in {} files\n",
+        BOLD, title, RESET, data.total_lines, data.total_files
+    );
+
+    // Every language gets the characters between the rounded start and end of its part of the bar
+    let mut bar = String::new();
+    for language in &data.languages {
+        let start = (language.x / data.bar_width * ANSI_BAR_WIDTH as f64).round() as usize;
+        let end = ((language.x + language.width) / data.bar_width * ANSI_BAR_WIDTH as f64).round()
+            as usize;
+        bar.push_str(&foreground(&language.color));
+        bar.push_str(&"█".repeat(end.saturating_sub(start)));
+    }
+    output.push_str(&bar);
+    output.push_str(RESET);
+    output.push('\n');
+
+    for language in &data.languages {
+        output.push_str(&format!(
+            "{}●{} {} {:.2}%\n",
+            foreground(&language.color),
+            RESET,
+            language.name,
+            language.percentage
+        ));
+    }
+
+    output
+}
+
+/// Table with a row per language, the largest first. Without `breakdown` only the total lines are
+/// known, like for stored stats, and the comment and blank columns are empty
+pub fn render_stats_table(cloc: &ClocData, breakdown: bool) -> String {
+    let languages = cloc.ranked_languages();
+    let total: u64 = languages.iter().map(|(_, s)| s.total_lines()).sum();
+    let width = languages
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["Language".len()])
+        .max()
+        .unwrap_or_default();
+    let column = |value: u64| {
+        if breakdown {
+            value.to_string()
+        } else {
+            "-".to_string()
+        }
+    };
+
+    let mut table = format!(
+        "{:<width$}  {:>7}  {:>10}  {:>10}  {:>10}  {:>7}",
+        "Language", "Files", "Code", "Comment", "Blank", "%"
+    );
+    for (name, stats) in &languages {
+        let share = match total {
+            0 => 0.0,
+            total => stats.total_lines() as f64 / total as f64 * 100.0,
+        };
+        let _ = write!(
+            table,
+            "\n{:<width$}  {:>7}  {:>10}  {:>10}  {:>10}  {:>6.2}%",
+            name,
+            stats.n_files,
+            stats.code,
+            column(stats.comment),
+            column(stats.blank),
+            share
+        );
+    }
+    let sum =
+        |value: fn(&LanguageStats) -> u64| languages.iter().map(|(_, s)| value(s)).sum::<u64>();
+    let _ = write!(
+        table,
+        "\n{:<width$}  {:>7}  {:>10}  {:>10}  {:>10}  {:>6.2}%",
+        "Total",
+        sum(|s| s.n_files),
+        sum(|s| s.code),
+        column(sum(|s| s.comment)),
+        column(sum(|s| s.blank)),
+        if total > 0 { 100.0 } else { 0.0 }
+    );
+    table
+}
+
+/// Table of the largest files of a project for `pstatool stats`
+pub fn render_largest_files_table(files: &[LargestFile]) -> String {
+    let width = files
+        .iter()
+        .map(|file| file.path.len())
+        .chain(["File".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!(
+        "{:<width$}  {:>10}  {:>10}  Language",
+        "File", "Lines", "Code"
+    );
+    for file in files {
+        let _ = write!(
+            table,
+            "\n{:<width$}  {:>10}  {:>10}  {}",
+            file.path, file.lines, file.code, file.language
+        );
+    }
+    table
+}
+
+/// Table of the contributors of a project for `pstatool stats`
+pub fn render_contributors_table(contributors: &[Contributor]) -> String {
+    let width = contributors
+        .iter()
+        .map(|contributor| contributor.name.chars().count())
+        .chain(["Author".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!("{:<width$}  {:>8}  Email", "Author", "Commits");
+    for contributor in contributors {
+        let _ = write!(
+            table,
+            "\n{:<width$}  {:>8}  {}",
+            contributor.name, contributor.commits, contributor.email
+        );
+    }
+    table
+}
+
+/// Escape code for a `#rrggbb` foreground color, empty for other formats
+fn foreground(color: &str) -> String {
+    let hex = color.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => format!("\x1b[38;2;{};{};{}m", r, g, b),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi::{
+        foreground, render_ansi, render_contributors_table, render_largest_files_table,
+        render_stats_table,
+    };
+    use crate::model::{CardOptions, ClocData, Contributor, LanguageStats, LargestFile};
+    use crate::svg::cloc_to_svg_template_data;
+
+    #[test]
+    fn test_render_ansi() {
+        let mut cloc = ClocData::default();
+        for (language, code) in [("Rust", 300), ("Swift", 100)] {
+            cloc.languages.insert(
+                language.to_string(),
+                LanguageStats {
+                    n_files: 1,
+                    code,
+                    ..LanguageStats::default()
+                },
+            );
+        }
+        let data = cloc_to_svg_template_data(&cloc, &CardOptions::default());
+
+        let output = render_ansi("Test", &data);
+        assert!(output.contains("Stats for Test"));
+        assert!(output.contains("400 lines of code in 2 files"));
+        assert!(output.contains("Rust 75.00%"));
+        assert_eq!(output.matches('█').count(), 50);
+    }
+
+    #[test]
+    fn test_render_stats_table() {
+        let mut cloc = ClocData::default();
+        for (language, code, comment, blank) in [("Swift", 100, 20, 30), ("Rust", 300, 40, 10)] {
+            cloc.languages.insert(
+                language.to_string(),
+                LanguageStats {
+                    n_files: 2,
+                    code,
+                    comment,
+                    blank,
+                },
+            );
+        }
+
+        let table = render_stats_table(&cloc, true);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Language    Files        Code     Comment       Blank        %"
+        );
+        assert_eq!(
+            lines[1],
+            "Rust            2         300          40          10   70.00%"
+        );
+        assert!(lines[2].starts_with("Swift"));
+        assert_eq!(
+            lines[3],
+            "Total           4         400          60          40  100.00%"
+        );
+
+        let table = render_stats_table(&cloc, false);
+        assert!(table.contains("Rust            2         300           -           -   70.00%"));
+    }
+
+    #[test]
+    fn test_render_largest_files_table() {
+        let files = vec![LargestFile {
+            path: "src/generated/schema.rs".to_string(),
+            language: "Rust".to_string(),
+            lines: 4200,
+            code: 4000,
+        }];
+        let table = render_largest_files_table(&files);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "File                          Lines        Code  Language",
+                "src/generated/schema.rs        4200        4000  Rust",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_contributors_table() {
+        let contributor = |name: &str, email: &str, commits| Contributor {
+            name: name.to_string(),
+            email: email.to_string(),
+            commits,
+        };
+        let contributors = vec![
+            contributor("Wesley", "wesley@example.com", 412),
+            contributor("Bo", "bo@example.com", 3),
+        ];
+        let table = render_contributors_table(&contributors);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Author   Commits  Email",
+                "Wesley       412  wesley@example.com",
+                "Bo             3  bo@example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_foreground() {
+        assert_eq!(foreground("#dea584"), "\x1b[38;2;222;165;132m");
+        assert_eq!(foreground("#ccc"), "");
+    }
+}
@@ -0,0 +1,439 @@
+use crate::error::PstatoolError;
+use flate2::read::GzDecoder;
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// Host of the tarballs of the repositories on GitHub
+const CODELOAD_URL: &str = "https://codeload.github.com";
+
+/// Size of the largest tarball that is downloaded, larger repositories are cloned
+const MAX_TARBALL_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Limits of what an archive extracts to, so an archive that is small compared to its contents
+/// can't fill the temp folder
+#[derive(Debug, Clone, Copy)]
+struct ExtractLimits {
+    max_entries: usize,
+    /// Total size of the extracted files in bytes
+    max_size: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        ExtractLimits {
+            max_entries: 1_000_000,
+            max_size: 16 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// The entries and bytes extracted so far, fails once they pass the limits
+struct Extracted {
+    limits: ExtractLimits,
+    entries: usize,
+    size: u64,
+}
+
+impl Extracted {
+    fn new(limits: ExtractLimits) -> Self {
+        Extracted {
+            limits,
+            entries: 0,
+            size: 0,
+        }
+    }
+
+    fn add_entry(&mut self) -> io::Result<()> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            let message = format!("Archive has more than {} entries", self.limits.max_entries);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(())
+    }
+
+    fn add_size(&mut self, size: u64) -> io::Result<()> {
+        self.size = self.size.saturating_add(size);
+        if self.size > self.limits.max_size {
+            let message = format!(
+                "Archive extracts to more than {} MB",
+                self.limits.max_size / 1024 / 1024
+            );
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, message));
+        }
+        Ok(())
+    }
+
+    /// Bytes that can still be extracted
+    fn remaining(&self) -> u64 {
+        self.limits.max_size.saturating_sub(self.size)
+    }
+}
+
+/// Formats of the archives that can be counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The format of an archive by the extension of its file name
+    pub fn detect(file_name: &str) -> Option<Self> {
+        let extension = Self::extension(file_name)?;
+        extension.parse().ok()
+    }
+
+    fn extension(file_name: &str) -> Option<&str> {
+        let lower = file_name.to_ascii_lowercase();
+        [".tar.gz", ".tgz", ".tar", ".zip"]
+            .into_iter()
+            .find(|extension| lower.ends_with(extension))
+            .map(|extension| &file_name[file_name.len() - extension.len() + 1..])
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "zip" => Ok(ArchiveFormat::Zip),
+            "tar" => Ok(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            _ => Err(format!(
+                "Unknown archive format {}, use zip, tar or tar.gz",
+                s
+            )),
+        }
+    }
+}
+
+/// Name of the project of an archive, the file name without the extension
+pub fn project_name(file_name: &str) -> &str {
+    match ArchiveFormat::extension(file_name) {
+        Some(extension) => &file_name[..file_name.len() - extension.len() - 1],
+        None => file_name,
+    }
+}
+
+/// Extract an archive into `dest`, nothing is written outside of `dest`
+pub fn extract(
+    reader: impl Read + Seek,
+    format: ArchiveFormat,
+    dest: &Path,
+) -> Result<(), PstatoolError> {
+    extract_limited(reader, format, dest, ExtractLimits::default())
+}
+
+fn extract_limited(
+    reader: impl Read + Seek,
+    format: ArchiveFormat,
+    dest: &Path,
+    limits: ExtractLimits,
+) -> Result<(), PstatoolError> {
+    std::fs::create_dir_all(dest)?;
+    let mut extracted = Extracted::new(limits);
+    match format {
+        ArchiveFormat::Zip => extract_zip(reader, dest, &mut extracted),
+        ArchiveFormat::Tar => unpack_tar(tar::Archive::new(reader), dest, &mut extracted),
+        ArchiveFormat::TarGz => unpack_tar(
+            tar::Archive::new(GzDecoder::new(reader)),
+            dest,
+            &mut extracted,
+        ),
+    }
+}
+
+/// Extract the files of a zip archive, the sizes in the archive aren't trusted so the extracted
+/// bytes are counted
+fn extract_zip(
+    reader: impl Read + Seek,
+    dest: &Path,
+    extracted: &mut Extracted,
+) -> Result<(), PstatoolError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    for index in 0..archive.len() {
+        extracted.add_entry()?;
+        let mut file = archive.by_index(index)?;
+        let Some(relative) = file.enclosed_name() else {
+            let message = format!("Invalid path in archive: {}", file.name());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        };
+        let target = dest.join(relative);
+        if file.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Links are written as files with their target, so nothing is written through them
+        let mut output = std::fs::File::create(&target)?;
+        let limited = &mut (&mut file).take(extracted.remaining() + 1);
+        let size = io::copy(limited, &mut output)?;
+        extracted.add_size(size)?;
+    }
+    Ok(())
+}
+
+/// Unpack the entries of a tar archive, paths outside of `dest` are skipped
+fn unpack_tar(
+    mut archive: tar::Archive<impl Read>,
+    dest: &Path,
+    extracted: &mut Extracted,
+) -> Result<(), PstatoolError> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        extracted.add_entry()?;
+        extracted.add_size(entry.size())?;
+        entry.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+/// Extract a `.tar.gz` of a repository into `dest` without its top level folder, like the
+/// `<repository>-<commit>` folder of the tarballs of GitHub. Entries outside of the folder, like the
+/// global header with the commit, are skipped
+pub fn extract_tarball(reader: impl Read, dest: &Path) -> Result<(), PstatoolError> {
+    // The entries are unpacked with their folder next to `dest`, which is then moved into place
+    let mut staging = dest.as_os_str().to_owned();
+    staging.push(".extract");
+    let staging = PathBuf::from(staging);
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::create_dir_all(&staging)?;
+
+    let limits = ExtractLimits::default();
+    let extracted = unpack_tarball(reader, &staging, limits).and_then(|folder| {
+        match folder {
+            Some(folder) => std::fs::rename(staging.join(folder), dest)?,
+            None => std::fs::create_dir_all(dest)?,
+        }
+        Ok(())
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    extracted
+}
+
+/// Unpack the entries of a tarball into `dest`, returns the top level folder of the repository
+fn unpack_tarball(
+    reader: impl Read,
+    dest: &Path,
+    limits: ExtractLimits,
+) -> Result<Option<PathBuf>, PstatoolError> {
+    let mut folder = None;
+    let mut extracted = Extracted::new(limits);
+    let mut archive = tar::Archive::new(GzDecoder::new(reader));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        extracted.add_entry()?;
+        extracted.add_size(entry.size())?;
+        let path = entry.path()?.into_owned();
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            let message = format!("Invalid path in tarball: {}", path.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message).into());
+        }
+        let mut components = path.components();
+        let (Some(top), Some(_)) = (components.next(), components.next()) else {
+            continue;
+        };
+        let top = PathBuf::from(top.as_os_str());
+        if folder.get_or_insert_with(|| top.clone()) != &top {
+            continue;
+        }
+
+        // Also keeps links and the files below them inside `dest`
+        entry.unpack_in(dest)?;
+    }
+    Ok(folder)
+}
+
+/// Download the tarball of a commit of a public repository on GitHub and extract it into `dest`
+pub async fn download_github_tarball(
+    user: &str,
+    repository: &str,
+    commit: &str,
+    dest: &Path,
+) -> Result<(), PstatoolError> {
+    let url = format!("{}/{}/{}/tar.gz/{}", CODELOAD_URL, user, repository, commit);
+    let mut response = reqwest::Client::builder()
+        .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+        .build()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let too_large = || {
+        let message = format!(
+            "Tarball is larger than {} MB",
+            MAX_TARBALL_SIZE / 1024 / 1024
+        );
+        io::Error::new(io::ErrorKind::FileTooLarge, message)
+    };
+    if response.content_length().unwrap_or(0) > MAX_TARBALL_SIZE {
+        return Err(too_large().into());
+    }
+    let mut tarball = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (tarball.len() + chunk.len()) as u64 > MAX_TARBALL_SIZE {
+            return Err(too_large().into());
+        }
+        tarball.extend_from_slice(&chunk);
+    }
+
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || extract_tarball(&tarball[..], &dest))
+        .await
+        .map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::{
+        extract, extract_limited, extract_tarball, project_name, ArchiveFormat, ExtractLimits,
+    };
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            ArchiveFormat::detect("client-x.zip"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect("client-x.TAR.GZ"),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::detect("client-x.tgz"),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::detect("client-x.rar"), None);
+        assert_eq!(project_name("client-x.tar.gz"), "client-x");
+        assert_eq!(project_name("client-x"), "client-x");
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        archive
+            .start_file("src/main.rs", SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(b"fn main() {}\n").unwrap();
+        let archive = archive.finish().unwrap();
+
+        let root = std::env::temp_dir().join("pstatool-extract-zip");
+        let _ = std::fs::remove_dir_all(&root);
+        let dest = root.join("project");
+        extract(archive, ArchiveFormat::Zip, &dest).unwrap();
+        assert!(dest.join("src/main.rs").exists());
+
+        // Entries outside of the destination are never written
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        archive
+            .start_file("../escaped.rs", SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(b"fn main() {}\n").unwrap();
+        let archive = archive.finish().unwrap();
+        assert!(extract(archive, ArchiveFormat::Zip, &dest).is_err());
+        assert!(!root.join("escaped.rs").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_extract_limits() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            archive.write_all(&[b'a'; 1000]).unwrap();
+        }
+        let mut archive = archive.finish().unwrap();
+
+        let root = std::env::temp_dir().join("pstatool-extract-limits");
+        let _ = std::fs::remove_dir_all(&root);
+        let mut extract_with = |max_entries, max_size| {
+            archive.set_position(0);
+            let limits = ExtractLimits {
+                max_entries,
+                max_size,
+            };
+            extract_limited(&mut archive, ArchiveFormat::Zip, &root, limits)
+        };
+        assert!(extract_with(3, 3000).is_ok());
+        assert!(extract_with(2, 3000).is_err());
+        assert!(extract_with(3, 2500).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_extract_tarball() {
+        let tarball = |files: &[(&str, &[u8])]| {
+            let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            ));
+            for (path, contents) in files {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(0o644);
+                // Bypasses the path checks of the builder, like a crafted tarball
+                header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+                header.set_cksum();
+                builder.append(&header, *contents).unwrap();
+            }
+            builder.into_inner().unwrap().finish().unwrap()
+        };
+
+        let root = std::env::temp_dir().join("pstatool-extract-tarball");
+        let _ = std::fs::remove_dir_all(&root);
+        let dest = root.join("project");
+        let archive = tarball(&[
+            ("pax_global_header", b"52 comment=abc\n"),
+            ("project-abc/src/main.rs", b"fn main() {}\n"),
+        ]);
+        extract_tarball(&archive[..], &dest).unwrap();
+        assert!(dest.join("src/main.rs").exists());
+        assert!(!dest.join("pax_global_header").exists());
+
+        let archive = tarball(&[("project-abc/../../escaped.rs", b"fn main() {}\n")]);
+        assert!(extract_tarball(&archive[..], &dest).is_err());
+        assert!(!root.join("escaped.rs").exists());
+
+        // Files aren't written through a link out of the folder
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "project-abc/outside", &root)
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(13);
+        header.set_mode(0o644);
+        builder
+            .append_data(
+                &mut header,
+                "project-abc/outside/escaped.rs",
+                &b"fn main() {}\n"[..],
+            )
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+        let linked = root.join("linked");
+        assert!(extract_tarball(&archive[..], &linked).is_err());
+        assert!(!root.join("escaped.rs").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
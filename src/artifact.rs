@@ -0,0 +1,163 @@
+//! The stats of a project as JSON, written next to its card as `<project>-stats.json` so static
+//! site generators can use the numbers without a database
+
+use crate::model::{
+    CardMetadata, CardOptions, Churn, ClocData, CocomoEstimate, LanguageStats, ProjectRanking,
+    RepositoryMeta, SCHEMA_VERSION,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Lines of a language and its share of the counted lines
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageTotals {
+    pub name: String,
+    pub files: u64,
+    pub code: u64,
+    pub comment: u64,
+    pub blank: u64,
+    /// Share of the counted lines in percent, only the code lines with `code_only`
+    pub percentage: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsArtifact<'a> {
+    pub schema_version: u32,
+    /// `user/project_name`
+    pub project: String,
+    pub title: &'a str,
+    pub generated_at: DateTime<Utc>,
+    /// When the stats were counted, the time the file was generated when unknown
+    pub updated_at: DateTime<Utc>,
+    pub commit: Option<&'a str>,
+    pub total_files: u64,
+    pub total_lines: u64,
+    pub code_lines: u64,
+    pub comment_lines: u64,
+    pub blank_lines: u64,
+    /// Every language, not only the top languages of the card. The most lines first
+    pub languages: Vec<LanguageTotals>,
+    pub ranking: Option<ProjectRanking>,
+    pub repository: Option<&'a RepositoryMeta>,
+    pub estimate: Option<CocomoEstimate>,
+    pub churn: Option<Churn>,
+    pub contributors: Option<usize>,
+    /// The output of the counter, in the format of `cloc --json`
+    pub cloc: &'a ClocData,
+}
+
+impl<'a> StatsArtifact<'a> {
+    pub fn new(
+        name: String,
+        title: &'a str,
+        cloc: &'a ClocData,
+        options: &CardOptions,
+        metadata: &'a CardMetadata,
+    ) -> Self {
+        let sum =
+            |value: fn(&LanguageStats) -> u64| -> u64 { cloc.languages.values().map(value).sum() };
+        let counted: u64 = cloc
+            .languages
+            .values()
+            .map(|stats| stats.counted_lines(options.code_only))
+            .sum();
+        let languages = cloc
+            .ranked_languages()
+            .into_iter()
+            .map(|(name, stats)| LanguageTotals {
+                name: name.clone(),
+                files: stats.n_files,
+                code: stats.code,
+                comment: stats.comment,
+                blank: stats.blank,
+                percentage: stats.counted_lines(options.code_only) as f64 / counted.max(1) as f64
+                    * 100.0,
+            })
+            .collect();
+
+        let generated_at = Utc::now();
+        StatsArtifact {
+            schema_version: SCHEMA_VERSION,
+            project: name,
+            title,
+            generated_at,
+            updated_at: metadata.updated_at.unwrap_or(generated_at),
+            commit: metadata.commit.as_deref(),
+            total_files: sum(|stats| stats.n_files),
+            total_lines: sum(|stats| stats.total_lines()),
+            code_lines: sum(|stats| stats.code),
+            comment_lines: sum(|stats| stats.comment),
+            blank_lines: sum(|stats| stats.blank),
+            languages,
+            ranking: metadata.ranking,
+            repository: metadata.repository.as_ref(),
+            estimate: cloc.estimate.or(metadata.estimate),
+            churn: metadata.churn,
+            contributors: metadata.contributors,
+            cloc,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::artifact::StatsArtifact;
+    use crate::model::{CardMetadata, CardOptions, ClocData};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_stats_artifact() {
+        let cloc: ClocData = serde_json::from_str(
+            r#"{
+                "header": {"cloc_version": "2.00"},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 150}
+            }"#,
+        )
+        .unwrap();
+        let metadata = CardMetadata {
+            commit: Some("a1b2c3d".to_string()),
+            contributors: Some(2),
+            ..CardMetadata::default()
+        };
+        let artifact = StatsArtifact::new(
+            "user/project".to_string(),
+            "Project",
+            &cloc,
+            &CardOptions::default(),
+            &metadata,
+        );
+        let json = serde_json::to_value(&artifact).unwrap();
+        assert_eq!(json["project"], "user/project");
+        assert_eq!(json["commit"], "a1b2c3d");
+        assert_eq!(json["total_lines"], 250);
+        assert_eq!(json["code_lines"], 235);
+        assert_eq!(json["total_files"], 5);
+        assert_eq!(json["contributors"], 2);
+        assert_eq!(json["languages"][0]["name"], "Swift");
+        assert_eq!(json["languages"][0]["percentage"], 60.0);
+        assert_eq!(json["languages"][1]["comment"], 5);
+        assert_eq!(json["cloc"]["Rust"]["nFiles"], 4);
+        assert_eq!(artifact.updated_at, artifact.generated_at);
+
+        let options = CardOptions {
+            code_only: true,
+            ..CardOptions::default()
+        };
+        let updated_at = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        let metadata = CardMetadata {
+            updated_at: Some(updated_at),
+            ..metadata
+        };
+        let artifact = StatsArtifact::new(
+            "user/project".to_string(),
+            "Project",
+            &cloc,
+            &options,
+            &metadata,
+        );
+        assert_eq!(artifact.updated_at, updated_at);
+        let share = artifact.languages[1].percentage;
+        assert!((share - 85.0 / 235.0 * 100.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,296 @@
+//! Counting of historical commits for `pstatool backfill`, so the history of a long-lived project
+//! doesn't start at its first run
+//!
+//! The branch is cloned with its history and walked along its first parents. For every step since
+//! the start date the latest commit at that time is counted, and stored as a run at the time of
+//! the commit. Commits that already have a run are skipped, so a backfill can be repeated
+
+use crate::error::PstatoolError;
+use crate::events::Stage;
+use crate::hosting::GitCredentials;
+use crate::model::{
+    CardOptions, ClocConfig, PipelineSettings, Project, RunConfig, RunRecord, SCHEMA_VERSION,
+};
+use crate::report::ProjectStatus;
+use crate::{config, create_cloc_config, db, project_workdir, resolve_branch, run_cloc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Instant;
+
+/// Parse a step like `1d`, `2w` or `3m`, a month is 30 days
+pub fn parse_step(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let unit = s.chars().last().ok_or("The step is empty")?;
+    let count: i64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| format!("Invalid step {}, e.g. 1w", s))?;
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        _ => return Err(format!("Unknown unit of step {}, use d, w or m", s)),
+    };
+    match count > 0 {
+        true => Ok(Duration::days(count * days)),
+        false => Err(format!("The step must be positive: {}", s)),
+    }
+}
+
+/// Clone a branch with its history, the default branch when the branch doesn't exist. Returns the
+/// repository and the cloned branch
+fn clone_history(
+    repo_url: &str,
+    dest_path: &Path,
+    branch: &str,
+    credentials: &GitCredentials,
+) -> Result<(git2::Repository, String), git2::Error> {
+    let repo = git2::Repository::init(dest_path)?;
+    let mut remote = repo.remote("origin", repo_url)?;
+
+    let mut connection =
+        remote.connect_auth(git2::Direction::Fetch, Some(credentials.callbacks()), None)?;
+    let branch = resolve_branch(connection.remote(), branch)?;
+    drop(connection);
+
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(credentials.callbacks());
+    remote.fetch(
+        &[format!("refs/heads/{0}:refs/remotes/origin/{0}", branch)],
+        Some(&mut fetch_options),
+        None,
+    )?;
+    drop(remote);
+    Ok((repo, branch))
+}
+
+/// The commits to count, oldest first: the latest commit on the first-parent history of `head` at
+/// every step from `since` until `until`. Steps without a new commit are skipped
+pub fn sample_commits(
+    repo: &git2::Repository,
+    head: git2::Oid,
+    since: DateTime<Utc>,
+    step: Duration,
+    until: DateTime<Utc>,
+) -> Result<Vec<(git2::Oid, DateTime<Utc>)>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(head)?;
+    walk.simplify_first_parent()?;
+
+    let mut history = Vec::new();
+    for oid in walk {
+        let oid = oid?;
+        let time = repo.find_commit(oid)?.time().seconds();
+        history.push((oid, Utc.timestamp_opt(time, 0).single().unwrap_or(since)));
+    }
+    // Oldest first, the commit time of the first parent can be later with a skewed clock
+    history.reverse();
+
+    let mut commits: Vec<(git2::Oid, DateTime<Utc>)> = Vec::new();
+    let mut at = since;
+    while at <= until {
+        let latest = history.iter().rev().find(|(_, time)| *time <= at);
+        if let Some(&(oid, time)) = latest {
+            if commits.last().is_none_or(|(last, _)| *last != oid) {
+                commits.push((oid, time));
+            }
+        }
+        at += step;
+    }
+    Ok(commits)
+}
+
+/// Count the commits of a project since `since`, one per step, and store them as runs at the time
+/// of their commit. Returns the number of stored runs
+pub async fn backfill_project(
+    project: &Project,
+    db_url: &str,
+    since: DateTime<Utc>,
+    step: Duration,
+    settings: &PipelineSettings,
+) -> Result<usize, PstatoolError> {
+    let name = format!("{}/{}", project.github_user, project.project_name);
+    let workdir = project_workdir(&settings.temp_folder, project)?;
+    let project_path = workdir.path().join(&project.project_name);
+
+    log::info!("Cloning the history of {}", name);
+    let branch = project.branch.as_deref().unwrap_or(crate::DEFAULT_BRANCH);
+    let (repo, branch) = clone_history(
+        &project.repo_url(),
+        &project_path,
+        branch,
+        &settings.credentials,
+    )?;
+    let head = repo
+        .revparse_single(&format!("refs/remotes/origin/{}", branch))?
+        .id();
+    let commits = sample_commits(&repo, head, since, step, Utc::now())?;
+
+    let counted: HashSet<String> =
+        db::get_run_history(db_url, &project.github_user, &project.project_name)
+            .await?
+            .into_iter()
+            .filter(|run| run.total_lines.is_some())
+            .filter_map(|run| run.commit)
+            .collect();
+
+    let mut saved = 0;
+    for (oid, time) in commits {
+        let commit = oid.to_string();
+        if counted.contains(&commit) {
+            log::debug!("{} is already counted at {}", name, commit);
+            continue;
+        }
+
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout.force().remove_untracked(true);
+        repo.checkout_tree(&repo.find_object(oid, None)?, Some(&mut checkout))?;
+        repo.set_head_detached(oid)?;
+
+        // The ignores of the repository can change over time
+        let repo_config = config::load_repo_config(&project_path).unwrap_or_else(|e| {
+            log::warn!("Invalid repository config of {} at {}: {}", name, commit, e);
+            None
+        });
+        let resolved = config::resolve_project(project, repo_config.as_ref());
+        let cloc_config = ClocConfig {
+            symlinks: settings.symlinks,
+            counter: settings.counter,
+            sandbox: settings.sandbox,
+            timeout: settings.counter_timeout,
+            ..create_cloc_config(&resolved, &project_path)
+        };
+        let cloc_data = match run_cloc(cloc_config.clone()).await {
+            Ok(cloc_data) => cloc_data,
+            Err(e) => {
+                log::error!("Failed to count {} at {}: {}", name, commit, e);
+                continue;
+            }
+        };
+
+        let options = CardOptions::for_project(&resolved, &settings.card_defaults);
+        let run_config = RunConfig {
+            schema_version: SCHEMA_VERSION,
+            pstatool_version: env!("CARGO_PKG_VERSION").to_string(),
+            counter: cloc_config.counter.name().to_string(),
+            counter_version: cloc_data.header.cloc_version.clone(),
+            git_ref: branch.clone(),
+            commit: Some(commit.clone()),
+            ignored_dirs: cloc_config.ignored_dirs.clone(),
+            ignored_langs: cloc_config.ignored_langs.clone(),
+            card_style: options.style,
+            top_languages: options.top_languages,
+            min_share: options.min_share,
+            show_ranking: options.show_ranking,
+            template_dir: settings.template_dir.clone(),
+            symlinks: cloc_config.symlinks,
+            sandbox: cloc_config.sandbox,
+            warnings: vec![],
+            settings: None,
+        };
+        let run = RunRecord {
+            started_at,
+            commit: Some(commit.clone()),
+            status: ProjectStatus::Ok.as_str().to_string(),
+            error: None,
+            stage_timings: [(
+                Stage::Count.as_str().to_string(),
+                started.elapsed().as_millis() as u64,
+            )]
+            .into(),
+            created_at: Some(time),
+        };
+        db::save_run(
+            db_url,
+            &project.github_user,
+            &project.project_name,
+            &run,
+            Some((&cloc_data, &run_config)),
+        )
+        .await?;
+
+        let total_lines: u64 = cloc_data.languages.values().map(|s| s.total_lines()).sum();
+        log::info!(
+            "Counted {} at {} ({}): {} lines",
+            name,
+            &commit[..7],
+            time.format("%Y-%m-%d"),
+            total_lines
+        );
+        saved += 1;
+    }
+
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backfill::{parse_step, sample_commits};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(parse_step("1w"), Ok(Duration::days(7)));
+        assert_eq!(parse_step("3d"), Ok(Duration::days(3)));
+        assert_eq!(parse_step("2m"), Ok(Duration::days(60)));
+        assert!(parse_step("0w").is_err());
+        assert!(parse_step("1y").is_err());
+        assert!(parse_step("w").is_err());
+        assert!(parse_step("").is_err());
+    }
+
+    #[test]
+    fn test_sample_commits() {
+        let root = std::env::temp_dir().join("pstatool-backfill");
+        let _ = std::fs::remove_dir_all(&root);
+        let repo = git2::Repository::init(&root).unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+
+        // Commits on the 1st, 2nd, 3rd and 20th of January
+        let day = |d: u32| Utc.with_ymd_and_hms(2024, 1, d, 12, 0, 0).unwrap();
+        let mut commits = Vec::new();
+        for d in [1, 2, 3, 20] {
+            let time = git2::Time::new(day(d).timestamp(), 0);
+            let signature = git2::Signature::new("Test", "test@example.com", &time).unwrap();
+            let parents: Vec<git2::Commit> = commits
+                .last()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .into_iter()
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let message = format!("Day {}", d);
+            let commit = repo
+                .commit(None, &signature, &signature, &message, &tree, &parents)
+                .unwrap();
+            commits.push(commit);
+        }
+        let head = *commits.last().unwrap();
+
+        let sampled = sample_commits(&repo, head, day(1), Duration::days(7), day(31)).unwrap();
+        // The 8th has the commit of the 3rd, the 15th has no new commit
+        assert_eq!(
+            sampled,
+            vec![
+                (commits[0], day(1)),
+                (commits[2], day(3)),
+                (commits[3], day(20)),
+            ]
+        );
+
+        // Steps before the first commit are skipped
+        let sampled = sample_commits(
+            &repo,
+            head,
+            day(1) - Duration::days(14),
+            Duration::days(7),
+            day(2),
+        )
+        .unwrap();
+        assert_eq!(sampled, vec![(commits[0], day(1))]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//! Summary of a run posted to a Discord or Slack webhook, passed with `--chat-webhook`
+
+use crate::report::{format_duration, ProjectReport, ProjectStatus};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Failures listed in the message, the others are counted
+const MAX_FAILURES: usize = 10;
+/// Projects listed with the largest change of their lines
+const MAX_DELTAS: usize = 5;
+/// Discord rejects messages longer than 2000 characters
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatService {
+    Discord,
+    /// Also used for Mattermost, Rocket.Chat and other Slack compatible webhooks
+    Slack,
+}
+
+impl ChatService {
+    /// The service of a webhook URL, everything but Discord gets the Slack payload
+    pub fn from_url(url: &str) -> Self {
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split(['/', ':']).next())
+            .unwrap_or_default();
+        match host {
+            "discord.com" | "discordapp.com" => ChatService::Discord,
+            host if host.ends_with(".discord.com") => ChatService::Discord,
+            _ => ChatService::Slack,
+        }
+    }
+
+    /// The JSON body of a webhook message
+    pub fn payload(self, message: &str) -> Value {
+        match self {
+            ChatService::Discord => {
+                let content: String = match message.chars().count() > DISCORD_MAX_LENGTH {
+                    true => message
+                        .chars()
+                        .take(DISCORD_MAX_LENGTH - 1)
+                        .chain(['…'])
+                        .collect(),
+                    false => message.to_string(),
+                };
+                json!({ "content": content })
+            }
+            ChatService::Slack => json!({ "text": message }),
+        }
+    }
+}
+
+/// Message with the outcome of a run: the processed projects, the failures with their errors and
+/// the largest changes of the lines. `error` is set when the run itself failed
+pub fn run_message(reports: &[ProjectReport], duration: Duration, error: Option<&str>) -> String {
+    let count = |status| reports.iter().filter(|r| r.status == status).count();
+    let mut message = format!(
+        "pstatool processed {} projects in {}: {} ok, {} with warnings, {} failed, {} cancelled",
+        reports.len(),
+        format_duration(duration),
+        count(ProjectStatus::Ok),
+        count(ProjectStatus::Warning),
+        count(ProjectStatus::Failed),
+        count(ProjectStatus::Cancelled)
+    );
+    if let Some(error) = error {
+        let _ = write!(message, "\nThe run failed: {}", error);
+    }
+
+    let failures: Vec<&ProjectReport> = reports
+        .iter()
+        .filter(|r| r.status == ProjectStatus::Failed)
+        .collect();
+    if !failures.is_empty() {
+        message.push_str("\n\nFailures:");
+        for report in failures.iter().take(MAX_FAILURES) {
+            let _ = write!(
+                message,
+                "\n- {}: {}",
+                report.project,
+                report.errors.join("; ")
+            );
+        }
+        if failures.len() > MAX_FAILURES {
+            let _ = write!(message, "\n- and {} more", failures.len() - MAX_FAILURES);
+        }
+    }
+
+    let mut deltas: Vec<(&str, i64)> = reports
+        .iter()
+        .filter_map(|r| Some((r.project.as_str(), r.delta?)))
+        .filter(|(_, delta)| *delta != 0)
+        .collect();
+    deltas.sort_by_key(|(_, delta)| std::cmp::Reverse(delta.abs()));
+    if !deltas.is_empty() {
+        message.push_str("\n\nLargest changes:");
+        for (project, delta) in deltas.iter().take(MAX_DELTAS) {
+            let _ = write!(message, "\n- {}: {:+} lines", project, delta);
+        }
+    }
+    message
+}
+
+/// Post the message to the webhook
+pub async fn send(url: &str, message: &str) -> Result<(), reqwest::Error> {
+    crate::webhook::send(url, &ChatService::from_url(url).payload(message)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::{run_message, ChatService};
+    use crate::report::{ProjectReport, ProjectStatus};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn report(project: &str, status: ProjectStatus, delta: Option<i64>) -> ProjectReport {
+        ProjectReport {
+            project: project.to_string(),
+            status,
+            duration: Duration::from_secs(1),
+            total_lines: None,
+            delta,
+            saved: delta.is_some(),
+            warnings: vec![],
+            errors: match status {
+                ProjectStatus::Failed => vec!["Clone: repository not found".to_string()],
+                _ => vec![],
+            },
+            cards: vec![],
+        }
+    }
+
+    #[test]
+    fn test_run_message() {
+        let reports = vec![
+            report("user/small", ProjectStatus::Ok, Some(12)),
+            report("user/large", ProjectStatus::Ok, Some(-800)),
+            report("user/same", ProjectStatus::Ok, Some(0)),
+            report("user/gone", ProjectStatus::Failed, None),
+        ];
+        let message = run_message(&reports, Duration::from_secs(75), None);
+        assert_eq!(
+            message,
+            "pstatool processed 4 projects in 1m 15s: 3 ok, 0 with warnings, 1 failed, 0 cancelled\n\
+             \n\
+             Failures:\n\
+             - user/gone: Clone: repository not found\n\
+             \n\
+             Largest changes:\n\
+             - user/large: -800 lines\n\
+             - user/small: +12 lines"
+        );
+
+        let failed = run_message(&[], Duration::ZERO, Some("Database unavailable"));
+        assert!(failed.ends_with("\nThe run failed: Database unavailable"));
+    }
+
+    #[test]
+    fn test_chat_payload() {
+        let discord = "https://discord.com/api/webhooks/1/token";
+        assert_eq!(ChatService::from_url(discord), ChatService::Discord);
+        assert_eq!(
+            ChatService::from_url("https://hooks.slack.com/services/T0/B0/x"),
+            ChatService::Slack
+        );
+        assert_eq!(
+            ChatService::Slack.payload("done"),
+            json!({ "text": "done" })
+        );
+
+        let long = "x".repeat(2500);
+        let content = ChatService::Discord.payload(&long)["content"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(content.chars().count(), 2000);
+        assert!(content.ends_with('…'));
+    }
+}
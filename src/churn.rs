@@ -0,0 +1,183 @@
+use crate::error::PstatoolError;
+use crate::hosting::GitCredentials;
+use crate::model::{Churn, ClocConfig};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Churn shown on the cards is summed over this number of days
+pub const CHURN_PERIOD_DAYS: i64 = 7;
+
+/// Output of cloc with `--diff` and `--json`, only the totals are used
+#[derive(Debug, Deserialize)]
+struct ClocDiffOutput {
+    #[serde(rename = "SUM")]
+    sum: ClocDiffSum,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClocDiffSum {
+    added: ClocDiffStats,
+    removed: ClocDiffStats,
+    modified: ClocDiffStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClocDiffStats {
+    blank: u64,
+    comment: u64,
+    code: u64,
+}
+
+impl ClocDiffStats {
+    fn total_lines(&self) -> u64 {
+        self.blank + self.comment + self.code
+    }
+}
+
+fn parse_cloc_diff(output: &[u8]) -> Result<Churn, PstatoolError> {
+    let diff: ClocDiffOutput = serde_json::from_slice(output)?;
+    Ok(Churn {
+        added: diff.sum.added.total_lines(),
+        removed: diff.sum.removed.total_lines(),
+        modified: diff.sum.modified.total_lines(),
+    })
+}
+
+/// Check out the files of `commit` to `dest`, the commit is fetched when the clone doesn't have it
+pub fn checkout_commit(
+    repo_path: &Path,
+    commit: &str,
+    dest: &Path,
+    credentials: &GitCredentials,
+) -> Result<(), git2::Error> {
+    let repo = git2::Repository::open(repo_path)?;
+    let oid = git2::Oid::from_str(commit)?;
+
+    if repo.find_commit(oid).is_err() {
+        let mut remote = repo.find_remote("origin")?;
+        let mut fetch_options = git2::FetchOptions::new();
+        fetch_options
+            .depth(1)
+            .remote_callbacks(credentials.callbacks());
+        remote.fetch(&[commit], Some(&mut fetch_options), None)?;
+    }
+
+    let tree = repo.find_commit(oid)?.tree()?;
+    let mut checkout_builder = git2::build::CheckoutBuilder::new();
+    checkout_builder
+        .target_dir(dest)
+        .update_index(false)
+        .force();
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))
+}
+
+/// Count the lines changed since `previous_commit` with `cloc --diff`
+///
+/// The previous version is checked out next to the repository in `previous_path`, which is removed
+/// afterwards
+pub async fn measure_churn(
+    config: &ClocConfig,
+    previous_commit: &str,
+    previous_path: &Path,
+    credentials: &GitCredentials,
+) -> Result<Churn, PstatoolError> {
+    let churn = match checkout_commit(&config.path, previous_commit, previous_path, credentials) {
+        Ok(()) => crate::execute_cloc(config.clone(), &[], Some(previous_path))
+            .await
+            .and_then(|output| parse_cloc_diff(&output)),
+        Err(e) => Err(e.into()),
+    };
+
+    if previous_path.exists() {
+        if let Err(e) = std::fs::remove_dir_all(previous_path) {
+            log::warn!("Failed to remove {}: {}", previous_path.display(), e);
+        }
+    }
+    churn
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::churn::{checkout_commit, parse_cloc_diff};
+    use crate::hosting::GitCredentials;
+    use crate::model::Churn;
+    use std::fs;
+
+    #[test]
+    fn test_parse_cloc_diff() {
+        let output = r#"{
+            "header": {"cloc_url": "github.com/AlDanial/cloc", "cloc_version": "2.00"},
+            "added": {"Rust": {"nFiles": 1, "blank": 2, "comment": 1, "code": 20}},
+            "same": {"Rust": {"nFiles": 0, "blank": 10, "comment": 5, "code": 100}},
+            "modified": {"Rust": {"nFiles": 1, "blank": 0, "comment": 0, "code": 4}},
+            "removed": {"Rust": {"nFiles": 0, "blank": 1, "comment": 0, "code": 7}},
+            "SUM": {
+                "added": {"nFiles": 1, "blank": 2, "comment": 1, "code": 20},
+                "same": {"nFiles": 0, "blank": 10, "comment": 5, "code": 100},
+                "modified": {"nFiles": 1, "blank": 0, "comment": 0, "code": 4},
+                "removed": {"nFiles": 0, "blank": 1, "comment": 0, "code": 7}
+            }
+        }"#;
+
+        let churn = parse_cloc_diff(output.as_bytes()).unwrap();
+        assert_eq!(
+            churn,
+            Churn {
+                added: 23,
+                removed: 8,
+                modified: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_checkout_previous_commit() {
+        let base = std::env::temp_dir().join("pstatool-churn");
+        let _ = fs::remove_dir_all(&base);
+        let origin_path = base.join("origin");
+        let origin = git2::Repository::init(&origin_path).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+
+        let mut parents = vec![];
+        for content in ["fn main() {}\n", "fn main() {\n    println!();\n}\n"] {
+            fs::write(origin_path.join("main.rs"), content).unwrap();
+            let mut index = origin.index().unwrap();
+            index.add_path("main.rs".as_ref()).unwrap();
+            let tree = origin.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+            let oid = origin
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    "Commit",
+                    &tree,
+                    &parent_refs,
+                )
+                .unwrap();
+            parents = vec![origin.find_commit(oid).unwrap()];
+        }
+        let first = parents[0].parent_id(0).unwrap().to_string();
+
+        // Shallow fetches aren't supported locally, so the clone has the whole history
+        let clone_path = base.join("clone");
+        git2::Repository::clone(&origin_path.to_string_lossy(), &clone_path).unwrap();
+
+        let previous_path = base.join("previous");
+        checkout_commit(
+            &clone_path,
+            &first,
+            &previous_path,
+            &GitCredentials::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(previous_path.join("main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        // The clone itself still has the latest version
+        assert!(fs::read_to_string(clone_path.join("main.rs"))
+            .unwrap()
+            .contains("println"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Finds the cards and stored files of projects that are no longer in the `project` table, for
+//! `pstatool clean`
+//!
+//! Only files with the names pstatool writes are considered, so other files in the SVG folder like
+//! the run report are never removed
+
+use crate::output::{is_valid_name, PROJECT_FILE_SUFFIXES};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The users and projects of the `project` table
+#[derive(Debug, Default)]
+pub struct KnownProjects {
+    users: HashSet<String>,
+    projects: HashSet<(String, String)>,
+}
+
+impl KnownProjects {
+    pub fn new<'a>(projects: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut known = KnownProjects::default();
+        for (user, project_name) in projects {
+            known.users.insert(user.to_string());
+            known
+                .projects
+                .insert((user.to_string(), project_name.to_string()));
+        }
+        known
+    }
+
+    /// Whether a file of the folder of `user` belongs to a removed project, an empty user is the
+    /// root of the SVG folder with the cards of the users. Files with other names aren't orphans
+    pub fn is_orphan(&self, user: &str, file_name: &str) -> bool {
+        let mut names = PROJECT_FILE_SUFFIXES
+            .iter()
+            .filter_map(|suffix| file_name.strip_suffix(suffix))
+            .filter(|name| !name.is_empty())
+            .peekable();
+        if names.peek().is_none() {
+            return false;
+        }
+
+        // `a-badge.svg` is the card of project `a-badge` or the badge of project `a`
+        match user {
+            "" => !names.any(|name| self.users.contains(name)),
+            user => !names.any(|name| {
+                self.projects
+                    .contains(&(user.to_string(), name.to_string()))
+            }),
+        }
+    }
+
+    /// Files of the SVG folder that belong to removed projects, relative to the folder. The pack
+    /// folder of a user without projects is an orphan as a whole
+    pub fn orphaned_files(&self, svg_folder: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut orphans = Vec::new();
+        for entry in std::fs::read_dir(svg_folder)? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            let file_type = entry.file_type()?;
+            if file_type.is_file() && self.is_orphan("", &name) {
+                orphans.push(PathBuf::from(&name));
+            }
+            if !file_type.is_dir() || !is_valid_name(&name) || name.starts_with('.') {
+                continue;
+            }
+
+            for file in std::fs::read_dir(entry.path())? {
+                let file = file?;
+                let Some(file_name) = file.file_name().to_str().map(String::from) else {
+                    continue;
+                };
+                let file_type = file.file_type()?;
+                let pack = file_type.is_dir() && file_name == "pack";
+                if (pack && !self.users.contains(&name))
+                    || (file_type.is_file() && self.is_orphan(&name, &file_name))
+                {
+                    orphans.push(Path::new(&name).join(&file_name));
+                }
+            }
+        }
+        orphans.sort();
+        Ok(orphans)
+    }
+}
+
+/// Remove an orphaned file or folder of the SVG folder, or move it to the same path in `archive`.
+/// The folder of a user is removed once it's empty
+pub fn remove_orphan(svg_folder: &Path, orphan: &Path, archive: Option<&Path>) -> io::Result<()> {
+    let path = svg_folder.join(orphan);
+    match archive {
+        Some(archive) => {
+            let destination = archive.join(orphan);
+            if let Some(parent) = destination.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            if std::fs::rename(&path, &destination).is_err() {
+                // Another file system, only files are archived across them
+                std::fs::copy(&path, &destination)?;
+                std::fs::remove_file(&path)?;
+            }
+        }
+        None if path.is_dir() => std::fs::remove_dir_all(&path)?,
+        None => std::fs::remove_file(&path)?,
+    }
+
+    if let Some(parent) = path.parent().filter(|parent| *parent != svg_folder) {
+        // Fails while the folder still has files
+        let _ = std::fs::remove_dir(parent);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clean::{remove_orphan, KnownProjects};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_is_orphan() {
+        let known = KnownProjects::new([("user", "kept"), ("user", "a-badge")]);
+        assert!(!known.is_orphan("user", "kept.svg"));
+        assert!(!known.is_orphan("user", "kept.static.png"));
+        assert!(!known.is_orphan("user", "kept-shields.json"));
+        assert!(!known.is_orphan("user", "kept-stats.json"));
+        assert!(known.is_orphan("user", "removed-stats.json"));
+        assert!(known.is_orphan("user", "removed.svg"));
+        assert!(known.is_orphan("user", "removed-badge.svg"));
+        // The card of project a-badge
+        assert!(!known.is_orphan("user", "a-badge.svg"));
+        assert!(!known.is_orphan("user", "notes.txt"));
+
+        assert!(!known.is_orphan("", "user.svg"));
+        assert!(known.is_orphan("", "gone.static.svg"));
+        assert!(!known.is_orphan("", "report.html"));
+    }
+
+    #[test]
+    fn test_orphaned_files() {
+        let root = std::env::temp_dir().join("pstatool-clean");
+        let _ = std::fs::remove_dir_all(&root);
+        let svg_folder = root.join("svg");
+        for file in [
+            "user/kept.svg",
+            "user/removed.svg",
+            "user/removed-badge.png",
+            "user/pack/overview.svg",
+            "gone/project.svg",
+            "gone/pack/overview.svg",
+            "user.svg",
+            "gone.svg",
+            "index.txt",
+        ] {
+            let path = svg_folder.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "<svg/>").unwrap();
+        }
+
+        let known = KnownProjects::new([("user", "kept")]);
+        let orphans = known.orphaned_files(&svg_folder).unwrap();
+        assert_eq!(
+            orphans,
+            [
+                "gone/pack",
+                "gone/project.svg",
+                "gone.svg",
+                "user/removed-badge.png",
+                "user/removed.svg",
+            ]
+            .map(PathBuf::from)
+        );
+
+        let archive = root.join("archive");
+        for orphan in &orphans {
+            remove_orphan(&svg_folder, orphan, Some(&archive)).unwrap();
+        }
+        assert!(!svg_folder.join("gone").exists());
+        assert!(svg_folder.join("user/kept.svg").exists());
+        assert!(svg_folder.join("user/pack/overview.svg").exists());
+        assert!(archive.join("gone/pack/overview.svg").exists());
+        assert!(archive.join("user/removed.svg").exists());
+        assert!(known.orphaned_files(&svg_folder).unwrap().is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
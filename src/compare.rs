@@ -0,0 +1,251 @@
+use crate::model::{ClocData, SCHEMA_VERSION};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// Range of days, both ends included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// Label of the range on the card, the year when the range is a whole year
+    pub fn label(&self) -> String {
+        let whole_year = self.from.year() == self.to.year()
+            && self.from.ordinal() == 1
+            && self
+                .to
+                .succ_opt()
+                .is_none_or(|next| next.year() != self.to.year());
+        if whole_year {
+            self.from.year().to_string()
+        } else {
+            format!("{}..{}", self.from, self.to)
+        }
+    }
+
+    pub fn start(&self) -> DateTime<Utc> {
+        self.from.and_time(Default::default()).and_utc()
+    }
+
+    /// First moment after the range
+    pub fn end(&self) -> DateTime<Utc> {
+        self.to
+            .succ_opt()
+            .unwrap_or(NaiveDate::MAX)
+            .and_time(Default::default())
+            .and_utc()
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = String;
+
+    /// Parse a year like `2024`, or a range like `2024-01-01..2024-06-30`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let range = match s.split_once("..") {
+            Some((from, to)) => {
+                let parse = |date: &str| {
+                    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                        .map_err(|e| format!("Invalid date {}: {}", date, e))
+                };
+                DateRange {
+                    from: parse(from)?,
+                    to: parse(to)?,
+                }
+            }
+            None => {
+                let year: i32 = s
+                    .parse()
+                    .map_err(|_| format!("Invalid date range: {}", s))?;
+                let date = |month, day| {
+                    NaiveDate::from_ymd_opt(year, month, day)
+                        .ok_or_else(|| format!("Invalid year: {}", year))
+                };
+                DateRange {
+                    from: date(1, 1)?,
+                    to: date(12, 31)?,
+                }
+            }
+        };
+
+        if range.from > range.to {
+            return Err(format!("Range {} ends before it starts", s));
+        }
+        Ok(range)
+    }
+}
+
+/// Totals of all projects at the end of a range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RangeTotals {
+    pub label: String,
+    pub total_lines: u64,
+    pub total_files: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageComparison {
+    pub language: String,
+    pub before: u64,
+    pub after: u64,
+    /// Change in percent, `None` when the language is new
+    pub change: Option<f64>,
+}
+
+/// Lines per language of two date ranges
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    pub schema_version: u32,
+    pub before: RangeTotals,
+    pub after: RangeTotals,
+    /// Sorted by the largest of the two line counts
+    pub languages: Vec<LanguageComparison>,
+}
+
+fn change(before: u64, after: u64) -> Option<f64> {
+    (before > 0).then(|| (after as f64 - before as f64) / before as f64 * 100.0)
+}
+
+/// Total lines of all languages
+pub fn total_lines(stats: &ClocData) -> u64 {
+    stats.languages.values().map(|s| s.total_lines()).sum()
+}
+
+fn totals(label: String, stats: &ClocData) -> RangeTotals {
+    RangeTotals {
+        label,
+        total_lines: total_lines(stats),
+        total_files: stats.languages.values().map(|s| s.n_files).sum(),
+    }
+}
+
+/// The total lines of every language of either stats as `(language, before, after)`, by name. A
+/// language that is missing from one of them has 0 lines there
+pub fn language_lines(before: &ClocData, after: &ClocData) -> Vec<(String, u64, u64)> {
+    let lines = |stats: &ClocData, language: &str| {
+        stats
+            .languages
+            .get(language)
+            .map(|s| s.total_lines())
+            .unwrap_or(0)
+    };
+
+    let names: BTreeSet<&String> = before
+        .languages
+        .keys()
+        .chain(after.languages.keys())
+        .collect();
+    names
+        .into_iter()
+        .map(|language| {
+            (
+                language.clone(),
+                lines(before, language),
+                lines(after, language),
+            )
+        })
+        .collect()
+}
+
+/// Compare the stats of two ranges per language
+pub fn compare(
+    before: &DateRange,
+    before_stats: &ClocData,
+    after: &DateRange,
+    after_stats: &ClocData,
+) -> Comparison {
+    let mut languages: Vec<LanguageComparison> = language_lines(before_stats, after_stats)
+        .into_iter()
+        .map(|(language, before, after)| LanguageComparison {
+            language,
+            before,
+            after,
+            change: change(before, after),
+        })
+        .collect();
+    languages.sort_by_key(|l| std::cmp::Reverse(l.before.max(l.after)));
+
+    Comparison {
+        schema_version: SCHEMA_VERSION,
+        before: totals(before.label(), before_stats),
+        after: totals(after.label(), after_stats),
+        languages,
+    }
+}
+
+impl Comparison {
+    /// Change of the total lines in percent
+    pub fn change(&self) -> Option<f64> {
+        change(self.before.total_lines, self.after.total_lines)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::compare::{compare, DateRange};
+    use crate::model::{ClocData, LanguageStats};
+    use chrono::NaiveDate;
+
+    /// Stats with a file of `code` lines per language
+    pub(crate) fn stats(languages: &[(&str, u64)]) -> ClocData {
+        let mut data = ClocData::default();
+        for (language, code) in languages {
+            data.languages.insert(
+                language.to_string(),
+                LanguageStats {
+                    n_files: 1,
+                    code: *code,
+                    ..LanguageStats::default()
+                },
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let year: DateRange = "2024".parse().unwrap();
+        assert_eq!(year.from, NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        assert_eq!(year.to, NaiveDate::from_ymd_opt(2024, 12, 31).unwrap());
+        assert_eq!(year.label(), "2024");
+        assert_eq!(year.end().to_rfc3339(), "2025-01-01T00:00:00+00:00");
+
+        let range: DateRange = "2024-01-01..2024-06-30".parse().unwrap();
+        assert_eq!(range.label(), "2024-01-01..2024-06-30");
+
+        assert!("2024-06-30..2024-01-01".parse::<DateRange>().is_err());
+        assert!("last year".parse::<DateRange>().is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let comparison = compare(
+            &"2023".parse().unwrap(),
+            &stats(&[("Rust", 1000), ("Java", 500)]),
+            &"2024".parse().unwrap(),
+            &stats(&[("Rust", 1500), ("Kotlin", 2000)]),
+        );
+
+        assert_eq!(comparison.schema_version, 1);
+        assert_eq!(comparison.before.total_lines, 1500);
+        assert_eq!(comparison.after.total_lines, 3500);
+        let languages: Vec<(&str, Option<f64>)> = comparison
+            .languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.change))
+            .collect();
+        assert_eq!(
+            languages,
+            vec![
+                ("Kotlin", None),
+                ("Rust", Some(50.0)),
+                ("Java", Some(-100.0))
+            ]
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Resolution of the settings of a project from its sources
+//!
+//! Precedence, from high to low:
+//! 1. The `project` row in the database, set by the operator of pstatool
+//! 2. The `.pstatool.yml` file in the root of the repository, set by the owner of the repository
+//! 3. The command line flags (or env variables), the defaults for all projects
+//! 4. The built-in defaults
+//!
+//! Ignored directories and languages are not overridden but combined from all sources, and with
+//! the [DefaultIgnores] unless the project turns off `default_ignores`.
+
+use crate::error::PstatoolError;
+use crate::model::{CardOptions, Project};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+pub const REPO_CONFIG_FILE: &str = ".pstatool.yml";
+
+/// Built-in directories ignored by every project
+pub const DEFAULT_IGNORED_DIRS: [&str; 4] = ["target", ".idea", ".git", ".build"];
+/// Built-in languages ignored by every project, data and build files rather than code
+pub const DEFAULT_IGNORED_LANGS: [&str; 8] = [
+    "JSON",
+    "Markdown",
+    "Maven",
+    "Properties",
+    "SVG",
+    "TOML",
+    "XML",
+    "YAML",
+];
+
+static DEFAULT_IGNORES: OnceCell<DefaultIgnores> = OnceCell::new();
+
+/// Directories and languages ignored by every project, next to their own ignores
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefaultIgnores {
+    pub dirs: Vec<String>,
+    pub langs: Vec<String>,
+}
+
+impl Default for DefaultIgnores {
+    fn default() -> Self {
+        DefaultIgnores {
+            dirs: DEFAULT_IGNORED_DIRS.map(String::from).to_vec(),
+            langs: DEFAULT_IGNORED_LANGS.map(String::from).to_vec(),
+        }
+    }
+}
+
+impl DefaultIgnores {
+    /// The ignored directories and languages of a project: the defaults, unless the project turned
+    /// them off, followed by its own
+    pub fn apply(&self, project: &Project) -> (Vec<String>, Vec<String>) {
+        let use_defaults = project.default_ignores != Some(false);
+        let list = |defaults: &[String], own: &Option<String>| {
+            let defaults = defaults.iter().filter(|_| use_defaults).map(String::as_str);
+            normalize_list(defaults.chain(own.iter().flat_map(|own| own.split(','))))
+        };
+        (
+            list(&self.dirs, &project.ignored_dirs),
+            list(&self.langs, &project.ignored_langs),
+        )
+    }
+}
+
+/// Trim the values and remove the empty ones and the ones that differ only in case from an earlier
+/// value
+pub fn normalize_list<'a>(values: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for value in values.into_iter().map(str::trim) {
+        if !value.is_empty() && !normalized.iter().any(|v| v.eq_ignore_ascii_case(value)) {
+            normalized.push(value.to_string());
+        }
+    }
+    normalized
+}
+
+/// Check the ignored languages against the languages `known` to the counter. Languages that only
+/// differ in case are replaced with the known name, as the counter compares them exactly. Returns
+/// a warning for every language that was replaced or is unknown
+pub fn check_ignored_langs(langs: &mut [String], known: &[String]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for lang in langs.iter_mut() {
+        if known.contains(lang) {
+            continue;
+        }
+        match known.iter().find(|k| k.eq_ignore_ascii_case(lang)) {
+            Some(name) => {
+                warnings.push(format!("Ignored language {} is spelled {}", lang, name));
+                *lang = name.clone();
+            }
+            None => warnings.push(format!(
+                "Ignored language {} is not a language of cloc",
+                lang
+            )),
+        }
+    }
+    warnings
+}
+
+/// Replace the built-in default ignores, e.g. with the ones of the command line
+pub fn init_default_ignores(ignores: DefaultIgnores) {
+    if DEFAULT_IGNORES.set(ignores).is_err() {
+        log::warn!("Default ignores were already initialized");
+    }
+}
+
+/// The default ignores, the built-in ones unless they were replaced
+pub fn default_ignores() -> &'static DefaultIgnores {
+    DEFAULT_IGNORES.get_or_init(DefaultIgnores::default)
+}
+
+/// Settings a repository can set for its own card in `.pstatool.yml`
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub ignored_dirs: Vec<String>,
+    #[serde(default)]
+    pub ignored_langs: Vec<String>,
+    pub top_languages: Option<i32>,
+    pub card_style: Option<String>,
+    pub show_ranking: Option<bool>,
+    /// Whether the default ignores apply to the repository
+    pub default_ignores: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    CommandLine,
+    Repository,
+    Database,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Source::Default => "default",
+            Source::CommandLine => "command line",
+            Source::Repository => REPO_CONFIG_FILE,
+            Source::Database => "database",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Read the config file in the root of the repository, `None` when the repository has none
+pub fn load_repo_config(repo_path: &Path) -> Result<Option<RepoConfig>, PstatoolError> {
+    let path = repo_path.join(REPO_CONFIG_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)?;
+    Ok(Some(serde_yaml::from_str(&contents)?))
+}
+
+/// The project with the settings of the repository applied where the database has none
+pub fn resolve_project(project: &Project, repo: Option<&RepoConfig>) -> Project {
+    let Some(repo) = repo else {
+        return project.clone();
+    };
+
+    let mut resolved = project.clone();
+    if resolved.title.trim().is_empty() {
+        if let Some(title) = &repo.title {
+            resolved.title = title.clone();
+        }
+    }
+    resolved.ignored_dirs = combine(&project.ignored_dirs, &repo.ignored_dirs);
+    resolved.ignored_langs = combine(&project.ignored_langs, &repo.ignored_langs);
+    resolved.top_languages = project.top_languages.or(repo.top_languages);
+    resolved.card_style = project.card_style.clone().or(repo.card_style.clone());
+    resolved.show_ranking = project.show_ranking.or(repo.show_ranking);
+    resolved.default_ignores = project.default_ignores.or(repo.default_ignores);
+    resolved
+}
+
+/// Add the values of the repository to a comma separated list of the database
+fn combine(database: &Option<String>, repo: &[String]) -> Option<String> {
+    let values = normalize_list(
+        database
+            .iter()
+            .flat_map(|list| list.split(','))
+            .chain(repo.iter().map(String::as_str)),
+    );
+
+    (!values.is_empty()).then(|| values.join(","))
+}
+
+/// Describe every setting of the project with its value and the source it came from
+pub fn explain(project: &Project, repo: Option<&RepoConfig>, defaults: &CardOptions) -> String {
+    let builtin = CardOptions::default();
+    let source = |database: bool, repository: bool, command_line: bool| {
+        if database {
+            Source::Database
+        } else if repository {
+            Source::Repository
+        } else if command_line {
+            Source::CommandLine
+        } else {
+            Source::Default
+        }
+    };
+    let empty = RepoConfig::default();
+    let repo_config = repo.unwrap_or(&empty);
+    let resolved = resolve_project(project, repo);
+    let options = CardOptions::for_project(&resolved, defaults);
+
+    let mut lines = vec![format!(
+        "Settings of {}/{}",
+        project.github_user, project.project_name
+    )];
+    let mut line = |name: &str, value: String, source: Source| {
+        lines.push(format!("  {:<14} {:<30} ({})", name, value, source))
+    };
+
+    let title_source = if !project.title.trim().is_empty() {
+        Source::Database
+    } else if repo_config.title.is_some() {
+        Source::Repository
+    } else {
+        Source::Default
+    };
+    line("title", resolved.title.clone(), title_source);
+    line(
+        "top_languages",
+        options.top_languages.to_string(),
+        source(
+            project.top_languages.is_some(),
+            repo_config.top_languages.is_some(),
+            defaults.top_languages != builtin.top_languages,
+        ),
+    );
+    line(
+        "card_style",
+        format!("{:?}", options.style).to_lowercase(),
+        source(
+            project.card_style.is_some(),
+            repo_config.card_style.is_some(),
+            defaults.style != builtin.style,
+        ),
+    );
+    line(
+        "show_ranking",
+        options.show_ranking.to_string(),
+        source(
+            project.show_ranking.is_some(),
+            repo_config.show_ranking.is_some(),
+            defaults.show_ranking != builtin.show_ranking,
+        ),
+    );
+    let ignores = default_ignores();
+    line(
+        "default_ignores",
+        resolved.default_ignores.unwrap_or(true).to_string(),
+        source(
+            project.default_ignores.is_some(),
+            repo_config.default_ignores.is_some(),
+            *ignores != DefaultIgnores::default(),
+        ),
+    );
+
+    let mut list = |name: &str, database: &Option<String>, repository: &[String]| {
+        let database = combine(database, &[]);
+        let repository = combine(&None, repository);
+        if database.is_none() && repository.is_none() {
+            line(name, "-".to_string(), Source::Default);
+        }
+        if let Some(values) = database {
+            line(name, values, Source::Database);
+        }
+        if let Some(values) = repository {
+            line(name, values, Source::Repository);
+        }
+    };
+    list(
+        "ignored_dirs",
+        &project.ignored_dirs,
+        &repo_config.ignored_dirs,
+    );
+    list(
+        "ignored_langs",
+        &project.ignored_langs,
+        &repo_config.ignored_langs,
+    );
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{
+        check_ignored_langs, explain, load_repo_config, normalize_list, resolve_project,
+        DefaultIgnores, RepoConfig,
+    };
+    use crate::model::{CardOptions, CardStyle, Project};
+
+    fn test_project() -> Project {
+        Project {
+            github_user: "user".to_string(),
+            project_name: "project".to_string(),
+            title: "Project".to_string(),
+            ignored_dirs: Some("vendor, build".to_string()),
+            card_style: Some("donut".to_string()),
+            ..Project::default()
+        }
+    }
+
+    fn test_repo_config() -> RepoConfig {
+        serde_yaml::from_str(
+            r#"
+            title: From repository
+            ignored_dirs: [docs]
+            ignored_langs: [Markdown]
+            top_languages: 4
+            card_style: treemap
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_database_takes_precedence() {
+        let resolved = resolve_project(&test_project(), Some(&test_repo_config()));
+
+        assert_eq!(resolved.title, "Project");
+        assert_eq!(resolved.card_style.as_deref(), Some("donut"));
+        assert_eq!(resolved.top_languages, Some(4));
+        assert_eq!(resolved.show_ranking, None);
+    }
+
+    #[test]
+    fn test_ignores_are_combined() {
+        let resolved = resolve_project(&test_project(), Some(&test_repo_config()));
+
+        assert_eq!(resolved.ignored_dirs.as_deref(), Some("vendor,build,docs"));
+        assert_eq!(resolved.ignored_langs.as_deref(), Some("Markdown"));
+    }
+
+    #[test]
+    fn test_default_ignores() {
+        let defaults = DefaultIgnores {
+            dirs: vec!["vendor".to_string()],
+            langs: vec!["Markdown".to_string(), "JSON".to_string()],
+        };
+        let (dirs, langs) = defaults.apply(&test_project());
+        assert_eq!(dirs, vec!["vendor", "build"]);
+        assert_eq!(langs, vec!["Markdown", "JSON"]);
+
+        let project = Project {
+            ignored_langs: Some("XML".to_string()),
+            default_ignores: Some(false),
+            ..test_project()
+        };
+        let (dirs, langs) = defaults.apply(&project);
+        assert_eq!(dirs, vec!["vendor", "build"]);
+        assert_eq!(langs, vec!["XML"]);
+
+        let repo: RepoConfig = serde_yaml::from_str("default_ignores: false").unwrap();
+        let resolved = resolve_project(&test_project(), Some(&repo));
+        assert_eq!(resolved.default_ignores, Some(false));
+        assert!(defaults.apply(&resolved).1.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_list() {
+        assert_eq!(
+            normalize_list(" docs ,build,,Docs, target , build".split(',')),
+            vec!["docs", "build", "target"]
+        );
+        let defaults = DefaultIgnores {
+            dirs: vec!["target".to_string()],
+            langs: vec!["Markdown".to_string(), "JSON".to_string()],
+        };
+        let project = Project {
+            ignored_langs: Some("json, XML ,markdown".to_string()),
+            ..test_project()
+        };
+        assert_eq!(defaults.apply(&project).1, vec!["Markdown", "JSON", "XML"]);
+    }
+
+    #[test]
+    fn test_check_ignored_langs() {
+        let known: Vec<String> = ["JavaScript", "Rust"].map(String::from).to_vec();
+        let mut langs: Vec<String> = ["Rust", "Javascript", "Rusty"].map(String::from).to_vec();
+        assert_eq!(
+            check_ignored_langs(&mut langs, &known),
+            vec![
+                "Ignored language Javascript is spelled JavaScript",
+                "Ignored language Rusty is not a language of cloc",
+            ]
+        );
+        assert_eq!(langs, vec!["Rust", "JavaScript", "Rusty"]);
+    }
+
+    #[test]
+    fn test_without_repo_config() {
+        let project = test_project();
+        assert_eq!(resolve_project(&project, None), project);
+
+        let dir = std::env::temp_dir().join("pstatool-no-repo-config");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(load_repo_config(&dir).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let result: Result<RepoConfig, _> = serde_yaml::from_str("colour: red");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let defaults = CardOptions {
+            show_ranking: true,
+            style: CardStyle::Bar,
+            ..CardOptions::default()
+        };
+        let explanation = explain(&test_project(), Some(&test_repo_config()), &defaults);
+        let line = |name: &str| {
+            explanation
+                .lines()
+                .find(|line| line.trim_start().starts_with(name))
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(line("card_style").contains("donut"));
+        assert!(line("card_style").ends_with("(database)"));
+        assert!(line("top_languages").ends_with("(.pstatool.yml)"));
+        assert!(line("show_ranking").ends_with("(command line)"));
+        assert!(explanation.contains("docs"));
+    }
+}
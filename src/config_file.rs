@@ -0,0 +1,190 @@
+//! Settings and projects in a TOML or YAML file, passed with `--config`
+//!
+//! The command line flags and env variables take precedence over the file, the file over the
+//! built-in defaults. Projects in the file are processed instead of the projects in the database.
+
+use crate::error::PstatoolError;
+use crate::model::{CardSettings, CardStyle, Project};
+use crate::pack::PackConfig;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub db_url: Option<String>,
+    pub svg_folder: Option<PathBuf>,
+    pub temp_folder: Option<PathBuf>,
+    pub template_dir: Option<PathBuf>,
+    pub card_style: Option<CardStyle>,
+    /// Discord or Slack webhook that receives the summary of every run
+    pub chat_webhook: Option<String>,
+    /// Ignored by every project in the file, next to their own ignored directories
+    #[serde(default)]
+    pub ignored_dirs: Vec<String>,
+    /// Ignored by every project in the file, next to their own ignored languages
+    #[serde(default)]
+    pub ignored_langs: Vec<String>,
+    /// Replace the built-in default ignored directories, projects can turn them off
+    pub default_ignored_dirs: Option<Vec<String>>,
+    /// Replace the built-in default ignored languages, projects can turn them off
+    pub default_ignored_langs: Option<Vec<String>>,
+    /// Colors of languages by their cloc name, e.g. `Rust: "#dea584"`, instead of the linguist colors
+    #[serde(default)]
+    pub language_colors: HashMap<String, String>,
+    #[serde(default)]
+    pub projects: Vec<ProjectEntry>,
+    /// Card packs rendered by `pstatool pack`
+    #[serde(default)]
+    pub packs: Vec<PackConfig>,
+}
+
+/// A project in the config file, with lists instead of the comma separated columns
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectEntry {
+    pub user: String,
+    pub name: String,
+    /// The name of the project by default
+    pub title: Option<String>,
+    pub branch: Option<String>,
+    pub host: Option<String>,
+    #[serde(default)]
+    pub ignored_dirs: Vec<String>,
+    #[serde(default)]
+    pub ignored_langs: Vec<String>,
+    pub top_languages: Option<i32>,
+    pub card_style: Option<String>,
+    pub show_ranking: Option<bool>,
+    pub card_settings: Option<CardSettings>,
+    /// Tag or commit to count instead of the head of the branch
+    pub pinned_ref: Option<String>,
+    /// Branches counted as separate projects instead of `branch`
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Clone and count the submodules of the repository too
+    pub submodules: Option<bool>,
+    /// Whether the default ignored directories and languages apply, true by default
+    pub default_ignores: Option<bool>,
+    /// Set to false to skip the project without removing it from the file
+    pub enabled: Option<bool>,
+}
+
+impl ConfigFile {
+    /// The projects of the file with the default ignores of the file added
+    pub fn projects(&self) -> Vec<Project> {
+        let list = |defaults: &[String], own: &[String]| {
+            let values: Vec<&str> = defaults.iter().chain(own).map(|v| v.as_str()).collect();
+            (!values.is_empty()).then(|| values.join(","))
+        };
+
+        self.projects
+            .iter()
+            .filter(|entry| entry.enabled != Some(false))
+            .map(|entry| Project {
+                github_user: entry.user.clone(),
+                project_name: entry.name.clone(),
+                title: entry.title.clone().unwrap_or_else(|| entry.name.clone()),
+                ignored_dirs: list(&self.ignored_dirs, &entry.ignored_dirs),
+                ignored_langs: list(&self.ignored_langs, &entry.ignored_langs),
+                top_languages: entry.top_languages,
+                card_style: entry.card_style.clone(),
+                show_ranking: entry.show_ranking,
+                branch: entry.branch.clone(),
+                host: entry.host.clone(),
+                card_settings: entry
+                    .card_settings
+                    .as_ref()
+                    .and_then(|settings| serde_json::to_value(settings).ok()),
+                pinned_ref: entry.pinned_ref.clone(),
+                branches: list(&[], &entry.branches),
+                parent: None,
+                submodules: entry.submodules,
+                default_ignores: entry.default_ignores,
+            })
+            .collect()
+    }
+}
+
+/// Read a config file, `.toml` files are TOML and all other files YAML
+pub fn load(path: &Path) -> Result<ConfigFile, PstatoolError> {
+    let contents = std::fs::read_to_string(path)?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        Ok(toml::from_str(&contents)?)
+    } else {
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config_file::{load, ConfigFile};
+    use crate::model::CardStyle;
+
+    #[test]
+    fn test_toml_config() {
+        let config: ConfigFile = toml::from_str(
+            r##"
+            svg_folder = "cards"
+            card_style = "donut"
+            ignored_dirs = ["vendor"]
+
+            [language_colors]
+            Frobnicate = "#ff8800"
+
+            [[projects]]
+            user = "wdudokvanheel"
+            name = "pstatool"
+            title = "Project Statistics"
+            ignored_dirs = ["assets"]
+
+            [[projects]]
+            user = "wdudokvanheel"
+            name = "babycare"
+            branch = "develop"
+
+            [[projects]]
+            user = "wdudokvanheel"
+            name = "broken"
+            enabled = false
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.db_url, None);
+        assert_eq!(config.card_style, Some(CardStyle::Donut));
+        assert_eq!(config.language_colors["Frobnicate"], "#ff8800");
+
+        let projects = config.projects();
+        assert_eq!(projects[0].title, "Project Statistics");
+        assert_eq!(projects[0].ignored_dirs.as_deref(), Some("vendor,assets"));
+        assert_eq!(projects[0].ignored_langs, None);
+        assert_eq!(projects[1].title, "babycare");
+        assert_eq!(projects[1].branch.as_deref(), Some("develop"));
+        assert_eq!(projects.len(), 2);
+    }
+
+    #[test]
+    fn test_yaml_config() {
+        let path = std::env::temp_dir().join("pstatool-test-config.yml");
+        std::fs::write(
+            &path,
+            "db_url: postgresql://localhost/pstatool\nprojects:\n  - user: octocat\n    name: hello-world\n",
+        )
+        .unwrap();
+        let config = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            config.db_url.as_deref(),
+            Some("postgresql://localhost/pstatool")
+        );
+        assert_eq!(config.projects()[0].github_user, "octocat");
+
+        // Typos are reported instead of ignored
+        assert!(serde_yaml::from_str::<ConfigFile>("svg_foldr: cards").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Commits per author of a clone, the equivalent of `git shortlog -sne HEAD`
+//!
+//! Shallow clones only have the fetched commits, so the contributors are only complete when the
+//! whole history is cloned with `--clone-depth 0`
+
+use crate::model::Contributor;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The authors of the commits reachable from the checked out commit, with the mailmap of the
+/// repository applied. Sorted by commits, the most active author first
+pub fn shortlog(repo_path: &Path) -> Result<Vec<Contributor>, git2::Error> {
+    let repo = git2::Repository::open(repo_path)?;
+    let mailmap = repo.mailmap()?;
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+
+    let mut commits: HashMap<(String, String), u64> = HashMap::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        let author = commit.author_with_mailmap(&mailmap)?;
+        let key = (
+            String::from_utf8_lossy(author.name_bytes()).to_string(),
+            String::from_utf8_lossy(author.email_bytes()).to_string(),
+        );
+        *commits.entry(key).or_default() += 1;
+    }
+
+    let mut contributors: Vec<Contributor> = commits
+        .into_iter()
+        .map(|((name, email), commits)| Contributor {
+            name,
+            email,
+            commits,
+        })
+        .collect();
+    contributors.sort_by(|a, b| {
+        b.commits
+            .cmp(&a.commits)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.email.cmp(&b.email))
+    });
+    Ok(contributors)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::contributors::shortlog;
+    use crate::model::Contributor;
+
+    #[test]
+    fn test_shortlog() {
+        let root = std::env::temp_dir().join("pstatool-contributors");
+        let _ = std::fs::remove_dir_all(&root);
+        let repo = git2::Repository::init(&root).unwrap();
+        std::fs::write(
+            root.join(".mailmap"),
+            "Alice <alice@example.com> <alice@old.example.com>\n",
+        )
+        .unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new(".mailmap")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let authors = [
+            ("Alice", "alice@old.example.com"),
+            ("Bob", "bob@example.com"),
+            ("Alice", "alice@example.com"),
+            ("Alice", "alice@example.com"),
+        ];
+        let mut parent: Option<git2::Oid> = None;
+        for (name, email) in authors {
+            let signature = git2::Signature::now(name, email).unwrap();
+            let parents: Vec<git2::Commit> = parent
+                .map(|id| repo.find_commit(id).unwrap())
+                .into_iter()
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    "Commit",
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            parent = Some(commit);
+        }
+
+        let contributor = |name: &str, email: &str, commits| Contributor {
+            name: name.to_string(),
+            email: email.to_string(),
+            commits,
+        };
+        assert_eq!(
+            shortlog(&root).unwrap(),
+            vec![
+                contributor("Alice", "alice@example.com", 3),
+                contributor("Bob", "bob@example.com", 1),
+            ]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,569 @@
+use crate::error::PstatoolError;
+use crate::hosting::HostKind;
+use crate::model::{Project, RepositoryMeta};
+use crate::throttle::{rate_limit_wait, throttle};
+use crate::{db, gitea, DEFAULT_BRANCH};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::SystemTime;
+use tokio_util::sync::CancellationToken;
+
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Maximum page size of the GitHub API
+const PAGE_SIZE: usize = 100;
+
+/// Times a request is sent again after it hit the rate limit
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// The fields of a repository in the GitHub API that are used, repositories of other forges are
+/// converted to it
+#[derive(Debug, Deserialize)]
+pub struct GithubRepo {
+    pub name: String,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default)]
+    pub archived: bool,
+    pub default_branch: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Size of the repository in KB
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub stargazers_count: i32,
+    #[serde(default)]
+    pub forks_count: i32,
+    pub pushed_at: Option<DateTime<Utc>>,
+}
+
+/// Service that hosts the repositories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Forge {
+    /// GitHub, with the URL of its API
+    Github { api_url: String },
+    /// A Gitea or Forgejo instance, with the URL of its web interface
+    Gitea { url: String },
+}
+
+impl Forge {
+    pub fn github() -> Self {
+        Forge::Github {
+            api_url: GITHUB_API_URL.to_string(),
+        }
+    }
+
+    /// The forge hosting the repository of a project, `None` for hosts without a supported API
+    pub fn for_project(project: &Project) -> Option<Self> {
+        match &project.host {
+            None => Some(Forge::github()),
+            Some(host) if HostKind::detect(host) == HostKind::Standard => {
+                Some(Forge::Gitea { url: host.clone() })
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Host of the projects of this forge, `None` for GitHub
+    fn host(&self) -> Option<String> {
+        match self {
+            Forge::Github { .. } => None,
+            Forge::Gitea { url } => Some(url.trim_end_matches('/').to_string()),
+        }
+    }
+
+    pub async fn get_repo(
+        &self,
+        user: &str,
+        project_name: &str,
+        token: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<GithubRepo, PstatoolError> {
+        match self {
+            Forge::Github { api_url } => get_repo(api_url, user, project_name, token, cancel).await,
+            Forge::Gitea { url } => Ok(gitea::get_repo(url, user, project_name, token).await?),
+        }
+    }
+
+    pub async fn list_repos(
+        &self,
+        owner: &Owner,
+        token: Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<GithubRepo>, PstatoolError> {
+        match self {
+            Forge::Github { api_url } => list_repos(api_url, owner, token, cancel).await,
+            Forge::Gitea { url } => Ok(gitea::list_repos(url, owner, token).await?),
+        }
+    }
+}
+
+/// Owner of the discovered repositories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Owner {
+    User(String),
+    Organization(String),
+}
+
+impl Owner {
+    pub fn name(&self) -> &str {
+        match self {
+            Owner::User(name) | Owner::Organization(name) => name,
+        }
+    }
+
+    fn repos_path(&self) -> String {
+        match self {
+            Owner::User(name) => format!("users/{}/repos", name),
+            Owner::Organization(name) => format!("orgs/{}/repos", name),
+        }
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Public,
+    Private,
+    All,
+}
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "public" => Ok(Visibility::Public),
+            "private" => Ok(Visibility::Private),
+            "all" => Ok(Visibility::All),
+            _ => Err(format!("Unknown visibility: {}", s)),
+        }
+    }
+}
+
+/// Which repositories are tracked, forks and archived repositories never are
+#[derive(Debug, Clone, Default)]
+pub struct DiscoverFilter {
+    /// The repository needs at least one of these topics, any repository matches when empty
+    pub topics: Vec<String>,
+    pub visibility: Visibility,
+    /// Minimum size of the repository in KB
+    pub min_size: u64,
+}
+
+impl DiscoverFilter {
+    pub fn matches(&self, repo: &GithubRepo) -> bool {
+        let visible = match self.visibility {
+            Visibility::Public => !repo.private,
+            Visibility::Private => repo.private,
+            Visibility::All => true,
+        };
+        let topic = self.topics.is_empty()
+            || self
+                .topics
+                .iter()
+                .any(|topic| repo.topics.iter().any(|t| t.eq_ignore_ascii_case(topic)));
+
+        !repo.fork && !repo.archived && visible && topic && repo.size >= self.min_size
+    }
+}
+
+/// Result of discovering the repositories of an owner
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// New projects of repositories that match the filter
+    pub added: Vec<Project>,
+    /// Names of projects that were disabled, as their repository was archived or removed
+    pub disabled: Vec<String>,
+}
+
+impl GithubRepo {
+    pub fn meta(&self) -> RepositoryMeta {
+        RepositoryMeta {
+            stars: self.stargazers_count,
+            forks: self.forks_count,
+            pushed_at: self.pushed_at,
+        }
+    }
+
+    /// New project for this repository with the default settings
+    pub fn to_project(&self, user: &str) -> Project {
+        Project {
+            github_user: user.to_string(),
+            project_name: self.name.clone(),
+            title: self.name.clone(),
+            branch: self
+                .default_branch
+                .clone()
+                .filter(|branch| branch != DEFAULT_BRANCH),
+            ..Project::default()
+        }
+    }
+}
+
+/// GET request to the GitHub API, authenticated when there is a token
+fn api_request(
+    client: &reqwest::Client,
+    url: String,
+    token: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let request = client
+        .get(url)
+        .header("Accept", "application/vnd.github+json");
+    match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+}
+
+/// Send a request to the GitHub API after the request delay. Requests rejected by the rate limit
+/// are sent again once it resets, and an exhausted rate limit holds back the following requests.
+/// Fails when `cancel` stops the wait
+pub(crate) async fn send(
+    request: reqwest::RequestBuilder,
+    cancel: &CancellationToken,
+) -> Result<reqwest::Response, PstatoolError> {
+    let throttle = throttle();
+    let mut retries = 0;
+    loop {
+        if !throttle.request_slot(cancel).await {
+            let message = "Cancelled while waiting for the GitHub API rate limit";
+            return Err(io::Error::new(io::ErrorKind::Interrupted, message).into());
+        }
+        let Some(attempt) = request.try_clone() else {
+            return Ok(request.send().await?);
+        };
+        let response = attempt.send().await?;
+
+        let Some(wait) = rate_limit_wait(response.status(), response.headers(), SystemTime::now())
+        else {
+            return Ok(response);
+        };
+        throttle.postpone(wait).await;
+        if response.status().is_success() || retries >= RATE_LIMIT_RETRIES {
+            return Ok(response);
+        }
+        log::warn!("GitHub API rate limit exceeded, retrying in {:?}", wait);
+        retries += 1;
+    }
+}
+
+pub(crate) fn api_client() -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Get a single repository
+pub async fn get_repo(
+    api_url: &str,
+    user: &str,
+    project_name: &str,
+    token: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<GithubRepo, PstatoolError> {
+    let url = format!("{}/repos/{}/{}", api_url, user, project_name);
+    let repo = send(api_request(&api_client()?, url, token), cancel)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(repo)
+}
+
+/// Login of the user the token belongs to
+async fn authenticated_user(
+    client: &reqwest::Client,
+    api_url: &str,
+    token: &str,
+    cancel: &CancellationToken,
+) -> Result<String, PstatoolError> {
+    #[derive(Deserialize)]
+    struct User {
+        login: String,
+    }
+
+    let url = format!("{}/user", api_url);
+    let user: User = send(api_request(client, url, Some(token)), cancel)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(user.login)
+}
+
+/// List all repositories of a GitHub user or organization
+pub async fn list_repos(
+    api_url: &str,
+    owner: &Owner,
+    token: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<Vec<GithubRepo>, PstatoolError> {
+    let client = api_client()?;
+
+    // The repositories of a user only include the public ones, unless they're listed by the user
+    // the token belongs to
+    let mut path = owner.repos_path();
+    let mut affiliation = vec![];
+    if let (Owner::User(name), Some(token)) = (owner, token) {
+        match authenticated_user(&client, api_url, token, cancel).await {
+            Ok(login) if login.eq_ignore_ascii_case(name) => {
+                path = "user/repos".to_string();
+                affiliation.push(("affiliation", "owner"));
+            }
+            Ok(_) => {}
+            Err(e) => log::debug!("Failed to get the user of the GitHub token: {}", e),
+        }
+    }
+
+    let mut repos = Vec::new();
+    for page in 1.. {
+        let request = api_request(&client, format!("{}/{}", api_url, path), token)
+            .query(&[("sort", "full_name")])
+            .query(&affiliation)
+            .query(&[("per_page", PAGE_SIZE), ("page", page)]);
+
+        let results: Vec<GithubRepo> = send(request, cancel)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let last = results.len() < PAGE_SIZE;
+        repos.extend(results);
+        if last {
+            break;
+        }
+    }
+    Ok(repos)
+}
+
+/// Add the matching repositories that are not in the database yet, and disable the projects of
+/// repositories that were archived or removed
+pub async fn discover(
+    db_url: &str,
+    forge: &Forge,
+    owner: &Owner,
+    filter: &DiscoverFilter,
+    token: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<Discovery, PstatoolError> {
+    let repos = forge.list_repos(owner, token, cancel).await?;
+    log::info!("Found {} repositories of {}", repos.len(), owner);
+
+    let mut discovery = Discovery::default();
+    for repo in repos.iter().filter(|repo| filter.matches(repo)) {
+        let project = Project {
+            host: forge.host(),
+            ..repo.to_project(owner.name())
+        };
+        if db::insert_project(db_url, &project).await? {
+            log::info!("Added project {}/{}", owner, project.project_name);
+            discovery.added.push(project);
+        }
+    }
+
+    for name in db::get_enabled_project_names(db_url, owner.name(), forge.host().as_deref()).await?
+    {
+        let gone = match repos.iter().find(|repo| repo.name == name) {
+            Some(repo) => repo.archived,
+            None => true,
+        };
+        if gone && db::set_project_enabled(db_url, owner.name(), &name, false).await? {
+            log::info!(
+                "Disabled project {}/{}, the repository is archived or removed",
+                owner,
+                name
+            );
+            discovery.disabled.push(name);
+        }
+    }
+    Ok(discovery)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discover::{list_repos, DiscoverFilter, GithubRepo, Owner, Visibility};
+    use axum::extract::{Path, Query};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use tokio_util::sync::CancellationToken;
+
+    fn test_repo(name: &str) -> GithubRepo {
+        GithubRepo {
+            name: name.to_string(),
+            private: false,
+            fork: false,
+            archived: false,
+            default_branch: Some("main".to_string()),
+            topics: vec!["rust".to_string(), "cli".to_string()],
+            size: 120,
+            stargazers_count: 0,
+            forks_count: 0,
+            pushed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_repository_meta() {
+        let repo: GithubRepo = serde_json::from_str(
+            r#"{
+                "name": "pstatool",
+                "private": false,
+                "stargazers_count": 42,
+                "forks_count": 7,
+                "pushed_at": "2026-10-01T12:30:00Z"
+            }"#,
+        )
+        .unwrap();
+        let meta = repo.meta();
+        assert_eq!(meta.stars, 42);
+        assert_eq!(meta.forks, 7);
+        assert_eq!(
+            meta.pushed_at.unwrap().format("%Y-%m-%d").to_string(),
+            "2026-10-01"
+        );
+    }
+
+    #[test]
+    fn test_default_filter() {
+        let filter = DiscoverFilter::default();
+        assert!(filter.matches(&test_repo("project")));
+        let fork = GithubRepo {
+            fork: true,
+            ..test_repo("fork")
+        };
+        assert!(!filter.matches(&fork));
+        let archived = GithubRepo {
+            archived: true,
+            ..test_repo("archived")
+        };
+        assert!(!filter.matches(&archived));
+        let private = GithubRepo {
+            private: true,
+            ..test_repo("private")
+        };
+        assert!(!filter.matches(&private));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = DiscoverFilter {
+            topics: vec!["Rust".to_string(), "go".to_string()],
+            visibility: Visibility::All,
+            min_size: 100,
+        };
+        assert!(filter.matches(&test_repo("project")));
+        let private = GithubRepo {
+            private: true,
+            ..test_repo("private")
+        };
+        assert!(filter.matches(&private));
+        let small = GithubRepo {
+            size: 99,
+            ..test_repo("small")
+        };
+        assert!(!filter.matches(&small));
+        let other_topic = GithubRepo {
+            topics: vec!["java".to_string()],
+            ..test_repo("other")
+        };
+        assert!(!filter.matches(&other_topic));
+        assert_eq!("PRIVATE".parse(), Ok(Visibility::Private));
+    }
+
+    #[test]
+    fn test_project_defaults() {
+        let project = test_repo("project").to_project("user");
+        assert_eq!(project.github_user, "user");
+        assert_eq!(project.title, "project");
+        assert_eq!(project.branch, None);
+
+        let master = GithubRepo {
+            default_branch: Some("master".to_string()),
+            ..test_repo("project")
+        };
+        assert_eq!(master.to_project("user").branch.as_deref(), Some("master"));
+    }
+
+    #[tokio::test]
+    async fn test_list_all_pages() {
+        // 150 repositories, served in pages like the GitHub API
+        let app = Router::new().route(
+            "/orgs/{user}/repos",
+            get(
+                |Path(user): Path<String>, Query(query): Query<HashMap<String, String>>| async move {
+                    let page: usize = query["page"].parse().unwrap();
+                    let per_page: usize = query["per_page"].parse().unwrap();
+                    let repos: Vec<Value> = ((page - 1) * per_page..(page * per_page).min(150))
+                        .map(|i| json!({"name": format!("{}-{}", user, i), "fork": i % 2 == 0}))
+                        .collect();
+                    Json(repos)
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let owner = Owner::Organization("org".to_string());
+        let cancel = CancellationToken::new();
+        let repos = list_repos(&api_url, &owner, None, &cancel).await.unwrap();
+        assert_eq!(repos.len(), 150);
+        assert_eq!(repos[149].name, "org-149");
+        let filter = DiscoverFilter::default();
+        assert_eq!(repos.iter().filter(|repo| filter.matches(repo)).count(), 75);
+    }
+
+    #[tokio::test]
+    async fn test_list_own_private_repos() {
+        // The private repository is only listed for its owner
+        let app = Router::new()
+            .route("/user", get(|| async { Json(json!({"login": "User"})) }))
+            .route(
+                "/user/repos",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    assert_eq!(query["affiliation"], "owner");
+                    Json(json!([{"name": "public"}, {"name": "secret", "private": true}]))
+                }),
+            )
+            .route(
+                "/users/{user}/repos",
+                get(|| async { Json(json!([{"name": "public"}])) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let user = Owner::User("user".to_string());
+        let cancel = CancellationToken::new();
+        let repos = list_repos(&api_url, &user, Some("token"), &cancel)
+            .await
+            .unwrap();
+        assert_eq!(repos.len(), 2);
+        assert!(repos[1].private);
+        let other = Owner::User("other".to_string());
+        assert_eq!(
+            list_repos(&api_url, &other, Some("token"), &cancel)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        let repos = list_repos(&api_url, &user, None, &cancel).await.unwrap();
+        assert_eq!(repos.len(), 1);
+    }
+}
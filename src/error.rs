@@ -0,0 +1,47 @@
+use std::io;
+use thiserror::Error;
+
+/// Failures of counting, rendering and storing the stats of a project
+#[derive(Debug, Error)]
+pub enum PstatoolError {
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+    /// The counter couldn't be started or exited with an error
+    #[error("{0}")]
+    Counter(String),
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Template error: {0}")]
+    Template(#[from] tera::Error),
+    /// Rendering an SVG to a PNG failed
+    #[error("SVG error: {0}")]
+    Svg(String),
+    #[error("Database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("API error: {0}")]
+    Api(#[from] reqwest::Error),
+    #[error("Invalid archive: {0}")]
+    Archive(#[from] zip::result::ZipError),
+    #[error("Failed to watch files: {0}")]
+    Watch(#[from] notify_debouncer_mini::notify::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::PstatoolError;
+
+    #[test]
+    fn test_error_messages() {
+        let error: PstatoolError = git2::Error::from_str("Branch main not found").into();
+        assert_eq!(error.to_string(), "Git error: Branch main not found");
+
+        let error = PstatoolError::Counter("cloc failed: not installed".to_string());
+        assert_eq!(error.to_string(), "cloc failed: not installed");
+    }
+}
@@ -0,0 +1,157 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Stage {
+    Clone,
+    Count,
+    Render,
+    Save,
+    Cleanup,
+}
+
+impl Stage {
+    /// Name of the stage, as it is stored with the timings of the runs in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Clone => "clone",
+            Stage::Count => "count",
+            Stage::Render => "render",
+            Stage::Save => "save",
+            Stage::Cleanup => "cleanup",
+        }
+    }
+}
+
+/// Progress of the pipeline, `project` is always formatted as `user/project_name`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum PipelineEvent {
+    ProjectStarted {
+        project: String,
+    },
+    CloneProgress {
+        project: String,
+        received_objects: usize,
+        total_objects: usize,
+    },
+    Counted {
+        project: String,
+        languages: usize,
+        total_files: u64,
+        total_lines: u64,
+    },
+    Rendered {
+        project: String,
+        path: PathBuf,
+    },
+    /// Card of the project rendered for a terminal, sent when the ANSI preview is enabled. The
+    /// aggregate card of a user has the user as project
+    Preview {
+        project: String,
+        preview: String,
+    },
+    Saved {
+        project: String,
+    },
+    /// Time spent in a stage, also sent when the stage failed
+    StageFinished {
+        project: String,
+        stage: Stage,
+        duration: Duration,
+    },
+    /// The branch is still at the commit of the previous run, the stored stats are used
+    Unchanged {
+        project: String,
+        commit: String,
+    },
+    /// Something unexpected that didn't stop the processing, e.g. a missing branch
+    Warning {
+        project: String,
+        message: String,
+    },
+    Failed {
+        project: String,
+        stage: Stage,
+        error: String,
+    },
+    /// Processing stopped before `stage` because the cancellation token was triggered
+    Cancelled {
+        project: String,
+        stage: Stage,
+    },
+    ProjectFinished {
+        project: String,
+    },
+}
+
+/// Receives the events of the pipeline, implemented for closures and channel senders
+pub trait EventObserver: Send + Sync {
+    fn on_event(&self, event: &PipelineEvent);
+}
+
+impl<F> EventObserver for F
+where
+    F: Fn(&PipelineEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &PipelineEvent) {
+        self(event)
+    }
+}
+
+impl EventObserver for UnboundedSender<PipelineEvent> {
+    fn on_event(&self, event: &PipelineEvent) {
+        // The receiver might be dropped when the caller lost interest
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Observer that ignores all events
+pub struct NoopObserver;
+
+impl EventObserver for NoopObserver {
+    fn on_event(&self, _event: &PipelineEvent) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{EventObserver, PipelineEvent, Stage};
+    use std::sync::Mutex;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_closure_observer() {
+        let events = Mutex::new(Vec::new());
+        let observer = |event: &PipelineEvent| events.lock().unwrap().push(event.clone());
+
+        observer.on_event(&PipelineEvent::ProjectStarted {
+            project: "user/project".to_string(),
+        });
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stage_names() {
+        // The keys of the stage timings of the stored runs
+        assert_eq!(Stage::Clone.as_str(), "clone");
+        assert_eq!(Stage::Cleanup.as_str(), "cleanup");
+    }
+
+    #[tokio::test]
+    async fn test_channel_observer() {
+        let (sender, mut receiver) = unbounded_channel();
+        let event = PipelineEvent::Saved {
+            project: "user/project".to_string(),
+        };
+
+        sender.on_event(&event);
+        assert_eq!(receiver.recv().await, Some(event));
+
+        // Sending to a closed channel is ignored
+        drop(receiver);
+        sender.on_event(&PipelineEvent::Saved {
+            project: "user/project".to_string(),
+        });
+    }
+}
@@ -0,0 +1,469 @@
+//! The stored stats for `pstatool export`, as tables to paste into reports or spreadsheets or as
+//! a dashboard to publish
+//!
+//! The languages table has a row per language of every project, the summary a row per project.
+//! The dashboard is a single HTML file with the summary and the card, history chart and languages
+//! of every project. Everything is built from the latest stats of the projects in the database
+
+use crate::error::PstatoolError;
+use crate::model::{CardMetadata, CardOptions, CardStyle, ClocData, LanguageStats, Project};
+use crate::svg::{self, HistoryPoint};
+use crate::{collect_card_metadata, db};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tera::{Context, Tera};
+
+/// Formats of the export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// A Markdown table
+    Md,
+    /// A self-contained HTML dashboard
+    Html,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "md" | "markdown" => Ok(ExportFormat::Md),
+            "html" => Ok(ExportFormat::Html),
+            _ => Err(format!("Unknown export format {}, use csv, md or html", s)),
+        }
+    }
+}
+
+/// The latest stats of a project
+#[derive(Debug, Clone)]
+pub struct ProjectExport {
+    pub project: Project,
+    pub cloc: ClocData,
+    /// End of the latest run that counted the project
+    pub updated_at: Option<DateTime<Utc>>,
+    /// The total lines of every run that counted the project, oldest first
+    pub history: Vec<HistoryPoint>,
+}
+
+impl ProjectExport {
+    /// `user/project_name`
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.project.github_user, self.project.project_name)
+    }
+
+    fn total_lines(&self) -> u64 {
+        self.cloc.languages.values().map(|s| s.total_lines()).sum()
+    }
+}
+
+/// The latest stats of all projects, or of only `project` formatted as `user/project_name`.
+/// Projects without stats are skipped
+pub async fn load_projects(
+    db_url: &str,
+    project: Option<&str>,
+) -> Result<Vec<ProjectExport>, PstatoolError> {
+    let mut projects = db::get_all_projects(db_url).await?;
+    if let Some(project) = project {
+        projects.retain(|p| format!("{}/{}", p.github_user, p.project_name) == project);
+    }
+    projects
+        .sort_by(|a, b| (&a.github_user, &a.project_name).cmp(&(&b.github_user, &b.project_name)));
+
+    let mut exports = Vec::new();
+    for project in projects {
+        let cloc =
+            db::get_project_stats(db_url, &project.github_user, &project.project_name).await?;
+        if cloc.languages.is_empty() {
+            log::debug!(
+                "No stats of {}/{} to export",
+                project.github_user,
+                project.project_name
+            );
+            continue;
+        }
+        // Newest first
+        let runs: Vec<_> = db::get_run_history(db_url, &project.github_user, &project.project_name)
+            .await?
+            .into_iter()
+            .filter_map(|run| Some((run.created_at, run.total_lines?)))
+            .collect();
+        let history = runs
+            .iter()
+            .rev()
+            .map(|(created_at, total_lines)| HistoryPoint {
+                label: created_at.format("%Y-%m-%d").to_string(),
+                total_lines: *total_lines as u64,
+            })
+            .collect();
+        exports.push(ProjectExport {
+            updated_at: runs.first().map(|(created_at, _)| *created_at),
+            project,
+            cloc,
+            history,
+        });
+    }
+    Ok(exports)
+}
+
+/// A row per language of every project, the most lines first
+pub fn render_languages(projects: &[ProjectExport], format: ExportFormat) -> String {
+    let header = [
+        "project", "language", "files", "code", "comment", "blank", "lines", "share",
+    ];
+    let mut rows = Vec::new();
+    for project in projects {
+        let total = project.total_lines().max(1) as f64;
+        for (language, stats) in project.cloc.ranked_languages() {
+            rows.push(vec![
+                project.name(),
+                language.clone(),
+                stats.n_files.to_string(),
+                stats.code.to_string(),
+                stats.comment.to_string(),
+                stats.blank.to_string(),
+                stats.total_lines().to_string(),
+                format!("{:.2}", stats.total_lines() as f64 / total * 100.0),
+            ]);
+        }
+    }
+    render_table(&header, &rows, format)
+}
+
+/// A row per project with its totals and main language
+pub fn render_summary(projects: &[ProjectExport], format: ExportFormat) -> String {
+    let header = [
+        "project",
+        "title",
+        "languages",
+        "files",
+        "code",
+        "comment",
+        "blank",
+        "lines",
+        "main language",
+        "updated",
+    ];
+    let rows: Vec<Vec<String>> = projects
+        .iter()
+        .map(|project| {
+            let sum = |value: fn(&LanguageStats) -> u64| -> String {
+                project
+                    .cloc
+                    .languages
+                    .values()
+                    .map(value)
+                    .sum::<u64>()
+                    .to_string()
+            };
+            let main_language = project
+                .cloc
+                .ranked_languages()
+                .first()
+                .map(|(name, _)| *name);
+            vec![
+                project.name(),
+                project.project.title.clone(),
+                project.cloc.languages.len().to_string(),
+                sum(|s| s.n_files),
+                sum(|s| s.code),
+                sum(|s| s.comment),
+                sum(|s| s.blank),
+                project.total_lines().to_string(),
+                main_language.cloned().unwrap_or_default(),
+                project
+                    .updated_at
+                    .map(|at| at.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+    render_table(&header, &rows, format)
+}
+
+fn render_table(header: &[&str], rows: &[Vec<String>], format: ExportFormat) -> String {
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    match format {
+        ExportFormat::Csv => {
+            lines.push(
+                header
+                    .iter()
+                    .map(|cell| csv_cell(cell))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+            for row in rows {
+                lines.push(
+                    row.iter()
+                        .map(|cell| csv_cell(cell))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                );
+            }
+        }
+        ExportFormat::Md => {
+            let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+            lines.push(row(header.iter().map(|cell| md_cell(cell)).collect()));
+            // The numbers are aligned to the right
+            let numeric = |i: usize| rows.iter().all(|row| row[i].parse::<f64>().is_ok());
+            lines.push(row((0..header.len())
+                .map(|i| match !rows.is_empty() && numeric(i) {
+                    true => "---:".to_string(),
+                    false => "---".to_string(),
+                })
+                .collect()));
+            for cells in rows {
+                lines.push(row(cells.iter().map(|cell| md_cell(cell)).collect()));
+            }
+        }
+        ExportFormat::Html => {
+            let row = |tag: &str, cells: &mut dyn Iterator<Item = &str>| {
+                let cells: String = cells
+                    .map(|cell| format!("<{0}>{1}</{0}>", tag, tera::escape_html(cell)))
+                    .collect();
+                format!("<tr>{}</tr>", cells)
+            };
+            lines.push("<table>".to_string());
+            lines.push(row("th", &mut header.iter().copied()));
+            for cells in rows {
+                lines.push(row("td", &mut cells.iter().map(String::as_str)));
+            }
+            lines.push("</table>".to_string());
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The card of every project with the settings of the project, treemaps are rendered as bars as
+/// the files of the projects are not stored
+pub async fn render_cards(
+    projects: &[ProjectExport],
+    db_url: &str,
+    card_defaults: &CardOptions,
+) -> Result<Vec<String>, PstatoolError> {
+    let mut cards = Vec::with_capacity(projects.len());
+    for export in projects {
+        let mut options = CardOptions::for_project(&export.project, card_defaults);
+        if options.style == CardStyle::Treemap {
+            options.style = CardStyle::Bar;
+        }
+        let metadata = CardMetadata {
+            updated_at: export.updated_at,
+            ..collect_card_metadata(&export.project, &export.cloc, &options, Some(db_url)).await
+        };
+        cards.push(svg::generate_svg(
+            &export.project.title,
+            &export.cloc,
+            &options,
+            &metadata,
+        )?);
+    }
+    Ok(cards)
+}
+
+#[derive(Serialize)]
+struct DashboardLanguage<'a> {
+    name: &'a str,
+    files: u64,
+    lines: u64,
+    share: String,
+}
+
+#[derive(Serialize)]
+struct DashboardProject<'a> {
+    name: String,
+    title: &'a str,
+    files: u64,
+    lines: u64,
+    main_language: Option<&'a str>,
+    updated: Option<String>,
+    card: String,
+    /// `None` without counted runs
+    history: Option<String>,
+    languages: Vec<DashboardLanguage<'a>>,
+}
+
+/// A self-contained HTML dashboard of the projects with `cards` rendered by [render_cards]. The
+/// cards and charts are embedded as data URIs, the history of every project is included as JSON
+pub fn render_html(projects: &[ProjectExport], cards: &[String]) -> Result<String, tera::Error> {
+    let mut rows = Vec::with_capacity(projects.len());
+    for (export, card) in projects.iter().zip(cards) {
+        let total = export.total_lines();
+        let history = match export.history.is_empty() {
+            true => None,
+            false => Some(svg_data_uri(&svg::generate_history_svg(
+                &export.project.title,
+                &export.history,
+                &export.cloc,
+            )?)),
+        };
+        let ranked = export.cloc.ranked_languages();
+        rows.push(DashboardProject {
+            name: export.name(),
+            title: &export.project.title,
+            files: export.cloc.languages.values().map(|s| s.n_files).sum(),
+            lines: total,
+            main_language: ranked.first().map(|(name, _)| name.as_str()),
+            updated: export
+                .updated_at
+                .map(|at| at.format("%Y-%m-%d").to_string()),
+            card: svg_data_uri(card),
+            history,
+            languages: ranked
+                .iter()
+                .map(|(name, stats)| DashboardLanguage {
+                    name,
+                    files: stats.n_files,
+                    lines: stats.total_lines(),
+                    share: format!(
+                        "{:.2}",
+                        stats.total_lines() as f64 / total.max(1) as f64 * 100.0
+                    ),
+                })
+                .collect(),
+        });
+    }
+
+    let trends: BTreeMap<String, &[HistoryPoint]> = projects
+        .iter()
+        .map(|export| (export.name(), export.history.as_slice()))
+        .collect();
+    // A title can't end the script element
+    let trends = serde_json::to_string(&trends)
+        .map_err(|e| tera::Error::msg(e.to_string()))?
+        .replace("</", "<\\/");
+
+    let mut context = Context::new();
+    context.insert(
+        "generated_at",
+        &Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+    );
+    context.insert(
+        "total_lines",
+        &rows.iter().map(|row| row.lines).sum::<u64>(),
+    );
+    context.insert("projects", &rows);
+    context.insert("trends", &trends);
+    Tera::one_off(include_str!("../assets/dashboard.html"), &context, true)
+}
+
+/// An SVG as a data URI for an `img`, the ids of inline SVGs would clash
+fn svg_data_uri(svg: &str) -> String {
+    let mut uri = String::from("data:image/svg+xml,");
+    for byte in svg.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'=' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Quote a cell with a comma, quote or line break
+fn csv_cell(cell: &str) -> String {
+    match cell.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", cell.replace('"', "\"\"")),
+        false => cell.to_string(),
+    }
+}
+
+/// Escape the pipes of a cell, line breaks would end the row
+fn md_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::{
+        render_html, render_languages, render_summary, ExportFormat, ProjectExport,
+    };
+    use crate::model::{ClocData, Project};
+    use crate::svg::HistoryPoint;
+    use chrono::{TimeZone, Utc};
+
+    fn projects() -> Vec<ProjectExport> {
+        let cloc: ClocData = serde_json::from_str(
+            r#"{
+                "header": {"cloc_version": "2.00"},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 150}
+            }"#,
+        )
+        .unwrap();
+        vec![ProjectExport {
+            project: Project {
+                github_user: "user".to_string(),
+                project_name: "project".to_string(),
+                title: "Stats, \"the tool\" | v2".to_string(),
+                ..Project::default()
+            },
+            cloc,
+            updated_at: Some(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()),
+            history: vec![
+                HistoryPoint {
+                    label: "2024-02-01".to_string(),
+                    total_lines: 120,
+                },
+                HistoryPoint {
+                    label: "2024-03-01".to_string(),
+                    total_lines: 250,
+                },
+            ],
+        }]
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let projects = projects();
+        assert_eq!(
+            render_languages(&projects, ExportFormat::Csv),
+            "project,language,files,code,comment,blank,lines,share\n\
+             user/project,Swift,1,150,0,0,150,60.00\n\
+             user/project,Rust,4,85,5,10,100,40.00\n"
+        );
+        assert_eq!(
+            render_summary(&projects, ExportFormat::Csv),
+            "project,title,languages,files,code,comment,blank,lines,main language,updated\n\
+             user/project,\"Stats, \"\"the tool\"\" | v2\",2,5,235,5,10,250,Swift,2024-03-01\n"
+        );
+    }
+
+    #[test]
+    fn test_export_markdown() {
+        let projects = projects();
+        let table = render_summary(&projects, ExportFormat::Md);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(
+            lines[1],
+            "| --- | --- | ---: | ---: | ---: | ---: | ---: | ---: | --- | --- |"
+        );
+        assert!(lines[2].starts_with("| user/project | Stats, \"the tool\" \\| v2 | 2 |"));
+        assert!(render_languages(&projects, ExportFormat::Md)
+            .contains("| user/project | Swift | 1 | 150 | 0 | 0 | 150 | 60.00 |"));
+        assert!(render_languages(&[], ExportFormat::Md).starts_with("| project | language |"));
+    }
+
+    #[test]
+    fn test_export_html() {
+        let projects = projects();
+        let table = render_summary(&projects, ExportFormat::Html);
+        assert!(table.starts_with("<table>\n<tr><th>project</th><th>title</th>"));
+        assert!(table.contains("<td>Stats, &quot;the tool&quot; | v2</td>"));
+
+        let html = render_html(&projects, &["<svg id=\"card\"></svg>".to_string()]).unwrap();
+        assert!(html.contains("1 projects, 250 lines"));
+        assert!(html.contains(r#"<section id="user&#x2F;project">"#));
+        // The card is embedded, not inlined
+        assert!(html.contains("data:image&#x2F;svg+xml,%3Csvg%20id=%22card%22%3E"));
+        assert!(!html.contains("<svg"));
+        assert!(html.contains(">60.00%</td>"));
+        assert!(html.contains(
+            r#"{"user/project":[{"label":"2024-02-01","total_lines":120},{"label":"2024-03-01","total_lines":250}]}"#
+        ));
+    }
+}
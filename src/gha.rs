@@ -0,0 +1,210 @@
+//! Outputs and a job summary for GitHub Actions workflows, written with `--gha`
+//!
+//! The outputs are appended to the file of `$GITHUB_OUTPUT`, so later steps can gate on them with
+//! e.g. `steps.stats.outputs.delta`. The summary is a Markdown table appended to the file of
+//! `$GITHUB_STEP_SUMMARY`, which is shown on the page of the run
+
+use crate::model::ClocData;
+use crate::report::{ProjectReport, ProjectStatus};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Languages in the table of the summary
+const SUMMARY_LANGUAGES: usize = 5;
+
+/// Outputs of a run: the total lines of the projects, the language with the most lines in the
+/// counted `stats`, the change of the lines since the previous runs and the number of projects.
+/// Unknown values are empty
+pub fn outputs(reports: &[ProjectReport], stats: &ClocData) -> Vec<(&'static str, String)> {
+    let count = |status| reports.iter().filter(|r| r.status == status).count();
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    vec![
+        (
+            "total_lines",
+            optional(total_lines(reports).map(|t| t.to_string())),
+        ),
+        (
+            "top_language",
+            optional(stats.ranked_languages().first().map(|(l, _)| l.to_string())),
+        ),
+        ("delta", optional(delta(reports).map(|d| d.to_string()))),
+        ("projects", reports.len().to_string()),
+        ("failed", count(ProjectStatus::Failed).to_string()),
+    ]
+}
+
+/// The job summary: the totals, a row per project and the top languages
+pub fn step_summary(reports: &[ProjectReport], stats: &ClocData) -> String {
+    let mut summary = String::from("### pstatool\n\n");
+    match total_lines(reports) {
+        Some(total) => {
+            let _ = write!(summary, "**{} lines** in {} projects", total, reports.len());
+        }
+        None => {
+            let _ = write!(summary, "{} projects", reports.len());
+        }
+    }
+    if let Some((language, _)) = stats.ranked_languages().first() {
+        let _ = write!(summary, ", mostly {}", language);
+    }
+    summary.push('.');
+    if let Some(delta) = delta(reports) {
+        let _ = write!(summary, " {:+} lines since the previous run.", delta);
+    }
+
+    summary.push_str("\n\n| Project | Status | Lines | Change |\n| --- | --- | ---: | ---: |\n");
+    for report in reports {
+        let status = report.status.as_str();
+        let lines = report.total_lines.map(|t| t.to_string());
+        let delta = report.delta.map(|d| format!("{:+}", d));
+        let _ = writeln!(
+            summary,
+            "| {} | {} | {} | {} |",
+            report.project,
+            status,
+            lines.unwrap_or_default(),
+            delta.unwrap_or_default()
+        );
+    }
+
+    let total = stats
+        .languages
+        .values()
+        .map(|s| s.total_lines())
+        .sum::<u64>();
+    if total > 0 {
+        summary.push_str("\n| Language | Lines | Share |\n| --- | ---: | ---: |\n");
+        for (language, language_stats) in
+            stats.ranked_languages().into_iter().take(SUMMARY_LANGUAGES)
+        {
+            let lines = language_stats.total_lines();
+            let share = lines as f64 / total as f64 * 100.0;
+            let _ = writeln!(summary, "| {} | {} | {:.1}% |", language, lines, share);
+        }
+    }
+
+    for report in reports.iter().filter(|r| !r.errors.is_empty()) {
+        let _ = write!(
+            summary,
+            "\n> {}: {}\n",
+            report.project,
+            report.errors.join("; ")
+        );
+    }
+    summary
+}
+
+/// Append the outputs and the summary to the files of the job, returns false outside of a job
+pub fn write(outputs: &[(&str, String)], summary: &str) -> io::Result<bool> {
+    let output_file = std::env::var_os("GITHUB_OUTPUT");
+    let summary_file = std::env::var_os("GITHUB_STEP_SUMMARY");
+    if output_file.is_none() && summary_file.is_none() {
+        return Ok(false);
+    }
+
+    if let Some(path) = output_file {
+        let lines: String = outputs
+            .iter()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect();
+        append(Path::new(&path), &lines)?;
+    }
+    if let Some(path) = summary_file {
+        append(Path::new(&path), summary)?;
+    }
+    Ok(true)
+}
+
+fn append(path: &Path, content: &str) -> io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(content.as_bytes())
+}
+
+/// The total lines of the projects with known totals
+fn total_lines(reports: &[ProjectReport]) -> Option<u64> {
+    reports
+        .iter()
+        .filter_map(|r| r.total_lines)
+        .reduce(|a, b| a + b)
+}
+
+/// The change of the lines of the projects that were compared with their previous run
+fn delta(reports: &[ProjectReport]) -> Option<i64> {
+    reports.iter().filter_map(|r| r.delta).reduce(|a, b| a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gha::{outputs, step_summary};
+    use crate::model::ClocData;
+    use crate::report::{ProjectReport, ProjectStatus};
+    use std::time::Duration;
+
+    fn report(project: &str, total_lines: Option<u64>, delta: Option<i64>) -> ProjectReport {
+        ProjectReport {
+            project: project.to_string(),
+            status: match total_lines {
+                Some(_) => ProjectStatus::Ok,
+                None => ProjectStatus::Failed,
+            },
+            duration: Duration::from_secs(1),
+            total_lines,
+            delta,
+            saved: delta.is_some(),
+            warnings: vec![],
+            errors: match total_lines {
+                Some(_) => vec![],
+                None => vec!["Clone: repository not found".to_string()],
+            },
+            cards: vec![],
+        }
+    }
+
+    #[test]
+    fn test_gha_outputs() {
+        let stats: ClocData = serde_json::from_str(
+            r#"{
+                "header": {"cloc_version": "2.00"},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 150}
+            }"#,
+        )
+        .unwrap();
+        let reports = vec![
+            report("user/a", Some(150), Some(-20)),
+            report("user/b", Some(100), Some(5)),
+            report("user/gone", None, None),
+        ];
+        assert_eq!(
+            outputs(&reports, &stats),
+            vec![
+                ("total_lines", "250".to_string()),
+                ("top_language", "Swift".to_string()),
+                ("delta", "-15".to_string()),
+                ("projects", "3".to_string()),
+                ("failed", "1".to_string()),
+            ]
+        );
+        assert_eq!(
+            outputs(&[], &ClocData::default())[..3],
+            [
+                ("total_lines", String::new()),
+                ("top_language", String::new()),
+                ("delta", String::new()),
+            ]
+        );
+
+        let summary = step_summary(&reports, &stats);
+        assert!(summary.contains(
+            "**250 lines** in 3 projects, mostly Swift. -15 lines since the previous run."
+        ));
+        assert!(summary.contains("| user/a | ok | 150 | -20 |\n"));
+        assert!(summary.contains("| user/gone | failed |  |  |\n"));
+        assert!(summary.contains("| Swift | 150 | 60.0% |\n| Rust | 100 | 40.0% |\n"));
+        assert!(summary.ends_with("> user/gone: Clone: repository not found\n"));
+    }
+}
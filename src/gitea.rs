@@ -0,0 +1,179 @@
+use crate::discover::{GithubRepo, Owner};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Maximum page size of the Gitea API with the default server settings
+const PAGE_SIZE: usize = 50;
+
+/// The fields of a repository in the Gitea and Forgejo API that are used
+#[derive(Debug, Deserialize)]
+pub struct GiteaRepo {
+    pub name: String,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub fork: bool,
+    /// Pull mirrors are copies of a repository elsewhere, like forks
+    #[serde(default)]
+    pub mirror: bool,
+    #[serde(default)]
+    pub archived: bool,
+    pub default_branch: Option<String>,
+    /// Only set by recent versions
+    #[serde(default)]
+    pub topics: Option<Vec<String>>,
+    /// Size of the repository in KB
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub stars_count: i32,
+    #[serde(default)]
+    pub forks_count: i32,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<GiteaRepo> for GithubRepo {
+    fn from(repo: GiteaRepo) -> Self {
+        GithubRepo {
+            name: repo.name,
+            private: repo.private,
+            fork: repo.fork || repo.mirror,
+            archived: repo.archived,
+            default_branch: repo.default_branch,
+            topics: repo.topics.unwrap_or_default(),
+            size: repo.size,
+            stargazers_count: repo.stars_count,
+            forks_count: repo.forks_count,
+            // Gitea doesn't have the time of the last push
+            pushed_at: repo.updated_at,
+        }
+    }
+}
+
+fn api_url(url: &str) -> String {
+    format!("{}/api/v1", url.trim_end_matches('/'))
+}
+
+/// GET request to the Gitea API, authenticated when there is a token
+fn api_request(
+    client: &reqwest::Client,
+    url: String,
+    token: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let request = client.get(url).header("Accept", "application/json");
+    match token {
+        Some(token) => request.header("Authorization", format!("token {}", token)),
+        None => request,
+    }
+}
+
+fn api_client() -> Result<reqwest::Client, reqwest::Error> {
+    reqwest::Client::builder()
+        .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+/// Get a single repository from the instance at `url`
+pub async fn get_repo(
+    url: &str,
+    user: &str,
+    project_name: &str,
+    token: Option<&str>,
+) -> Result<GithubRepo, reqwest::Error> {
+    let url = format!("{}/repos/{}/{}", api_url(url), user, project_name);
+    let repo: GiteaRepo = api_request(&api_client()?, url, token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(repo.into())
+}
+
+/// List all repositories of a user or organization on the instance at `url`
+pub async fn list_repos(
+    url: &str,
+    owner: &Owner,
+    token: Option<&str>,
+) -> Result<Vec<GithubRepo>, reqwest::Error> {
+    let client = api_client()?;
+    let path = match owner {
+        Owner::User(name) => format!("users/{}/repos", name),
+        Owner::Organization(name) => format!("orgs/{}/repos", name),
+    };
+
+    let mut repos = Vec::new();
+    for page in 1.. {
+        let request = api_request(&client, format!("{}/{}", api_url(url), path), token)
+            .query(&[("limit", PAGE_SIZE), ("page", page)]);
+
+        // The server may cap the page size below the requested limit, so only an empty page is
+        // the end
+        let results: Vec<GiteaRepo> = request.send().await?.error_for_status()?.json().await?;
+        if results.is_empty() {
+            break;
+        }
+        repos.extend(results.into_iter().map(GithubRepo::from));
+    }
+    Ok(repos)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discover::{DiscoverFilter, GithubRepo, Owner};
+    use crate::gitea::{list_repos, GiteaRepo};
+    use axum::extract::{Path, Query};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_convert_repo() {
+        let repo: GiteaRepo = serde_json::from_str(
+            r#"{
+                "name": "pstatool",
+                "private": false,
+                "mirror": true,
+                "default_branch": "master",
+                "stars_count": 3,
+                "forks_count": 1,
+                "updated_at": "2026-10-01T12:30:00+02:00"
+            }"#,
+        )
+        .unwrap();
+        let repo = GithubRepo::from(repo);
+        assert!(repo.fork);
+        assert!(repo.topics.is_empty());
+        assert_eq!(repo.meta().stars, 3);
+        assert_eq!(repo.to_project("user").branch.as_deref(), Some("master"));
+        assert!(!DiscoverFilter::default().matches(&repo));
+    }
+
+    #[tokio::test]
+    async fn test_list_all_pages() {
+        // 70 repositories, served in pages like the Gitea API of a server that caps the page size
+        // below the requested limit
+        let app = Router::new().route(
+            "/api/v1/users/{user}/repos",
+            get(
+                |Path(user): Path<String>, Query(query): Query<HashMap<String, String>>| async move {
+                    let page: usize = query["page"].parse().unwrap();
+                    let limit = query["limit"].parse::<usize>().unwrap().min(30);
+                    let repos: Vec<Value> = ((page - 1) * limit..(page * limit).min(70))
+                        .map(|i| json!({"name": format!("{}-{}", user, i)}))
+                        .collect();
+                    Json(repos)
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let owner = Owner::User("user".to_string());
+        let repos = list_repos(&url, &owner, None).await.unwrap();
+        assert_eq!(repos.len(), 70);
+        assert_eq!(repos[69].name, "user-69");
+    }
+}
@@ -0,0 +1,232 @@
+use crate::model::GITHUB_URL;
+
+/// Layout of the clone URLs of a host, detected from its URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostKind {
+    /// GitHub, Gitea, Forgejo and other hosts with `{host}/{user}/{repository}.git` URLs
+    Standard,
+    /// `https://dev.azure.com/{organization}/{project}`, with the repositories in `_git`
+    AzureDevOps,
+    /// `https://git-codecommit.{region}.amazonaws.com`, with the repositories in `v1/repos`
+    CodeCommit,
+}
+
+impl HostKind {
+    pub fn detect(url: &str) -> Self {
+        let host = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split(['/', ':'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if host == "dev.azure.com" || host.ends_with(".visualstudio.com") {
+            HostKind::AzureDevOps
+        } else if host.starts_with("git-codecommit.") && host.ends_with(".amazonaws.com") {
+            HostKind::CodeCommit
+        } else {
+            HostKind::Standard
+        }
+    }
+}
+
+/// Clone URL of a repository, GitHub when there is no host
+pub fn repo_url(host: Option<&str>, user: &str, repository: &str) -> String {
+    let host = host.unwrap_or(GITHUB_URL).trim_end_matches('/');
+    match HostKind::detect(host) {
+        HostKind::Standard => format!("{}/{}/{}.git", host, user, repository),
+        HostKind::AzureDevOps => format!("{}/_git/{}", host, repository),
+        HostKind::CodeCommit => format!("{}/v1/repos/{}", host, repository),
+    }
+}
+
+/// Host name of a clone URL, also of SSH URLs like `git@github.com:user/repo.git`. `None` for
+/// local paths
+pub fn url_host(url: &str) -> Option<String> {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split('/').next()?,
+        // scp-like syntax, a colon before the first slash
+        None => {
+            let (authority, _) = url.split_once(':')?;
+            if authority.contains('/') || authority.contains('\\') {
+                return None;
+            }
+            authority
+        }
+    };
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Whether a URL is relative to the repository that has it, like `../library.git`
+pub fn is_relative_url(url: &str) -> bool {
+    url.starts_with("./") || url.starts_with("../")
+}
+
+/// Whether a submodule may be cloned from its URL: over HTTPS or SSH, or relative to the
+/// repository. Local paths and other schemes, e.g. `file://`, are rejected
+pub fn is_allowed_submodule_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    is_relative_url(url)
+        || lower.starts_with("https://")
+        || lower.starts_with("ssh://")
+        || (!url.contains("://") && url_host(url).is_some())
+}
+
+/// Credentials for the hosts that don't allow anonymous clones
+#[derive(Debug, Clone, Default)]
+pub struct GitCredentials {
+    /// Personal access token for Azure DevOps, sent with basic authentication
+    pub azure_devops_token: Option<String>,
+    /// Token for private repositories on GitHub, Gitea and Forgejo, e.g. the token of a GitHub Actions
+    /// workflow
+    pub repository_token: Option<String>,
+}
+
+impl GitCredentials {
+    /// Credentials for the credentials callback of git2, CodeCommit uses the credential helper of the
+    /// git config, e.g. `aws codecommit credential-helper`, which signs the request with SigV4
+    pub fn get(&self, url: &str, username: Option<&str>) -> Result<git2::Cred, git2::Error> {
+        match HostKind::detect(url) {
+            HostKind::AzureDevOps => match &self.azure_devops_token {
+                // Azure DevOps ignores the user name of a token
+                Some(token) => {
+                    git2::Cred::userpass_plaintext(username.unwrap_or("pstatool"), token)
+                }
+                None => Err(git2::Error::from_str(
+                    "Cloning from Azure DevOps requires --azure-devops-token",
+                )),
+            },
+            HostKind::CodeCommit => {
+                git2::Cred::credential_helper(&git2::Config::open_default()?, url, username)
+            }
+            HostKind::Standard => match &self.repository_token {
+                // GitHub accepts any user name with a token
+                Some(token) => git2::Cred::userpass_plaintext("x-access-token", token),
+                None => Err(git2::Error::from_str(&format!(
+                    "{} requires credentials, only public repositories can be cloned",
+                    url
+                ))),
+            },
+        }
+    }
+
+    /// Callbacks that authenticate with these credentials, a rejected attempt isn't repeated
+    pub fn callbacks(&self) -> git2::RemoteCallbacks<'_> {
+        let mut attempted = false;
+        let mut callbacks = git2::RemoteCallbacks::new();
+        callbacks.credentials(move |url, username, _| {
+            if attempted {
+                return Err(git2::Error::from_str(&format!(
+                    "The credentials for {} were rejected",
+                    url
+                )));
+            }
+            attempted = true;
+            self.get(url, username)
+        });
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hosting::{is_allowed_submodule_url, repo_url, url_host, GitCredentials, HostKind};
+
+    #[test]
+    fn test_detect_host() {
+        assert_eq!(HostKind::detect("https://github.com"), HostKind::Standard);
+        assert_eq!(
+            HostKind::detect("https://dev.azure.com/org/project"),
+            HostKind::AzureDevOps
+        );
+        assert_eq!(
+            HostKind::detect("https://org.visualstudio.com/project"),
+            HostKind::AzureDevOps
+        );
+        assert_eq!(
+            HostKind::detect("https://git-codecommit.eu-west-1.amazonaws.com"),
+            HostKind::CodeCommit
+        );
+        // Only the host name counts
+        assert_eq!(
+            HostKind::detect("https://git.example.com/dev.azure.com"),
+            HostKind::Standard
+        );
+    }
+
+    #[test]
+    fn test_submodule_urls() {
+        assert_eq!(
+            url_host("https://token@GitHub.com:443/user/repo.git").as_deref(),
+            Some("github.com")
+        );
+        assert_eq!(
+            url_host("git@gitea.example.com:user/repo.git").as_deref(),
+            Some("gitea.example.com")
+        );
+        assert_eq!(url_host("/srv/git/repo.git"), None);
+        assert_eq!(url_host("../library.git"), None);
+
+        assert!(is_allowed_submodule_url("https://github.com/user/repo.git"));
+        assert!(is_allowed_submodule_url(
+            "ssh://git@github.com/user/repo.git"
+        ));
+        assert!(is_allowed_submodule_url("git@github.com:user/repo.git"));
+        assert!(is_allowed_submodule_url("../library.git"));
+        assert!(!is_allowed_submodule_url("file:///etc"));
+        assert!(!is_allowed_submodule_url("/home/user/.ssh"));
+        assert!(!is_allowed_submodule_url("http://example.com/repo.git"));
+        assert!(!is_allowed_submodule_url("git://example.com/repo.git"));
+    }
+
+    #[test]
+    fn test_repo_url() {
+        assert_eq!(
+            repo_url(None, "user", "project"),
+            "https://github.com/user/project.git"
+        );
+        assert_eq!(
+            repo_url(Some("https://dev.azure.com/org/project/"), "work", "api"),
+            "https://dev.azure.com/org/project/_git/api"
+        );
+        assert_eq!(
+            repo_url(
+                Some("https://git-codecommit.eu-west-1.amazonaws.com"),
+                "work",
+                "api"
+            ),
+            "https://git-codecommit.eu-west-1.amazonaws.com/v1/repos/api"
+        );
+    }
+
+    #[test]
+    fn test_azure_devops_token() {
+        let url = "https://dev.azure.com/org/project/_git/api";
+        assert!(GitCredentials::default().get(url, None).is_err());
+
+        let credentials = GitCredentials {
+            azure_devops_token: Some("token".to_string()),
+            ..GitCredentials::default()
+        };
+        assert!(credentials.get(url, None).is_ok());
+        assert!(credentials
+            .get("https://github.com/user/project.git", None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_repository_token() {
+        let url = "https://github.com/user/private.git";
+        let credentials = GitCredentials {
+            repository_token: Some("token".to_string()),
+            ..GitCredentials::default()
+        };
+        assert!(credentials.get(url, None).is_ok());
+        // Not used for other hosts
+        assert!(credentials
+            .get("https://dev.azure.com/org/project/_git/api", None)
+            .is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Colors of the languages of GitHub linguist, downloaded with `--update-colors`
+//!
+//! The built-in `assets/langs.yml` is a snapshot of linguist, the downloaded `languages.yml` in the data folder
+//! adds the languages and colors of linguist since then.
+
+use crate::error::PstatoolError;
+use crate::svg::load_language_colors;
+use std::path::{Path, PathBuf};
+
+pub const LANGUAGES_URL: &str =
+    "https://raw.githubusercontent.com/github-linguist/linguist/main/lib/linguist/languages.yml";
+/// Name of the downloaded languages in the data folder
+pub const CACHE_FILE: &str = "languages.yml";
+
+/// `$XDG_DATA_HOME/pstatool`, or `~/.local/share/pstatool` without `XDG_DATA_HOME`
+pub fn default_data_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .map(|dir| dir.join("pstatool"))
+}
+
+/// Path of the downloaded languages in the data folder
+pub fn cache_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(CACHE_FILE)
+}
+
+/// Download the languages of linguist to the data folder, returns the number of languages with a color
+pub async fn update_colors(data_dir: &Path) -> Result<usize, PstatoolError> {
+    let yaml = reqwest::Client::builder()
+        .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+        .build()?
+        .get(LANGUAGES_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    save_languages(data_dir, &yaml)
+}
+
+/// Replace the cached languages, a file that doesn't parse keeps the previous copy
+pub fn save_languages(data_dir: &Path, yaml: &str) -> Result<usize, PstatoolError> {
+    let colors = load_language_colors(yaml)?;
+
+    std::fs::create_dir_all(data_dir)?;
+    let partial = data_dir.join(format!("{}.partial", CACHE_FILE));
+    std::fs::write(&partial, yaml)?;
+    std::fs::rename(&partial, cache_path(data_dir))?;
+    Ok(colors.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linguist::{cache_path, save_languages};
+    use crate::svg::load_cached_language_colors;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_cached_languages() {
+        let dir = std::env::temp_dir().join("pstatool-linguist");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let count = save_languages(
+            &dir,
+            "Rust:\n  color: \"#000000\"\nFrobnicate:\n  color: \"#ff8800\"\nText:\n  type: prose\n\
+             Broken:\n  color: \"red\\\" onload=\\\"alert(1)\"\n",
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        assert!(save_languages(&dir, "Rust: [").is_err());
+
+        let overrides = HashMap::from([("Rust".to_string(), "#123456".to_string())]);
+        let colors = load_cached_language_colors(Some(&cache_path(&dir)), &overrides);
+        assert_eq!(colors["Frobnicate"], "#ff8800");
+        // Colors that aren't hex colors are left out of the cards
+        assert!(!colors.contains_key("Broken"));
+        // Overrides of the config file take precedence over the downloaded colors
+        assert_eq!(colors["Rust"], "#123456");
+        // Languages of the built-in snapshot are kept
+        assert_eq!(colors["Swift"], "#F05138");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::error::PstatoolError;
+use crate::events::{EventObserver, PipelineEvent};
+use crate::model::{
+    CardMetadata, CardOptions, ClocConfig, ClocData, Counter, Project, SymlinkPolicy,
+};
+use crate::sandbox::Sandbox;
+use crate::{ansi, config, create_cloc_config, render_card, run_cloc, svg};
+use std::path::Path;
+use std::time::Duration;
+
+/// Settings of counting a local directory, without a database
+pub struct LocalOptions {
+    pub card_defaults: CardOptions,
+    pub symlinks: SymlinkPolicy,
+    pub counter: Counter,
+    pub sandbox: Sandbox,
+    /// Time the counter may run before it's killed
+    pub counter_timeout: Option<Duration>,
+}
+
+/// Project for the directory at `path`, named after the directory
+pub fn directory_project(path: &Path) -> Project {
+    let title = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Project {
+        title: title.clone(),
+        project_name: title,
+        ..Project::default()
+    }
+}
+
+/// The project with the settings of the .pstatool.yml in the directory
+pub fn resolve(project: &Project, path: &Path) -> Project {
+    match config::load_repo_config(path) {
+        Ok(repo_config) => config::resolve_project(project, repo_config.as_ref()),
+        Err(e) => {
+            log::warn!("Ignoring invalid {}: {}", config::REPO_CONFIG_FILE, e);
+            project.clone()
+        }
+    }
+}
+
+/// Count the directory at `path` and write the card of `project` to `out`, returns the stats
+pub async fn write_card(
+    project: &Project,
+    path: &Path,
+    out: &Path,
+    options: &LocalOptions,
+    observer: &dyn EventObserver,
+) -> Result<ClocData, PstatoolError> {
+    let project = resolve(project, path);
+    let card_options = CardOptions::for_project(&project, &options.card_defaults);
+    let config = ClocConfig {
+        symlinks: options.symlinks,
+        counter: options.counter,
+        sandbox: options.sandbox,
+        timeout: options.counter_timeout,
+        ..create_cloc_config(&project, path)
+    };
+
+    let cloc_data = run_cloc(config.clone()).await?;
+    let metadata = CardMetadata {
+        estimate: cloc_data.estimate.filter(|_| card_options.show_estimate),
+        ..CardMetadata::default()
+    };
+    let card = render_card(&project, &cloc_data, config, &card_options, &metadata).await?;
+    std::fs::write(out, card.animated)?;
+    if card_options.static_variant {
+        std::fs::write(out.with_extension("static.svg"), card.snapshot)?;
+    }
+
+    if card_options.preview_ansi {
+        let data = svg::cloc_to_svg_template_data(&cloc_data, &card_options);
+        observer.on_event(&PipelineEvent::Preview {
+            project: project.project_name.clone(),
+            preview: ansi::render_ansi(&project.title, &data),
+        });
+    }
+    Ok(cloc_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::local::directory_project;
+    use std::path::Path;
+
+    #[test]
+    fn test_directory_project() {
+        let project = directory_project(Path::new("/home/user/projects/pstatool"));
+        assert_eq!(project.title, "pstatool");
+        assert_eq!(project.project_name, "pstatool");
+        assert!(project.ignored_dirs.is_none());
+    }
+}
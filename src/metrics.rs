@@ -0,0 +1,292 @@
+//! Metrics of a run pushed to a Prometheus Pushgateway or an InfluxDB write endpoint, for cron
+//! runs that no scraper would find
+//!
+//! Every project gets its lines, files, duration and whether it succeeded, and the lines and files
+//! of each of its counted languages. Projects that weren't counted only get the duration and
+//! status
+
+use crate::model::ClocData;
+use crate::report::ProjectStatus;
+use crate::summary::{ProcessReport, RunSummary};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// Where the metrics of a run are pushed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsTarget {
+    /// URL of a Pushgateway, the metrics replace the group of job `pstatool`
+    Pushgateway { url: String },
+    /// Write endpoint of InfluxDB with its query, e.g.
+    /// `http://influx:8086/api/v2/write?org=org&bucket=pstatool`
+    Influx { url: String, token: Option<String> },
+}
+
+/// The totals of a project, `None` when it wasn't counted
+fn project_totals(report: &ProcessReport) -> Option<(u64, u64)> {
+    let stats = report.stats.as_ref()?;
+    let files = stats.languages.values().map(|s| s.n_files).sum();
+    let lines = report
+        .total_lines
+        .unwrap_or_else(|| stats.languages.values().map(|s| s.total_lines()).sum());
+    Some((lines, files))
+}
+
+fn succeeded(report: &ProcessReport) -> bool {
+    matches!(report.status, ProjectStatus::Ok | ProjectStatus::Warning)
+}
+
+/// The metrics in the Prometheus text format
+pub fn prometheus_metrics(summary: &RunSummary, finished_at: DateTime<Utc>) -> String {
+    let mut metrics = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, String)>| {
+        let _ = writeln!(metrics, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        for (labels, value) in samples {
+            let _ = writeln!(metrics, "{}{{{}}} {}", name, labels, value);
+        }
+    };
+    let project = |report: &ProcessReport| format!("project=\"{}\"", label(&report.project));
+
+    let counted: Vec<(&ProcessReport, u64, u64, &ClocData)> = summary
+        .reports
+        .iter()
+        .filter_map(|r| {
+            let (lines, files) = project_totals(r)?;
+            Some((r, lines, files, r.stats.as_ref()?))
+        })
+        .collect();
+    gauge(
+        "pstatool_project_lines",
+        "Lines of the project",
+        counted
+            .iter()
+            .map(|(r, lines, _, _)| (project(r), lines.to_string()))
+            .collect(),
+    );
+    gauge(
+        "pstatool_project_files",
+        "Files of the project",
+        counted
+            .iter()
+            .map(|(r, _, files, _)| (project(r), files.to_string()))
+            .collect(),
+    );
+    let mut languages = Vec::new();
+    let mut language_files = Vec::new();
+    for (report, _, _, stats) in &counted {
+        for (language, language_stats) in stats.ranked_languages() {
+            let labels = format!("{},language=\"{}\"", project(report), label(language));
+            languages.push((labels.clone(), language_stats.total_lines().to_string()));
+            language_files.push((labels, language_stats.n_files.to_string()));
+        }
+    }
+    gauge(
+        "pstatool_language_lines",
+        "Lines of a language of the project",
+        languages,
+    );
+    gauge(
+        "pstatool_language_files",
+        "Files of a language of the project",
+        language_files,
+    );
+    gauge(
+        "pstatool_project_duration_seconds",
+        "Time it took to process the project",
+        summary
+            .reports
+            .iter()
+            .map(|r| (project(r), format!("{:.3}", r.duration.as_secs_f64())))
+            .collect(),
+    );
+    gauge(
+        "pstatool_project_success",
+        "1 when the project was processed, 0 when it failed or was cancelled",
+        summary
+            .reports
+            .iter()
+            .map(|r| (project(r), u8::from(succeeded(r)).to_string()))
+            .collect(),
+    );
+    let _ = writeln!(
+        metrics,
+        "# HELP pstatool_last_run_timestamp_seconds End of the run\n\
+         # TYPE pstatool_last_run_timestamp_seconds gauge\n\
+         pstatool_last_run_timestamp_seconds {}",
+        finished_at.timestamp()
+    );
+    metrics
+}
+
+/// The metrics in the line protocol of InfluxDB, with timestamps in nanoseconds
+pub fn influx_lines(summary: &RunSummary, finished_at: DateTime<Utc>) -> String {
+    let timestamp = finished_at.timestamp_nanos_opt().unwrap_or_default();
+    let mut lines = String::new();
+    for report in &summary.reports {
+        let project = tag(&report.project);
+        let mut fields = format!(
+            "duration={:.3},success={}",
+            report.duration.as_secs_f64(),
+            succeeded(report)
+        );
+        if let Some((total_lines, files)) = project_totals(report) {
+            let _ = write!(fields, ",lines={}i,files={}i", total_lines, files);
+        }
+        let _ = writeln!(
+            lines,
+            "pstatool_project,project={} {} {}",
+            project, fields, timestamp
+        );
+
+        for (language, stats) in report.stats.iter().flat_map(|s| s.ranked_languages()) {
+            let _ = writeln!(
+                lines,
+                "pstatool_language,project={},language={} lines={}i,files={}i {}",
+                project,
+                tag(language),
+                stats.total_lines(),
+                stats.n_files,
+                timestamp
+            );
+        }
+    }
+    lines
+}
+
+/// Escape a Prometheus label value
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Escape an InfluxDB tag value
+fn tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Push the metrics of a run to the target
+pub async fn push(target: &MetricsTarget, summary: &RunSummary) -> Result<(), reqwest::Error> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let finished_at = Utc::now();
+    let request = match target {
+        // PUT replaces the metrics of removed projects
+        MetricsTarget::Pushgateway { url } => client
+            .put(format!(
+                "{}/metrics/job/pstatool",
+                url.trim_end_matches('/')
+            ))
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(prometheus_metrics(summary, finished_at)),
+        MetricsTarget::Influx { url, token } => {
+            let request = client
+                .post(url)
+                .header("Content-Type", "text/plain; charset=utf-8")
+                .body(influx_lines(summary, finished_at));
+            match token {
+                Some(token) => request.header("Authorization", format!("Token {}", token)),
+                None => request,
+            }
+        }
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{influx_lines, prometheus_metrics, push, MetricsTarget};
+    use crate::model::ClocData;
+    use crate::report::ProjectStatus;
+    use crate::summary::{ProcessReport, RunSummary};
+    use axum::routing::put;
+    use axum::Router;
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn summary() -> RunSummary {
+        let stats: ClocData = serde_json::from_str(
+            r#"{
+                "header": {"cloc_version": "2.00"},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "C++": {"nFiles": 1, "blank": 0, "comment": 0, "code": 150}
+            }"#,
+        )
+        .unwrap();
+        let report = |project: &str, status, stats| ProcessReport {
+            project: project.to_string(),
+            status,
+            started_at: Utc::now(),
+            durations: vec![],
+            duration: Duration::from_millis(1500),
+            total_lines: None,
+            errors: vec![],
+            stats,
+        };
+        RunSummary {
+            reports: vec![
+                report("user/project", ProjectStatus::Ok, Some(stats)),
+                report("user/gone", ProjectStatus::Failed, None),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let metrics = prometheus_metrics(&summary(), at);
+        assert!(metrics.contains(
+            "# TYPE pstatool_project_lines gauge\npstatool_project_lines{project=\"user/project\"} 250\n"
+        ));
+        assert!(metrics.contains("pstatool_project_files{project=\"user/project\"} 5\n"));
+        assert!(metrics
+            .contains("pstatool_language_lines{project=\"user/project\",language=\"C++\"} 150\n"));
+        assert!(metrics.contains("pstatool_project_success{project=\"user/gone\"} 0\n"));
+        assert!(
+            metrics.contains("pstatool_project_duration_seconds{project=\"user/gone\"} 1.500\n")
+        );
+        assert!(!metrics.contains("pstatool_project_lines{project=\"user/gone\"}"));
+        assert!(metrics.ends_with("pstatool_last_run_timestamp_seconds 1709294400\n"));
+    }
+
+    #[test]
+    fn test_influx_lines() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            influx_lines(&summary(), at),
+            "pstatool_project,project=user/project duration=1.500,success=true,lines=250i,files=5i 1709294400000000000\n\
+             pstatool_language,project=user/project,language=C++ lines=150i,files=1i 1709294400000000000\n\
+             pstatool_language,project=user/project,language=Rust lines=100i,files=4i 1709294400000000000\n\
+             pstatool_project,project=user/gone duration=1.500,success=false 1709294400000000000\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_metrics() {
+        let (sender, mut receiver) = unbounded_channel();
+        let app = Router::new().route(
+            "/metrics/job/pstatool",
+            put(move |body: String| async move {
+                sender.send(body).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let target = MetricsTarget::Pushgateway { url };
+        push(&target, &summary()).await.unwrap();
+        let body = receiver.recv().await.unwrap();
+        assert!(body.contains("pstatool_project_lines{project=\"user/project\"} 250\n"));
+    }
+}
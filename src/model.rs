@@ -1,8 +1,17 @@
+use crate::hosting::{self, GitCredentials};
+use crate::output::is_valid_name;
+use crate::retry::RetryPolicy;
+use crate::s3::S3Target;
+use crate::sandbox::Sandbox;
+use crate::svg::is_hex_color;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ClocHeader {
     pub cloc_url: Option<String>,
     pub cloc_version: Option<String>,
@@ -13,7 +22,7 @@ pub struct ClocHeader {
     pub lines_per_second: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct LanguageStats {
     #[serde(rename = "nFiles")]
     pub n_files: u64,
@@ -26,22 +35,279 @@ impl LanguageStats {
     pub fn total_lines(&self) -> u64 {
         self.blank + self.comment + self.code
     }
+
+    /// The code lines with `code_only`, otherwise the total lines
+    pub fn counted_lines(&self, code_only: bool) -> u64 {
+        if code_only {
+            self.code
+        } else {
+            self.total_lines()
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ClocData {
     pub header: ClocHeader,
     #[serde(flatten)]
-    pub languages: HashMap<String, LanguageStats>,
+    pub languages: BTreeMap<String, LanguageStats>,
+    /// COCOMO estimate of the development effort, only calculated by scc
+    #[serde(skip)]
+    pub estimate: Option<CocomoEstimate>,
+}
+
+/// Estimate of the cost to develop the counted code, with the basic COCOMO model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CocomoEstimate {
+    /// Cost in dollars
+    pub cost: f64,
+    pub schedule_months: f64,
+    pub people: f64,
 }
 
-#[derive(Debug, sqlx::FromRow)]
+impl CocomoEstimate {
+    pub fn effort_months(&self) -> f64 {
+        self.schedule_months * self.people
+    }
+}
+
+impl ClocData {
+    /// Sum the language stats of multiple cloc results
+    pub fn aggregate(results: &[ClocData]) -> ClocData {
+        let mut aggregate = ClocData::default();
+        for result in results {
+            for (language, stats) in &result.languages {
+                let total = aggregate.languages.entry(language.clone()).or_default();
+                total.n_files += stats.n_files;
+                total.blank += stats.blank;
+                total.comment += stats.comment;
+                total.code += stats.code;
+            }
+        }
+        aggregate
+    }
+
+    /// Share of the comments in the code and comment lines of all languages, `None` without comments
+    pub fn comment_density(&self) -> Option<f64> {
+        let comment: u64 = self.languages.values().map(|stats| stats.comment).sum();
+        let code: u64 = self.languages.values().map(|stats| stats.code).sum();
+        (comment > 0).then(|| comment as f64 / (comment + code) as f64 * 100.0)
+    }
+
+    /// The languages with the most lines first, languages with the same number of lines by name
+    pub fn ranked_languages(&self) -> Vec<(&String, &LanguageStats)> {
+        let mut languages: Vec<_> = self.languages.iter().collect();
+        languages.sort_by(|(a_name, a), (b_name, b)| {
+            b.total_lines()
+                .cmp(&a.total_lines())
+                .then_with(|| a_name.cmp(b_name))
+        });
+        languages
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileStats {
+    pub blank: u64,
+    pub comment: u64,
+    pub code: u64,
+    #[serde(default)]
+    pub language: String,
+}
+
+impl FileStats {
+    pub fn total_lines(&self) -> u64 {
+        self.blank + self.comment + self.code
+    }
+}
+
+/// Output of cloc with the `--by-file` flag
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClocFileData {
+    pub header: ClocHeader,
+    #[serde(flatten)]
+    pub files: HashMap<String, FileStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirectoryStats {
+    pub name: String,
+    pub files: u64,
+    pub lines: u64,
+    pub languages: HashMap<String, u64>,
+}
+
+impl DirectoryStats {
+    /// The language with the most lines in this directory
+    pub fn dominant_language(&self) -> Option<&str> {
+        self.languages
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(lang, _)| lang.as_str())
+    }
+}
+
+pub const ROOT_DIRECTORY: &str = "/";
+
+/// A file of a project with its lines, the largest files are stored with `--largest-files`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargestFile {
+    /// Relative to the root of the repository
+    pub path: String,
+    pub language: String,
+    pub lines: u64,
+    pub code: u64,
+}
+
+/// Commits of an author of a project, as counted by `git shortlog -sne` with the mailmap applied.
+/// Stored with `--show-contributors`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contributor {
+    pub name: String,
+    pub email: String,
+    pub commits: u64,
+}
+
+impl ClocFileData {
+    /// Group the file stats by their top level directory relative to `root`, sorted by lines
+    pub fn directory_stats(&self, root: &Path) -> Vec<DirectoryStats> {
+        let mut dirs: HashMap<String, DirectoryStats> = HashMap::new();
+
+        for (file, stats) in &self.files {
+            let path = Path::new(file);
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            let mut components = relative
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)));
+
+            let first = components.next();
+            let name = match (first, components.next()) {
+                (Some(dir), Some(_)) => format!("{}/", dir.as_os_str().to_string_lossy()),
+                _ => ROOT_DIRECTORY.to_string(),
+            };
+
+            let dir = dirs.entry(name.clone()).or_insert_with(|| DirectoryStats {
+                name,
+                files: 0,
+                lines: 0,
+                languages: HashMap::new(),
+            });
+            dir.files += 1;
+            dir.lines += stats.total_lines();
+            *dir.languages.entry(stats.language.clone()).or_insert(0) += stats.total_lines();
+        }
+
+        let mut result: Vec<DirectoryStats> = dirs.into_values().collect();
+        result.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+        result
+    }
+
+    /// The `count` files with the most lines, with their path relative to `root`
+    pub fn largest_files(&self, root: &Path, count: usize) -> Vec<LargestFile> {
+        let mut files: Vec<LargestFile> = self
+            .files
+            .iter()
+            .map(|(file, stats)| {
+                let path = Path::new(file);
+                LargestFile {
+                    path: path
+                        .strip_prefix(root)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .to_string(),
+                    language: stats.language.clone(),
+                    lines: stats.total_lines(),
+                    code: stats.code,
+                }
+            })
+            .collect();
+        files.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.path.cmp(&b.path)));
+        files.truncate(count);
+        files
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Project {
+    #[serde(rename = "user")]
     pub github_user: String,
     pub project_name: String,
     pub title: String,
     pub ignored_dirs: Option<String>,
     pub ignored_langs: Option<String>,
+    pub top_languages: Option<i32>,
+    pub card_style: Option<String>,
+    pub show_ranking: Option<bool>,
+    /// Branch to clone, defaults to `main`
+    pub branch: Option<String>,
+    /// URL of the Gitea, Forgejo, Azure DevOps or CodeCommit host of the repository, GitHub when
+    /// empty
+    pub host: Option<String>,
+    /// [CardSettings] of the project as JSON
+    pub card_settings: Option<serde_json::Value>,
+    /// Tag or commit the project is counted at instead of the head of its branch
+    pub pinned_ref: Option<String>,
+    /// Comma separated branches that are counted as separate projects instead of `branch`, see
+    /// [Project::branch_projects]
+    pub branches: Option<String>,
+    /// Project whose repository is counted, set for the projects of its branches
+    pub parent: Option<String>,
+    /// Clone and count the submodules of the repository too
+    pub submodules: Option<bool>,
+    /// Whether the default ignored directories and languages apply, `None` is true
+    pub default_ignores: Option<bool>,
+}
+
+/// Host of the projects without their own host
+pub const GITHUB_URL: &str = "https://github.com";
+
+impl Project {
+    /// Whether the user and project name are safe to use in paths and URLs. Check this before
+    /// cloning or writing the project, the names come from the database or the API
+    pub fn has_valid_names(&self) -> bool {
+        is_valid_name(&self.github_user) && is_valid_name(&self.project_name)
+    }
+
+    /// Name of the repository on its host, the project name unless this is a branch project
+    pub fn repository_name(&self) -> &str {
+        self.parent.as_deref().unwrap_or(&self.project_name)
+    }
+
+    pub fn repo_url(&self) -> String {
+        hosting::repo_url(
+            self.host.as_deref(),
+            &self.github_user,
+            self.repository_name(),
+        )
+    }
+
+    /// The projects that are counted: a project per branch of `branches`, named
+    /// `<project>-<branch>` with its own cards and history, or the project itself without branches
+    pub fn branch_projects(&self) -> Vec<Project> {
+        let branches: Vec<&str> = self
+            .branches
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|branch| !branch.is_empty())
+            .collect();
+        if branches.is_empty() {
+            return vec![self.clone()];
+        }
+
+        branches
+            .into_iter()
+            .map(|branch| Project {
+                project_name: format!("{}-{}", self.project_name, branch.replace('/', "-")),
+                title: format!("{} ({})", self.title, branch),
+                branch: Some(branch.to_string()),
+                branches: None,
+                parent: Some(self.project_name.clone()),
+                ..self.clone()
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,9 +315,911 @@ pub struct Language {
     pub color: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClocConfig {
     pub path: PathBuf,
     pub ignored_langs: Vec<String>,
     pub ignored_dirs: Vec<String>,
+    pub symlinks: SymlinkPolicy,
+    pub counter: Counter,
+    pub sandbox: Sandbox,
+    /// The counter is killed when it runs longer
+    pub timeout: Option<Duration>,
+}
+
+/// Program that counts the lines of code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Counter {
+    #[default]
+    Cloc,
+    /// scc, which also estimates the development effort
+    Scc,
+}
+
+impl Counter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::Cloc => "cloc",
+            Counter::Scc => "scc",
+        }
+    }
+}
+
+impl FromStr for Counter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cloc" => Ok(Counter::Cloc),
+            "scc" => Ok(Counter::Scc),
+            _ => Err(format!("Unknown counter: {}", s)),
+        }
+    }
+}
+
+/// Where the cards and exports of a run are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// Files in the SVG folder
+    #[default]
+    Folder,
+    /// The `artifact` table, served by the server, so nodes don't need a shared file system
+    Database,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "folder" => Ok(Storage::Folder),
+            "database" | "db" => Ok(Storage::Database),
+            _ => Err(format!("Unknown storage: {}", s)),
+        }
+    }
+}
+
+/// A card or export stored in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub content_type: String,
+    pub data: Vec<u8>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How symbolic links in a repository are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// Links are not counted, the files they point to are only counted at their own location
+    #[default]
+    Skip,
+    /// Links are counted as the file or directory they point to, unless it is outside the repository
+    Follow,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(SymlinkPolicy::Skip),
+            "follow" => Ok(SymlinkPolicy::Follow),
+            _ => Err(format!("Unknown symlink policy: {}", s)),
+        }
+    }
+}
+
+pub const DEFAULT_TOP_LANGUAGES: usize = 6;
+/// Size of the cards in pixels
+pub const DEFAULT_CARD_WIDTH: u32 = 300;
+pub const DEFAULT_CARD_HEIGHT: u32 = 190;
+
+/// Projects a run is limited to, by their `user/project_name`. Projects of branches are selected
+/// by the name of their parent
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProjectFilter {
+    /// Only process these projects, all projects when empty
+    pub only: Vec<String>,
+    /// Don't process these projects
+    pub skip: Vec<String>,
+}
+
+impl ProjectFilter {
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.skip.is_empty()
+    }
+
+    pub fn matches(&self, project: &Project) -> bool {
+        let listed = |names: &[String]| names.iter().any(|name| is_named(project, name));
+        (self.only.is_empty() || listed(&self.only)) && !listed(&self.skip)
+    }
+
+    /// The projects that match, names of the filter that match none of the projects are logged
+    pub fn select(&self, projects: &[Project]) -> Vec<Project> {
+        for name in self.only.iter().chain(&self.skip) {
+            if !projects.iter().any(|project| is_named(project, name)) {
+                log::warn!("{} isn't one of the enabled projects", name.trim());
+            }
+        }
+        projects
+            .iter()
+            .filter(|project| self.matches(project))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Whether `name` is the `user/project_name` of the project, in any case like on GitHub
+fn is_named(project: &Project, name: &str) -> bool {
+    name.trim()
+        .split_once('/')
+        .is_some_and(|(user, project_name)| {
+            user.eq_ignore_ascii_case(&project.github_user)
+                && project_name.eq_ignore_ascii_case(&project.project_name)
+        })
+}
+
+/// Settings shared by every project processed in a run
+#[derive(Debug, Clone)]
+pub struct PipelineSettings {
+    pub svg_folder: PathBuf,
+    pub temp_folder: PathBuf,
+    /// Stats are only saved (and rankings only shown) when a database is set
+    pub db_url: Option<String>,
+    pub card_defaults: CardOptions,
+    /// Folder with the custom templates, recorded in the config of every run
+    pub template_dir: Option<PathBuf>,
+    pub symlinks: SymlinkPolicy,
+    pub counter: Counter,
+    pub sandbox: Sandbox,
+    /// Time the counter may run before it's killed, `None` waits until the counter exits
+    pub counter_timeout: Option<Duration>,
+    /// Time an attempt to clone a project may take before it's aborted, `None` waits until it's done
+    pub clone_timeout: Option<Duration>,
+    /// Time a project may take from its clone until its stats are saved. After that it is aborted
+    /// between its stages and fails, `None` doesn't limit it
+    pub project_timeout: Option<Duration>,
+    /// Token for the GitHub API, used for the metadata of the repositories
+    pub github_token: Option<String>,
+    /// Token for the API of the Gitea or Forgejo instance of the projects
+    pub gitea_token: Option<String>,
+    /// URL of the instance the Gitea token belongs to, it isn't sent to other hosts
+    pub gitea_url: Option<String>,
+    /// Credentials to clone from Azure DevOps and CodeCommit
+    pub credentials: GitCredentials,
+    /// Receives the changes of every saved project, requires a database
+    pub webhook_url: Option<String>,
+    /// Use the stored stats when the branch didn't change since the previous run, requires a database
+    pub skip_unchanged: bool,
+    /// Where the cards are written, the SVG folder is used without a database
+    pub storage: Storage,
+    /// Bucket the cards are uploaded to instead of the SVG folder
+    pub upload: Option<S3Target>,
+    /// Retrying of failed clones and database writes
+    pub retry: RetryPolicy,
+    /// Files with the most lines stored per project, requires a database. The files are counted
+    /// again when this isn't 0
+    pub largest_files: usize,
+    /// Commits fetched of the branch of every project, 0 fetches the whole history. The shortlog of
+    /// the contributors only covers the fetched commits
+    pub clone_depth: u32,
+    /// Download public GitHub repositories as a tarball instead of cloning them, see
+    /// [crate::archive::download_github_tarball]
+    pub tarball: bool,
+    /// Largest repository in MB that is cloned, as reported by the API of its forge. `None` doesn't
+    /// check the size
+    pub max_repo_size: Option<u64>,
+    /// Clone repositories above the size limit anyway, with a warning
+    pub force: bool,
+    /// Open pull requests that update the stats section of the README of GitHub projects, see
+    /// [crate::readme]
+    pub readme_pr: bool,
+    /// URL the cards are published at, the README links `<card_url>/<user>/<project>.svg`
+    pub card_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProjectRanking {
+    pub rank: i64,
+    pub total: i64,
+}
+
+impl ProjectRanking {
+    pub fn describe(&self) -> String {
+        if self.rank == 1 {
+            return format!("Largest of {} projects", self.total);
+        }
+
+        let suffix = match (self.rank % 10, self.rank % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        };
+        format!("{}{} largest of {} projects", self.rank, suffix, self.total)
+    }
+}
+
+/// Additional data shown on the card next to the cloc results
+#[derive(Debug, Default)]
+pub struct CardMetadata {
+    pub ranking: Option<ProjectRanking>,
+    pub repository: Option<RepositoryMeta>,
+    pub estimate: Option<CocomoEstimate>,
+    /// Lines changed in the last days, see [crate::churn::CHURN_PERIOD_DAYS]
+    pub churn: Option<Churn>,
+    /// Authors with a commit in the cloned history
+    pub contributors: Option<usize>,
+    /// When the stats were counted, the time of rendering when unknown
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Commit that was counted
+    pub commit: Option<String>,
+}
+
+/// Lines changed between two versions of a repository, as counted by `cloc --diff`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Churn {
+    pub added: u64,
+    pub removed: u64,
+    pub modified: u64,
+}
+
+/// Popularity and activity of the repository on GitHub
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositoryMeta {
+    pub stars: i32,
+    pub forks: i32,
+    /// Time of the latest push to any branch
+    pub pushed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CardStyle {
+    /// Language bar with a legend of the top languages
+    #[default]
+    Bar,
+    /// Treemap of the top level directories, colored by their dominant language
+    Treemap,
+    /// Donut chart of the top languages with a legend
+    Donut,
+}
+
+/// How the totals are written on the cards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    /// `128734`
+    Plain,
+    /// `128,734`, with the thousands separator of the locale of the card
+    #[default]
+    Grouped,
+    /// `128.7k`
+    Compact,
+}
+
+/// Colors of the cards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// The colors of `theme.svg`
+    #[default]
+    Dark,
+    Light,
+}
+
+/// Appearance of the card of a single project, stored as JSON in the `card_settings` column
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardSettings {
+    pub theme: Option<Theme>,
+    /// Color of the header, e.g. `#58a6ff`
+    pub accent_color: Option<String>,
+    pub top_languages: Option<usize>,
+    /// Render the card without CSS animations
+    #[serde(default)]
+    pub hide_animation: bool,
+    /// Header of the card, `{title}` is replaced by the title, e.g. `{title} in numbers`
+    pub title_format: Option<String>,
+    /// Base the totals and percentages on the code lines, without comments and blanks
+    #[serde(default)]
+    pub code_only: bool,
+    /// Show when the stats were counted and the commit in the footer, overrides `--show-updated`
+    pub show_updated: Option<bool>,
+    /// Show the lines of the languages in the legend, overrides `--legend-lines`
+    pub legend_lines: Option<bool>,
+    /// Languages with a smaller share in percent are grouped as "Other", overrides `--min-share`
+    pub min_share: Option<f64>,
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "plain" => Ok(NumberFormat::Plain),
+            "grouped" => Ok(NumberFormat::Grouped),
+            "compact" => Ok(NumberFormat::Compact),
+            _ => Err(format!("Unknown number format: {}", s)),
+        }
+    }
+}
+
+impl FromStr for CardStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bar" => Ok(CardStyle::Bar),
+            "treemap" => Ok(CardStyle::Treemap),
+            "donut" | "pie" => Ok(CardStyle::Donut),
+            _ => Err(format!("Unknown card style: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CardOptions {
+    /// Number of languages shown on the card, the remaining languages are grouped as "Other"
+    pub top_languages: usize,
+    pub style: CardStyle,
+    /// Show the size ranking of the project compared to the other projects of the user
+    pub show_ranking: bool,
+    /// Also write a PNG version of every SVG, scaled by this factor
+    pub png_scale: Option<f32>,
+    /// Also write the static snapshot of every card as `<name>.static.svg`
+    pub static_variant: bool,
+    /// Locale of the language names shown on the card, e.g. `de`
+    pub locale: Option<String>,
+    /// Format of the totals, grouped with the separator of `locale`
+    pub number_format: NumberFormat,
+    pub theme: Theme,
+    /// Color of the header instead of the accent color of the theme
+    pub accent_color: Option<String>,
+    /// Animate the bars and labels with CSS, otherwise the card is the static snapshot
+    pub animated: bool,
+    /// Header of the card, `{title}` is replaced by the title. `Stats for {title}` by default
+    pub title_format: Option<String>,
+    /// Count only the code lines in the totals and percentages, not the comments and blanks
+    pub code_only: bool,
+    /// Print an approximation of the card to the terminal after rendering
+    pub preview_ansi: bool,
+    /// Show the stars, forks and latest push of the repository in the header
+    pub show_repository: bool,
+    /// Show the COCOMO estimate of the effort, when the counter calculates it
+    pub show_estimate: bool,
+    /// Measure the lines changed since the previous run and show the total of the last week
+    pub show_churn: bool,
+    /// Link the languages in the legend to a GitHub code search in the repository
+    pub language_links: bool,
+    /// Also write a card with the lines per top level directory as `<name>-directories.svg`
+    pub directory_card: bool,
+    /// Show the number of authors in the cloned history, stored with their commits
+    pub show_contributors: bool,
+    /// Show the date the stats were counted and the short commit in the footer
+    pub show_updated: bool,
+    /// Show the lines of every language in the legend next to its share, e.g. `Rust 45.2k (61.3%)`
+    pub legend_lines: bool,
+    /// Languages with a smaller share in percent are grouped as "Other", like the languages after
+    /// the top languages
+    pub min_share: f64,
+    /// Size of the card in pixels, the bar and the columns of the legend are laid out for it
+    pub width: u32,
+    pub height: u32,
+    /// GitHub repository of the card as `user/project`, set by [CardOptions::for_project] for
+    /// projects on GitHub
+    pub repository: Option<String>,
+}
+
+impl Default for CardOptions {
+    fn default() -> Self {
+        CardOptions {
+            top_languages: DEFAULT_TOP_LANGUAGES,
+            style: CardStyle::default(),
+            show_ranking: false,
+            png_scale: None,
+            static_variant: false,
+            locale: None,
+            number_format: NumberFormat::default(),
+            theme: Theme::default(),
+            accent_color: None,
+            animated: true,
+            title_format: None,
+            code_only: false,
+            preview_ansi: false,
+            show_repository: false,
+            show_estimate: false,
+            show_churn: false,
+            language_links: false,
+            directory_card: false,
+            show_contributors: false,
+            show_updated: false,
+            legend_lines: false,
+            min_share: 0.0,
+            width: DEFAULT_CARD_WIDTH,
+            height: DEFAULT_CARD_HEIGHT,
+            repository: None,
+        }
+    }
+}
+
+impl CardOptions {
+    /// Apply the settings of the project on top of the default options
+    pub fn for_project(project: &Project, defaults: &CardOptions) -> Self {
+        let mut options = defaults.clone();
+        // Local repositories have no user, and the search links only go to GitHub
+        if !project.github_user.is_empty() && project.host.is_none() {
+            options.repository = Some(format!(
+                "{}/{}",
+                project.github_user,
+                project.repository_name()
+            ));
+        }
+        if let Some(top) = project.top_languages.filter(|top| *top > 0) {
+            options.top_languages = top as usize;
+        }
+        if let Some(style) = project.card_style.as_deref() {
+            match style.parse() {
+                Ok(style) => options.style = style,
+                Err(e) => log::warn!("{}, using default style", e),
+            }
+        }
+        if let Some(show_ranking) = project.show_ranking {
+            options.show_ranking = show_ranking;
+        }
+        if let Some(settings) = &project.card_settings {
+            match serde_json::from_value::<CardSettings>(settings.clone()) {
+                Ok(settings) => options.apply_settings(settings),
+                Err(e) => log::warn!(
+                    "Invalid card settings of {}/{}: {}, using default settings",
+                    project.github_user,
+                    project.project_name,
+                    e
+                ),
+            }
+        }
+        options
+    }
+
+    /// Apply the card settings of a project, they take precedence over the columns of the project
+    fn apply_settings(&mut self, settings: CardSettings) {
+        if let Some(theme) = settings.theme {
+            self.theme = theme;
+        }
+        match settings.accent_color {
+            Some(color) if is_hex_color(&color) => self.accent_color = Some(color),
+            Some(color) => log::warn!("Invalid accent color {}, using the theme color", color),
+            None => {}
+        }
+        if let Some(top) = settings.top_languages.filter(|top| *top > 0) {
+            self.top_languages = top;
+        }
+        if settings.hide_animation {
+            self.animated = false;
+        }
+        if settings.title_format.is_some() {
+            self.title_format = settings.title_format;
+        }
+        if settings.code_only {
+            self.code_only = true;
+        }
+        if let Some(show_updated) = settings.show_updated {
+            self.show_updated = show_updated;
+        }
+        if let Some(legend_lines) = settings.legend_lines {
+            self.legend_lines = legend_lines;
+        }
+        match settings.min_share {
+            Some(share) if (0.0..100.0).contains(&share) => self.min_share = share,
+            Some(share) => log::warn!("Invalid minimum share {}, using the default", share),
+            None => {}
+        }
+    }
+}
+
+/// Version of the JSON written by pstatool: run configs, webhook payloads and API responses. Only
+/// increased when a field is removed or changes meaning, consumers should ignore unknown fields
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Version of JSON written before the version was added to it
+fn first_schema_version() -> u32 {
+    1
+}
+
+/// The merged configuration a project was processed with, stored with every run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunConfig {
+    #[serde(default = "first_schema_version")]
+    pub schema_version: u32,
+    pub pstatool_version: String,
+    pub counter: String,
+    pub counter_version: Option<String>,
+    pub git_ref: String,
+    /// Commit that was counted
+    #[serde(default)]
+    pub commit: Option<String>,
+    pub ignored_dirs: Vec<String>,
+    pub ignored_langs: Vec<String>,
+    pub card_style: CardStyle,
+    pub top_languages: usize,
+    /// Languages with a smaller share in percent were grouped as "Other"
+    #[serde(default)]
+    pub min_share: f64,
+    pub show_ranking: bool,
+    /// Custom template folder, `None` when the built-in templates are used
+    pub template_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub symlinks: SymlinkPolicy,
+    #[serde(default)]
+    pub sandbox: Sandbox,
+    /// Differences between the configuration of the project and the repository, e.g. a missing branch
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Settings of the project and the run before the repository config was applied, `None` for
+    /// runs stored before they were recorded
+    #[serde(default)]
+    pub settings: Option<RunSettings>,
+}
+
+/// The settings the stats and card of a project depend on besides its commit. The stored stats of
+/// an unchanged project are only used again when they're the same
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSettings {
+    pub counter: String,
+    pub ignored_dirs: Vec<String>,
+    pub ignored_langs: Vec<String>,
+    pub card_style: CardStyle,
+    pub top_languages: usize,
+    pub min_share: f64,
+    pub show_ranking: bool,
+    pub template_dir: Option<PathBuf>,
+    pub symlinks: SymlinkPolicy,
+}
+
+/// An attempt to process a project, recorded in the run history also when it failed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub started_at: DateTime<Utc>,
+    /// Commit that was counted, or that was found unchanged
+    pub commit: Option<String>,
+    /// `ok`, `warning`, `failed` or `cancelled`
+    pub status: String,
+    /// Errors of the failed stages, one per line
+    pub error: Option<String>,
+    /// Milliseconds spent per stage, e.g. `{"clone": 1200}`
+    pub stage_timings: BTreeMap<String, u64>,
+    /// Time the run is recorded at, now when `None`. Backfilled runs have the time of their commit
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// A stored run of a project with the config it was processed with
+#[derive(Debug, Serialize)]
+pub struct RunHistory {
+    /// End of the run
+    pub created_at: DateTime<Utc>,
+    /// `None` for runs stored before the start was recorded
+    pub started_at: Option<DateTime<Utc>>,
+    pub commit: Option<String>,
+    /// `ok` for runs stored before the status was recorded
+    pub status: String,
+    pub error: Option<String>,
+    pub stage_timings: Option<serde_json::Value>,
+    /// The totals and config are `None` when the project wasn't counted, e.g. when it was unchanged
+    pub total_files: Option<i32>,
+    pub total_lines: Option<i32>,
+    pub config: Option<serde_json::Value>,
+}
+
+/// Freshness of the card of a project, from its run history
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RunStatus {
+    /// End of the latest run that counted the project
+    pub last_updated: Option<DateTime<Utc>>,
+    /// End and status of the latest run
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_status: Option<String>,
+    /// Error and end of the latest failed run
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{
+        CardOptions, CardStyle, ClocData, ClocFileData, Project, ProjectFilter, ProjectRanking,
+        RunConfig, Theme, ROOT_DIRECTORY, SCHEMA_VERSION,
+    };
+    use serde_json::json;
+    use std::path::Path;
+
+    #[test]
+    fn test_run_config_schema() {
+        // Stored before the schema version was added
+        let config: RunConfig = serde_json::from_value(json!({
+            "pstatool_version": "0.1.1",
+            "counter": "cloc",
+            "counter_version": "2.00",
+            "git_ref": "main",
+            "ignored_dirs": ["target"],
+            "ignored_langs": [],
+            "card_style": "bar",
+            "top_languages": 6,
+            "show_ranking": false,
+            "template_dir": null
+        }))
+        .unwrap();
+        assert_eq!(config.schema_version, 1);
+        assert_eq!(config.commit, None);
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(serde_json::from_value::<RunConfig>(json).unwrap(), config);
+    }
+
+    #[test]
+    fn test_project_filter() {
+        let project = |user: &str, name: &str| Project {
+            github_user: user.to_string(),
+            project_name: name.to_string(),
+            ..Project::default()
+        };
+        let projects = vec![
+            project("user", "a"),
+            project("user", "b"),
+            project("other", "a"),
+        ];
+        let names = |filter: ProjectFilter| {
+            filter
+                .select(&projects)
+                .iter()
+                .map(|p| format!("{}/{}", p.github_user, p.project_name))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(ProjectFilter::default()).len(), 3);
+        assert_eq!(
+            names(ProjectFilter {
+                only: vec!["User/A".to_string(), " other/a".to_string()],
+                skip: vec![],
+            }),
+            vec!["user/a", "other/a"]
+        );
+        assert_eq!(
+            names(ProjectFilter {
+                only: vec![],
+                skip: vec!["user/b".to_string(), "gone/c".to_string()],
+            }),
+            vec!["user/a", "other/a"]
+        );
+        assert!(names(ProjectFilter {
+            only: vec!["user/a".to_string()],
+            skip: vec!["user/a".to_string()],
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn test_ranked_languages() {
+        let data: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 50},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "C": {"nFiles": 2, "blank": 0, "comment": 0, "code": 50}
+            }"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = data
+            .ranked_languages()
+            .into_iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Rust", "C", "Swift"]);
+
+        // Serialized in the same order every time
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.find("\"C\"").unwrap() < json.find("\"Rust\"").unwrap());
+        assert!(json.find("\"Rust\"").unwrap() < json.find("\"Swift\"").unwrap());
+    }
+
+    #[test]
+    fn test_directory_stats() {
+        let data: ClocFileData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "/tmp/repo/src/main.rs": {"blank": 1, "comment": 1, "code": 38, "language": "Rust"},
+                "/tmp/repo/src/lib.rs": {"blank": 0, "comment": 0, "code": 20, "language": "Rust"},
+                "/tmp/repo/web/app.js": {"blank": 0, "comment": 0, "code": 30, "language": "JavaScript"},
+                "/tmp/repo/web/index.html": {"blank": 0, "comment": 0, "code": 5, "language": "HTML"},
+                "/tmp/repo/build.sh": {"blank": 0, "comment": 0, "code": 5, "language": "Bourne Shell"}
+            }"#,
+        )
+        .unwrap();
+
+        let dirs = data.directory_stats(Path::new("/tmp/repo"));
+        let names: Vec<&str> = dirs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["src/", "web/", ROOT_DIRECTORY]);
+        assert_eq!(dirs[0].lines, 60);
+        assert_eq!(dirs[0].files, 2);
+        assert_eq!(dirs[1].dominant_language(), Some("JavaScript"));
+
+        let largest = data.largest_files(Path::new("/tmp/repo"), 2);
+        let paths: Vec<&str> = largest.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["src/main.rs", "web/app.js"]);
+        assert_eq!(largest[0].lines, 40);
+        assert_eq!(largest[0].code, 38);
+        assert_eq!(largest[0].language, "Rust");
+    }
+
+    #[test]
+    fn test_aggregate_cloc_data() {
+        let first: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 2, "blank": 1, "comment": 2, "code": 30},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 10}
+            }"#,
+        )
+        .unwrap();
+        let second: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 3, "blank": 4, "comment": 5, "code": 60}
+            }"#,
+        )
+        .unwrap();
+
+        let aggregate = ClocData::aggregate(&[first, second]);
+        assert_eq!(aggregate.languages.len(), 2);
+        assert_eq!(aggregate.languages["Rust"].n_files, 5);
+        assert_eq!(aggregate.languages["Rust"].total_lines(), 102);
+        assert_eq!(aggregate.languages["Swift"].total_lines(), 10);
+    }
+
+    #[test]
+    fn test_ranking_description() {
+        let describe = |rank, total| ProjectRanking { rank, total }.describe();
+        assert_eq!(describe(1, 27), "Largest of 27 projects");
+        assert_eq!(describe(2, 27), "2nd largest of 27 projects");
+        assert_eq!(describe(3, 27), "3rd largest of 27 projects");
+        assert_eq!(describe(12, 27), "12th largest of 27 projects");
+        assert_eq!(describe(21, 27), "21st largest of 27 projects");
+    }
+
+    #[test]
+    fn test_repo_url() {
+        let project = Project {
+            github_user: "user".to_string(),
+            project_name: "project".to_string(),
+            ..Project::default()
+        };
+        assert_eq!(project.repo_url(), "https://github.com/user/project.git");
+
+        let gitea = Project {
+            host: Some("https://git.example.com/".to_string()),
+            ..project
+        };
+        assert_eq!(gitea.repo_url(), "https://git.example.com/user/project.git");
+    }
+
+    #[test]
+    fn test_branch_projects() {
+        let project = Project {
+            github_user: "user".to_string(),
+            project_name: "project".to_string(),
+            title: "Project".to_string(),
+            top_languages: Some(3),
+            ..Project::default()
+        };
+        assert_eq!(project.branch_projects(), vec![project.clone()]);
+
+        let project = Project {
+            branches: Some("main, release/v2,".to_string()),
+            ..project
+        };
+        let branches = project.branch_projects();
+        let names: Vec<&str> = branches.iter().map(|p| p.project_name.as_str()).collect();
+        assert_eq!(names, ["project-main", "project-release-v2"]);
+        let v2 = &branches[1];
+        assert_eq!(v2.title, "Project (release/v2)");
+        assert_eq!(v2.branch.as_deref(), Some("release/v2"));
+        assert_eq!(v2.top_languages, Some(3));
+        assert_eq!(v2.branches, None);
+        assert_eq!(v2.repo_url(), "https://github.com/user/project.git");
+        assert!(v2.has_valid_names());
+    }
+
+    #[test]
+    fn test_has_valid_names() {
+        let project = |user: &str, name: &str| Project {
+            github_user: user.to_string(),
+            project_name: name.to_string(),
+            ..Project::default()
+        };
+        assert!(project("wdudokvanheel", "pstatool.rs").has_valid_names());
+        assert!(!project("user", "../../etc").has_valid_names());
+        assert!(!project("..", "project").has_valid_names());
+        assert!(!project("user", "project?ref=evil").has_valid_names());
+        assert!(!project("", "project").has_valid_names());
+    }
+
+    #[test]
+    fn test_card_style_parse() {
+        assert_eq!("Treemap".parse::<CardStyle>(), Ok(CardStyle::Treemap));
+        assert_eq!(" bar".parse::<CardStyle>(), Ok(CardStyle::Bar));
+        assert_eq!("pie".parse::<CardStyle>(), Ok(CardStyle::Donut));
+        assert!("pie chart".parse::<CardStyle>().is_err());
+    }
+
+    #[test]
+    fn test_card_options_for_project() {
+        let defaults = CardOptions {
+            style: CardStyle::Donut,
+            ..CardOptions::default()
+        };
+
+        let project = Project::default();
+        let options = CardOptions::for_project(&project, &defaults);
+        assert_eq!(options.style, CardStyle::Donut);
+
+        let project = Project {
+            card_style: Some("treemap".to_string()),
+            top_languages: Some(3),
+            ..Project::default()
+        };
+        let options = CardOptions::for_project(&project, &defaults);
+        assert_eq!(options.style, CardStyle::Treemap);
+        assert_eq!(options.top_languages, 3);
+
+        // Only repositories on GitHub link to its search
+        let project = Project {
+            github_user: "user".to_string(),
+            project_name: "project".to_string(),
+            ..Project::default()
+        };
+        let options = CardOptions::for_project(&project, &defaults);
+        assert_eq!(options.repository.as_deref(), Some("user/project"));
+        let project = Project {
+            host: Some("https://git.example.com".to_string()),
+            ..project
+        };
+        assert_eq!(
+            CardOptions::for_project(&project, &defaults).repository,
+            None
+        );
+    }
+
+    #[test]
+    fn test_card_settings() {
+        let project = Project {
+            top_languages: Some(3),
+            card_settings: Some(json!({
+                "theme": "light",
+                "accent_color": "#58a6ff",
+                "top_languages": 4,
+                "hide_animation": true,
+                "title_format": "{title} in numbers"
+            })),
+            ..Project::default()
+        };
+        let options = CardOptions::for_project(&project, &CardOptions::default());
+        assert_eq!(options.theme, Theme::Light);
+        assert_eq!(options.accent_color.as_deref(), Some("#58a6ff"));
+        assert_eq!(options.top_languages, 4);
+        assert!(!options.animated);
+        assert_eq!(options.title_format.as_deref(), Some("{title} in numbers"));
+
+        // Invalid settings are ignored instead of failing the card
+        let project = Project {
+            card_settings: Some(json!({"theme": "neon"})),
+            ..Project::default()
+        };
+        let options = CardOptions::for_project(&project, &CardOptions::default());
+        assert_eq!(options.theme, Theme::Dark);
+        assert!(options.animated);
+    }
 }
@@ -0,0 +1,199 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const SVG_CONTENT_TYPE: &str = "image/svg+xml; charset=utf-8";
+pub const PNG_CONTENT_TYPE: &str = "image/png";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Suffixes of the files written for a project, after the name of the project. The cards of the
+/// users in the root of the output folder have the same suffixes
+pub const PROJECT_FILE_SUFFIXES: [&str; 12] = [
+    "-shields.json",
+    "-stats.json",
+    "-badge.svg",
+    "-badge.png",
+    "-directories.static.svg",
+    "-directories.static.png",
+    "-directories.svg",
+    "-directories.png",
+    ".static.svg",
+    ".static.png",
+    ".svg",
+    ".png",
+];
+
+/// Only allow the characters GitHub allows in user and repository names, so they are safe file names
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn invalid_name(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Invalid name for a file in the output folder: {:?}", name),
+    )
+}
+
+fn outside_folder(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} is outside of the output folder", path.display()),
+    )
+}
+
+pub fn write_svg_to_output_dir(
+    folder: &Path,
+    user: &str,
+    project_name: &str,
+    contents: &str,
+) -> io::Result<PathBuf> {
+    write_to_output_dir(
+        folder,
+        user,
+        &format!("{}.svg", project_name),
+        contents.as_bytes(),
+    )
+}
+
+/// Write a file to the subfolder of the user, an empty user writes to the output folder itself
+///
+/// The names must be single path components, and the resolved file must stay inside the output
+/// folder, so links in the output folder can't be used to write elsewhere
+pub fn write_to_output_dir(
+    folder: &Path,
+    user: &str,
+    file_name: &str,
+    contents: &[u8],
+) -> io::Result<PathBuf> {
+    if !user.is_empty() && !is_valid_name(user) {
+        return Err(invalid_name(user));
+    }
+    if !is_valid_name(file_name) {
+        return Err(invalid_name(file_name));
+    }
+
+    let subfolder_path = folder.join(user);
+    if !subfolder_path.exists() {
+        fs::create_dir_all(&subfolder_path)?;
+    }
+    let output_file = subfolder_path.join(file_name);
+
+    let root = folder.canonicalize()?;
+    if !subfolder_path.canonicalize()?.starts_with(&root) {
+        return Err(outside_folder(&subfolder_path));
+    }
+    if output_file.is_symlink() && !output_file.canonicalize()?.starts_with(&root) {
+        return Err(outside_folder(&output_file));
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&output_file)?;
+
+    file.write_all(contents)?;
+    Ok(output_file)
+}
+
+/// Move the files of a project to the subfolder and name of another project, returns the moved
+/// files at their new paths. Files that weren't written are skipped
+pub fn move_project_files(
+    folder: &Path,
+    from: (&str, &str),
+    to: (&str, &str),
+) -> io::Result<Vec<PathBuf>> {
+    for name in [from.0, from.1, to.0, to.1] {
+        if !is_valid_name(name) {
+            return Err(invalid_name(name));
+        }
+    }
+
+    let mut moved = Vec::new();
+    for suffix in PROJECT_FILE_SUFFIXES {
+        let source = folder.join(from.0).join(format!("{}{}", from.1, suffix));
+        if !source.is_file() {
+            continue;
+        }
+        let destination = folder.join(to.0).join(format!("{}{}", to.1, suffix));
+        fs::create_dir_all(folder.join(to.0))?;
+        fs::rename(&source, &destination)?;
+        moved.push(destination);
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{move_project_files, write_to_output_dir};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_write_to_output_dir() {
+        let base = std::env::temp_dir().join("pstatool-output");
+        let _ = fs::remove_dir_all(&base);
+        let folder = base.join("svg");
+        fs::create_dir_all(&folder).unwrap();
+
+        let path = write_to_output_dir(&folder, "user", "project.svg", b"<svg/>").unwrap();
+        assert_eq!(path, folder.join("user/project.svg"));
+        let path = write_to_output_dir(&folder, "", "user.svg", b"<svg/>").unwrap();
+        assert_eq!(path, folder.join("user.svg"));
+    }
+
+    #[test]
+    fn test_path_traversal() {
+        let base = std::env::temp_dir().join("pstatool-traversal");
+        let _ = fs::remove_dir_all(&base);
+        let folder = base.join("svg");
+        fs::create_dir_all(&folder).unwrap();
+        fs::create_dir_all(base.join("elsewhere")).unwrap();
+
+        assert!(write_to_output_dir(&folder, "..", "project.svg", b"").is_err());
+        assert!(write_to_output_dir(&folder, "../../etc", "project.svg", b"").is_err());
+        assert!(write_to_output_dir(&folder, "user", "../project.svg", b"").is_err());
+        assert!(write_to_output_dir(&folder, "/etc", "project.svg", b"").is_err());
+        assert!(write_to_output_dir(&folder, "user", "", b"").is_err());
+
+        // A link in the output folder that points outside of it
+        symlink(base.join("elsewhere"), folder.join("linked")).unwrap();
+        assert!(write_to_output_dir(&folder, "linked", "project.svg", b"").is_err());
+        symlink(base.join("elsewhere/file.svg"), folder.join("file.svg")).unwrap();
+        fs::write(base.join("elsewhere/file.svg"), "").unwrap();
+        assert!(write_to_output_dir(&folder, "", "file.svg", b"<svg/>").is_err());
+        assert_eq!(fs::read(base.join("elsewhere/file.svg")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_move_project_files() {
+        let folder = std::env::temp_dir().join("pstatool-move");
+        let _ = fs::remove_dir_all(&folder);
+        for file in [
+            "project.svg",
+            "project-badge.svg",
+            "project-shields.json",
+            "other.svg",
+        ] {
+            write_to_output_dir(&folder, "old", file, b"<svg/>").unwrap();
+        }
+
+        let moved = move_project_files(&folder, ("old", "project"), ("new", "renamed")).unwrap();
+        assert_eq!(moved.len(), 3);
+        assert!(folder.join("new/renamed.svg").exists());
+        assert!(folder.join("new/renamed-badge.svg").exists());
+        assert!(folder.join("new/renamed-shields.json").exists());
+        assert!(!folder.join("old/project.svg").exists());
+        assert!(folder.join("old/other.svg").exists());
+        assert!(move_project_files(&folder, ("old", ".."), ("new", "renamed")).is_err());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+//! Card packs: the cards of a user for a profile README, rendered in one go with a shared style
+//!
+//! A pack is written to `<svg folder>/<user>/pack`:
+//! - `projects/<project>.svg`, the card of every enabled project
+//! - `overview.svg`, the combined stats of all projects
+//! - `top-languages.svg`, a donut of the most used languages
+//! - `history.svg`, the total lines at the end of every month
+//!
+//! With `--static-cards` the project, overview and top languages cards also get a `.static.svg`
+
+use crate::error::PstatoolError;
+use crate::model::{CardMetadata, CardOptions, CardStyle, ClocData, PipelineSettings};
+use crate::output::{is_valid_name, write_to_output_dir};
+use crate::svg::{CardVariants, HistoryPoint};
+use crate::{collect_card_metadata, db, svg};
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PACK_LANGUAGES: usize = 8;
+const DEFAULT_HISTORY_MONTHS: u32 = 12;
+
+/// The cards of a user, defined in the `packs` of the config file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackConfig {
+    pub user: String,
+    /// Style of the project and overview cards, `--card-style` by default
+    pub card_style: Option<CardStyle>,
+    /// Languages on the top languages card
+    #[serde(default = "default_top_languages")]
+    pub top_languages: usize,
+    /// Months on the history chart
+    #[serde(default = "default_history_months")]
+    pub history_months: u32,
+}
+
+fn default_top_languages() -> usize {
+    DEFAULT_PACK_LANGUAGES
+}
+
+fn default_history_months() -> u32 {
+    DEFAULT_HISTORY_MONTHS
+}
+
+impl PackConfig {
+    /// A pack with the default settings
+    pub fn new(user: &str) -> Self {
+        PackConfig {
+            user: user.to_string(),
+            card_style: None,
+            top_languages: DEFAULT_PACK_LANGUAGES,
+            history_months: DEFAULT_HISTORY_MONTHS,
+        }
+    }
+}
+
+/// Folder the pack of a user is written to
+pub fn pack_folder(svg_folder: &Path, user: &str) -> PathBuf {
+    svg_folder.join(user).join("pack")
+}
+
+/// The first day of the month `months_ago` months before `today`, and of the month after it
+fn month_bounds(today: NaiveDate, months_ago: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = today
+        .with_day(1)?
+        .checked_sub_months(Months::new(months_ago))?;
+    Some((start, start.checked_add_months(Months::new(1))?))
+}
+
+/// Write a card to the pack, and its static snapshot with `--static-cards`
+fn write_pack_card(
+    folder: &Path,
+    sub_folder: &str,
+    name: &str,
+    card: &CardVariants,
+    options: &CardOptions,
+    written: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let file_name = format!("{}.svg", name);
+    written.push(write_to_output_dir(
+        folder,
+        sub_folder,
+        &file_name,
+        card.animated.as_bytes(),
+    )?);
+    if options.static_variant {
+        let file_name = format!("{}.static.svg", name);
+        written.push(write_to_output_dir(
+            folder,
+            sub_folder,
+            &file_name,
+            card.snapshot.as_bytes(),
+        )?);
+    }
+    Ok(())
+}
+
+/// Total lines of all projects of the user at the end of the last months, starting at the first
+/// month with stats
+async fn history(db_url: &str, user: &str, months: u32) -> Result<Vec<HistoryPoint>, sqlx::Error> {
+    let today = Utc::now().date_naive();
+    let mut points = Vec::new();
+    for months_ago in (0..months).rev() {
+        let Some((start, end)) = month_bounds(today, months_ago) else {
+            continue;
+        };
+        let end = end.and_time(Default::default()).and_utc();
+        let stats = db::get_language_totals(db_url, Some(user), DateTime::UNIX_EPOCH, end).await?;
+        let total_lines = stats.languages.values().map(|s| s.total_lines()).sum();
+        if total_lines > 0 || !points.is_empty() {
+            points.push(HistoryPoint {
+                label: start.format("%Y-%m").to_string(),
+                total_lines,
+            });
+        }
+    }
+    Ok(points)
+}
+
+/// Render the cards of a pack from the stored stats, returns the written files
+pub async fn render_pack(
+    pack: &PackConfig,
+    db_url: &str,
+    settings: &PipelineSettings,
+) -> Result<Vec<PathBuf>, PstatoolError> {
+    if !is_valid_name(&pack.user) {
+        let message = format!("Invalid user name: {:?}", pack.user);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    }
+
+    // The files of the projects are not stored, so treemaps use the default layout
+    let mut style = pack.card_style.unwrap_or(settings.card_defaults.style);
+    if style == CardStyle::Treemap {
+        style = CardStyle::Bar;
+    }
+
+    let folder = pack_folder(&settings.svg_folder, &pack.user);
+    std::fs::create_dir_all(&folder)?;
+    let mut written = Vec::new();
+
+    let projects = db::get_enabled_projects(db_url).await?;
+    let mut all_stats: Vec<ClocData> = Vec::new();
+    for project in projects.iter().filter(|p| p.github_user == pack.user) {
+        let cloc_data =
+            db::get_project_stats(db_url, &project.github_user, &project.project_name).await?;
+        if cloc_data.languages.is_empty() {
+            continue;
+        }
+
+        let mut options = CardOptions::for_project(project, &settings.card_defaults);
+        options.style = style;
+        let metadata = collect_card_metadata(project, &cloc_data, &options, Some(db_url)).await;
+        let card = svg::generate_svg_variants(&project.title, &cloc_data, &options, &metadata)?;
+        write_pack_card(
+            &folder,
+            "projects",
+            &project.project_name,
+            &card,
+            &options,
+            &mut written,
+        )?;
+        all_stats.push(cloc_data);
+    }
+
+    let combined = ClocData::aggregate(&all_stats);
+    let options = CardOptions {
+        style,
+        ..settings.card_defaults.clone()
+    };
+    let overview =
+        svg::generate_svg_variants(&pack.user, &combined, &options, &CardMetadata::default())?;
+    write_pack_card(&folder, "", "overview", &overview, &options, &mut written)?;
+
+    let options = CardOptions {
+        style: CardStyle::Donut,
+        top_languages: pack.top_languages,
+        ..settings.card_defaults.clone()
+    };
+    let top_languages =
+        svg::generate_svg_variants(&pack.user, &combined, &options, &CardMetadata::default())?;
+    write_pack_card(
+        &folder,
+        "",
+        "top-languages",
+        &top_languages,
+        &options,
+        &mut written,
+    )?;
+
+    let points = history(db_url, &pack.user, pack.history_months).await?;
+    if points.is_empty() {
+        log::warn!("No history of {} to chart yet", pack.user);
+    } else {
+        let chart = svg::generate_history_svg(&pack.user, &points, &combined)?;
+        written.push(write_to_output_dir(
+            &folder,
+            "",
+            "history.svg",
+            chart.as_bytes(),
+        )?);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pack::{month_bounds, PackConfig};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_month_bounds() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert_eq!(
+            month_bounds(date(2026, 10, 16), 0),
+            Some((date(2026, 10, 1), date(2026, 11, 1)))
+        );
+        assert_eq!(
+            month_bounds(date(2026, 3, 31), 3),
+            Some((date(2025, 12, 1), date(2026, 1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_pack_defaults() {
+        let pack: PackConfig = serde_yaml::from_str("user: wdudokvanheel").unwrap();
+        assert_eq!(pack, PackConfig::new("wdudokvanheel"));
+    }
+}
@@ -0,0 +1,158 @@
+//! Progress bars of a run in a terminal, a line per project with the stage it is in and a line
+//! with the number of processed projects
+//!
+//! The bars are drawn by a [MultiProgress], the logger has to be wrapped with the same
+//! [MultiProgress] so log messages are printed above the bars instead of through them
+
+use crate::events::{EventObserver, PipelineEvent, Stage};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_millis(120);
+
+/// Observer that shows the status of the projects as progress bars
+pub struct ProgressObserver {
+    multi: MultiProgress,
+    total: ProgressBar,
+    projects: Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl ProgressObserver {
+    pub fn new(multi: MultiProgress) -> Self {
+        let total = multi.add(ProgressBar::new_spinner());
+        total.set_style(
+            ProgressStyle::with_template("{spinner} {pos} projects processed in {elapsed}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        total.enable_steady_tick(TICK);
+        ProgressObserver {
+            multi,
+            total,
+            projects: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remove the bars, e.g. before printing the summary of the run
+    pub fn finish(&self) {
+        for (_, bar) in self.projects.lock().unwrap().drain() {
+            bar.finish_and_clear();
+        }
+        self.total.finish_and_clear();
+    }
+}
+
+impl EventObserver for ProgressObserver {
+    fn on_event(&self, event: &PipelineEvent) {
+        let mut projects = self.projects.lock().unwrap();
+        match event {
+            PipelineEvent::ProjectStarted { project } => {
+                let bar = self
+                    .multi
+                    .insert_before(&self.total, ProgressBar::new_spinner());
+                bar.set_style(
+                    ProgressStyle::with_template("  {spinner} {prefix}: {msg}")
+                        .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+                );
+                bar.set_prefix(project.clone());
+                bar.enable_steady_tick(TICK);
+                projects.insert(project.clone(), bar);
+            }
+            PipelineEvent::ProjectFinished { project } => {
+                if let Some(bar) = projects.remove(project) {
+                    bar.finish_and_clear();
+                }
+                self.total.inc(1);
+                return;
+            }
+            _ => {}
+        }
+
+        if let (Some(bar), Some(status)) = (projects.get(event_project(event)), status(event)) {
+            bar.set_message(status);
+        }
+    }
+}
+
+fn event_project(event: &PipelineEvent) -> &str {
+    match event {
+        PipelineEvent::ProjectStarted { project }
+        | PipelineEvent::CloneProgress { project, .. }
+        | PipelineEvent::Counted { project, .. }
+        | PipelineEvent::Rendered { project, .. }
+        | PipelineEvent::Preview { project, .. }
+        | PipelineEvent::Saved { project }
+        | PipelineEvent::StageFinished { project, .. }
+        | PipelineEvent::Unchanged { project, .. }
+        | PipelineEvent::Warning { project, .. }
+        | PipelineEvent::Failed { project, .. }
+        | PipelineEvent::Cancelled { project, .. }
+        | PipelineEvent::ProjectFinished { project } => project,
+    }
+}
+
+/// The status of a project after `event`, `None` when the event doesn't change it
+pub fn status(event: &PipelineEvent) -> Option<String> {
+    let status = match event {
+        PipelineEvent::ProjectStarted { .. } => "cloning".to_string(),
+        PipelineEvent::CloneProgress {
+            received_objects,
+            total_objects,
+            ..
+        } => format!("cloning {}/{} objects", received_objects, total_objects),
+        PipelineEvent::StageFinished { stage, .. } => match stage {
+            Stage::Clone => "counting".to_string(),
+            Stage::Count => "rendering".to_string(),
+            Stage::Render => "saving".to_string(),
+            // The cleanup runs after the count and the save finishes the project
+            Stage::Save | Stage::Cleanup => return None,
+        },
+        PipelineEvent::Unchanged { .. } => "unchanged".to_string(),
+        PipelineEvent::Failed { stage, .. } => {
+            format!("failed to {}", stage.as_str())
+        }
+        PipelineEvent::Cancelled { .. } => "cancelled".to_string(),
+        _ => return None,
+    };
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{PipelineEvent, Stage};
+    use crate::progress::status;
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress_status() {
+        let project = "user/project".to_string();
+        let finished = |stage| PipelineEvent::StageFinished {
+            project: project.clone(),
+            stage,
+            duration: Duration::from_secs(1),
+        };
+        assert_eq!(
+            status(&PipelineEvent::CloneProgress {
+                project: project.clone(),
+                received_objects: 5,
+                total_objects: 10,
+            })
+            .as_deref(),
+            Some("cloning 5/10 objects")
+        );
+        assert_eq!(status(&finished(Stage::Clone)).as_deref(), Some("counting"));
+        assert_eq!(status(&finished(Stage::Render)).as_deref(), Some("saving"));
+        assert_eq!(status(&finished(Stage::Cleanup)), None);
+        assert_eq!(
+            status(&PipelineEvent::Failed {
+                project: project.clone(),
+                stage: Stage::Clone,
+                error: "not found".to_string(),
+            })
+            .as_deref(),
+            Some("failed to clone")
+        );
+        assert_eq!(status(&PipelineEvent::Saved { project }), None);
+    }
+}
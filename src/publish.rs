@@ -0,0 +1,184 @@
+//! Publishing of the SVG folder to a git repository, passed with `--publish-repo`
+//!
+//! After a run the branch of the repository is fetched, the files of the SVG folder are copied
+//! over it and committed, and the commit is pushed. Files of the repository that aren't in the SVG
+//! folder are kept, so the cards can live next to a README or in a folder of a profile repository
+
+use crate::hosting::GitCredentials;
+use crate::output::is_valid_name;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// Repository and branch the SVG folder is published to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishTarget {
+    pub repo_url: String,
+    /// Created without history when the repository doesn't have it yet
+    pub branch: String,
+    /// Folder in the repository, the root of the repository when empty
+    pub folder: PathBuf,
+}
+
+/// Commit the files of `source` to the target and push them, `workdir` is an empty folder for
+/// the checkout. Returns the pushed commit, `None` when no file changed
+pub fn publish_folder(
+    source: &Path,
+    target: &PublishTarget,
+    workdir: &Path,
+    credentials: &GitCredentials,
+) -> Result<Option<String>, git2::Error> {
+    let repo = git2::Repository::init(workdir)?;
+    let mut remote = repo.remote("origin", &target.repo_url)?;
+
+    // Not shallow, pushing a commit needs its parent. A branch the remote doesn't have isn't
+    // fetched, listing the branches instead fails for empty repositories
+    let branch_ref = format!("refs/heads/{}", target.branch);
+    let remote_ref = format!("refs/remotes/origin/{}", target.branch);
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(credentials.callbacks());
+    remote.fetch(
+        &[format!("{}:{}", branch_ref, remote_ref)],
+        Some(&mut fetch_options),
+        None,
+    )?;
+    let parent = match repo.revparse_single(&remote_ref) {
+        Ok(object) => {
+            let commit = object.peel_to_commit()?;
+            repo.reset(commit.as_object(), git2::ResetType::Hard, None)?;
+            Some(commit)
+        }
+        Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let destination = workdir.join(&target.folder);
+    let copied = copy_folder(source, &destination)
+        .map_err(|e| git2::Error::from_str(&format!("Failed to copy the SVG folder: {}", e)))?;
+    log::debug!("Copied {} files to {}", copied, destination.display());
+
+    let mut index = repo.index()?;
+    index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree.id())
+    {
+        return Ok(None);
+    }
+
+    let signature = repo
+        .signature()
+        .or_else(|_| git2::Signature::now("pstatool", "pstatool@localhost"))?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let commit = repo.commit(
+        Some(&branch_ref),
+        &signature,
+        &signature,
+        "Update the project statistics",
+        &tree,
+        &parents,
+    )?;
+
+    // A rejected update isn't an error of the push itself
+    let rejected = RefCell::new(None);
+    let mut callbacks = credentials.callbacks();
+    callbacks.push_update_reference(|reference, status| {
+        if let Some(status) = status {
+            *rejected.borrow_mut() = Some(format!("{} was rejected: {}", reference, status));
+        }
+        Ok(())
+    });
+    let mut push_options = git2::PushOptions::new();
+    push_options.remote_callbacks(callbacks);
+    remote.push(&[format!("{0}:{0}", branch_ref)], Some(&mut push_options))?;
+    drop(push_options);
+    if let Some(error) = rejected.into_inner() {
+        return Err(git2::Error::from_str(&error));
+    }
+
+    Ok(Some(commit.to_string()))
+}
+
+/// Copy the files of `source` into `destination`, returns the number of copied files. Hidden files
+/// and names that aren't written by pstatool are skipped, so `.git` is never touched
+fn copy_folder(source: &Path, destination: &Path) -> std::io::Result<usize> {
+    std::fs::create_dir_all(destination)?;
+    let mut copied = 0;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str().filter(|name| is_valid_name(name)) else {
+            continue;
+        };
+        if name.starts_with('.') || name.ends_with(".partial") {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copied += copy_folder(&entry.path(), &destination.join(name))?;
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), destination.join(name))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hosting::GitCredentials;
+    use crate::publish::{publish_folder, PublishTarget};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_publish_folder() {
+        let root = std::env::temp_dir().join("pstatool-publish");
+        let _ = std::fs::remove_dir_all(&root);
+        let origin = root.join("origin.git");
+        git2::Repository::init_bare(&origin).unwrap();
+
+        let svg_folder = root.join("svg");
+        std::fs::create_dir_all(svg_folder.join("user")).unwrap();
+        std::fs::write(svg_folder.join("user/project.svg"), "<svg/>").unwrap();
+        std::fs::write(svg_folder.join(".hidden"), "skipped").unwrap();
+
+        let target = PublishTarget {
+            repo_url: origin.to_string_lossy().to_string(),
+            branch: "gh-pages".to_string(),
+            folder: PathBuf::from("stats"),
+        };
+        let credentials = GitCredentials::default();
+        let first = publish_folder(&svg_folder, &target, &root.join("first"), &credentials)
+            .unwrap()
+            .unwrap();
+        // Nothing changed
+        assert!(
+            publish_folder(&svg_folder, &target, &root.join("second"), &credentials)
+                .unwrap()
+                .is_none()
+        );
+        std::fs::write(svg_folder.join("user/project.svg"), "<svg></svg>").unwrap();
+        let third = publish_folder(&svg_folder, &target, &root.join("third"), &credentials)
+            .unwrap()
+            .unwrap();
+
+        let repo = git2::Repository::open_bare(&origin).unwrap();
+        let head = repo
+            .find_reference("refs/heads/gh-pages")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        assert_eq!(head.id().to_string(), third);
+        assert_eq!(head.parent_id(0).unwrap().to_string(), first);
+        let tree = head.tree().unwrap();
+        assert!(tree
+            .get_path(std::path::Path::new("stats/user/project.svg"))
+            .is_ok());
+        assert!(tree
+            .get_path(std::path::Path::new("stats/.hidden"))
+            .is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use crate::events::NoopObserver;
+use crate::model::PipelineSettings;
+use crate::shutdown::Shutdown;
+use crate::singleflight::SingleFlight;
+use crate::summary::ProcessReport;
+use crate::{db, refresh_project};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Queue of projects to reprocess in the background, formatted as `user/project_name`
+#[derive(Clone)]
+pub struct RefreshQueue {
+    sender: UnboundedSender<(String, String)>,
+    /// Projects waiting in the queue, a project is only queued once until processing starts
+    pending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl RefreshQueue {
+    /// Start a worker that processes the queued projects one by one, until the shutdown starts
+    pub fn spawn(settings: PipelineSettings, shutdown: Shutdown) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let queue = RefreshQueue {
+            sender,
+            pending: Arc::new(Mutex::new(HashSet::new())),
+        };
+        tokio::spawn(run_worker(
+            receiver,
+            queue.pending.clone(),
+            settings,
+            shutdown,
+        ));
+        queue
+    }
+
+    /// Add a project to the queue, returns false when it is already waiting to be processed
+    pub fn enqueue(&self, user: &str, project_name: &str) -> bool {
+        let key = format!("{}/{}", user, project_name);
+        if !self.pending.lock().unwrap().insert(key.clone()) {
+            return false;
+        }
+
+        if self
+            .sender
+            .send((user.to_string(), project_name.to_string()))
+            .is_err()
+        {
+            log::error!("Refresh worker stopped, can't queue {}", key);
+            self.pending.lock().unwrap().remove(&key);
+            return false;
+        }
+        true
+    }
+}
+
+async fn run_worker(
+    mut receiver: UnboundedReceiver<(String, String)>,
+    pending: Arc<Mutex<HashSet<String>>>,
+    settings: PipelineSettings,
+    shutdown: Shutdown,
+) {
+    let refreshes: SingleFlight<ProcessReport> = SingleFlight::new();
+    let Some(db_url) = settings.db_url.clone() else {
+        log::error!("Refreshing projects requires a database");
+        return;
+    };
+
+    loop {
+        let next = tokio::select! {
+            _ = shutdown.stopping() => break,
+            next = receiver.recv() => next,
+        };
+        let Some((user, project_name)) = next else {
+            break;
+        };
+        let Some(_work) = shutdown.begin() else {
+            break;
+        };
+
+        // New requests for this project are queued again, as they might include newer commits
+        pending
+            .lock()
+            .unwrap()
+            .remove(&format!("{}/{}", user, project_name));
+
+        match db::get_project(&db_url, &user, &project_name).await {
+            Ok(Some(project)) => {
+                log::info!("Refreshing project {}/{}", user, project_name);
+                // A push to one of the branches of a project refreshes all of them
+                for project in project.branch_projects() {
+                    let cancel = shutdown.abort_token();
+                    refresh_project(&refreshes, &project, &settings, &NoopObserver, cancel).await;
+                }
+            }
+            Ok(None) => log::warn!("Can't refresh unknown project {}/{}", user, project_name),
+            Err(e) => log::error!("Failed to get project {}/{}: {}", user, project_name, e),
+        }
+    }
+
+    let pending = pending.lock().unwrap().len();
+    if pending > 0 {
+        log::info!(
+            "Stopped the refresh queue, {} queued projects are processed by the next run",
+            pending
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::queue::RefreshQueue;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[tokio::test]
+    async fn test_enqueue_once() {
+        let (sender, mut receiver) = unbounded_channel();
+        let queue = RefreshQueue {
+            sender,
+            pending: Default::default(),
+        };
+
+        assert!(queue.enqueue("user", "project"));
+        assert!(!queue.enqueue("user", "project"));
+        assert!(queue.enqueue("user", "other"));
+
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first, ("user".to_string(), "project".to_string()));
+
+        // Stopped worker
+        drop(receiver);
+        assert!(!queue.enqueue("user", "stopped"));
+        assert!(!queue.pending.lock().unwrap().contains("user/stopped"));
+    }
+}
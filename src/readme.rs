@@ -0,0 +1,315 @@
+//! Pull requests that keep a stats section in the README of a project up to date, enabled with
+//! `--readme-pr`
+//!
+//! Only READMEs with a section between [START_MARKER] and [END_MARKER] are updated. The new README
+//! is committed on top of the counted commit through the Git Data API of GitHub, to the
+//! [PR_BRANCH] branch, and a pull request is opened for it unless one is open already
+
+use crate::discover::{api_client, send};
+use crate::error::PstatoolError;
+use crate::model::{ClocData, NumberFormat};
+use crate::svg::format_number;
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt::Write;
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+
+pub const START_MARKER: &str = "<!-- pstatool:start -->";
+pub const END_MARKER: &str = "<!-- pstatool:end -->";
+/// Branch of the pull requests, the same branch is updated by every run
+pub const PR_BRANCH: &str = "pstatool/readme";
+
+/// Languages in the table of the section
+const SECTION_LANGUAGES: usize = 8;
+
+/// Name of the README in the root of a checkout, the first of the names GitHub shows
+pub fn find_readme(path: &Path) -> Option<String> {
+    let mut names: Vec<String> = std::fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            let lower = name.to_ascii_lowercase();
+            lower == "readme" || lower.starts_with("readme.")
+        })
+        .collect();
+    // README.md before README.rst or README
+    names.sort_by_key(|name| (!name.to_ascii_lowercase().ends_with(".md"), name.clone()));
+    names.into_iter().next()
+}
+
+/// The stats section: the card when its URL is known and a table of the top languages
+pub fn stats_section(title: &str, cloc: &ClocData, card_url: Option<&str>) -> String {
+    let mut section = String::new();
+    if let Some(card_url) = card_url {
+        let _ = writeln!(section, "![Stats for {}]({})\n", title, card_url);
+    }
+
+    let total: u64 = cloc.languages.values().map(|s| s.total_lines()).sum();
+    section.push_str("| Language | Files | Lines | Share |\n| --- | ---: | ---: | ---: |\n");
+    for (language, stats) in cloc.ranked_languages().into_iter().take(SECTION_LANGUAGES) {
+        let _ = writeln!(
+            section,
+            "| {} | {} | {} | {:.1}% |",
+            language,
+            format_number(stats.n_files, NumberFormat::Grouped, None),
+            format_number(stats.total_lines(), NumberFormat::Grouped, None),
+            stats.total_lines() as f64 / total.max(1) as f64 * 100.0
+        );
+    }
+    let _ = writeln!(
+        section,
+        "\n{} lines in total",
+        format_number(total, NumberFormat::Grouped, None)
+    );
+    section
+}
+
+/// The README with `section` between the markers, `None` when it has no markers
+pub fn update_section(readme: &str, section: &str) -> Option<String> {
+    let start = readme.find(START_MARKER)? + START_MARKER.len();
+    let end = start + readme[start..].find(END_MARKER)?;
+    // Keep the line endings of the README
+    let newline = match readme.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+    let section = section.trim_end().replace('\n', newline);
+    Some(format!(
+        "{}{nl}{}{nl}{}",
+        &readme[..start],
+        section,
+        &readme[end..],
+        nl = newline
+    ))
+}
+
+#[derive(Deserialize)]
+struct Sha {
+    sha: String,
+}
+
+#[derive(Deserialize)]
+struct GitCommit {
+    tree: Sha,
+}
+
+#[derive(Deserialize)]
+struct GitRef {
+    object: Sha,
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    html_url: String,
+}
+
+/// Where the README of a project is committed
+pub struct ReadmeTarget<'a> {
+    pub api_url: &'a str,
+    pub user: &'a str,
+    pub repository: &'a str,
+    /// Branch the pull request is opened against
+    pub base_branch: &'a str,
+    /// The counted commit, the parent of the new commit
+    pub base_commit: &'a str,
+    /// Path of the README in the repository
+    pub path: &'a str,
+}
+
+/// Commit `readme` to [PR_BRANCH] and open a pull request for it, returns the URL of the pull
+/// request. `None` when the branch already has this README
+pub async fn open_pull_request(
+    target: &ReadmeTarget<'_>,
+    readme: &str,
+    token: &str,
+    cancel: &CancellationToken,
+) -> Result<Option<String>, PstatoolError> {
+    let client = api_client()?;
+    let repo_url = format!(
+        "{}/repos/{}/{}",
+        target.api_url, target.user, target.repository
+    );
+    let request = |method: reqwest::Method, path: &str| {
+        client
+            .request(method, format!("{}/{}", repo_url, path))
+            .header("Accept", "application/vnd.github+json")
+            .bearer_auth(token)
+    };
+
+    let base: GitCommit = send(
+        request(
+            reqwest::Method::GET,
+            &format!("git/commits/{}", target.base_commit),
+        ),
+        cancel,
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+    let tree: Sha = send(
+        request(reqwest::Method::POST, "git/trees").json(&json!({
+            "base_tree": base.tree.sha,
+            "tree": [{ "path": target.path, "mode": "100644", "type": "blob", "content": readme }],
+        })),
+        cancel,
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+    // The branch of an earlier run can have the same README
+    let branch = send(
+        request(
+            reqwest::Method::GET,
+            &format!("git/ref/heads/{}", PR_BRANCH),
+        ),
+        cancel,
+    )
+    .await?;
+    let existing = match branch.status() {
+        reqwest::StatusCode::NOT_FOUND => None,
+        _ => Some(
+            branch
+                .error_for_status()?
+                .json::<GitRef>()
+                .await?
+                .object
+                .sha,
+        ),
+    };
+    if let Some(existing) = &existing {
+        let commit: GitCommit = send(
+            request(reqwest::Method::GET, &format!("git/commits/{}", existing)),
+            cancel,
+        )
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+        if commit.tree.sha == tree.sha {
+            return Ok(None);
+        }
+    }
+
+    let commit: Sha = send(
+        request(reqwest::Method::POST, "git/commits").json(&json!({
+            "message": "Update the project statistics in the README",
+            "tree": tree.sha,
+            "parents": [target.base_commit],
+        })),
+        cancel,
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+    let updated = match existing {
+        // Replaces the commit of the earlier run
+        Some(_) => request(
+            reqwest::Method::PATCH,
+            &format!("git/refs/heads/{}", PR_BRANCH),
+        )
+        .json(&json!({ "sha": commit.sha, "force": true })),
+        None => request(reqwest::Method::POST, "git/refs").json(&json!({
+            "ref": format!("refs/heads/{}", PR_BRANCH),
+            "sha": commit.sha,
+        })),
+    };
+    send(updated, cancel).await?.error_for_status()?;
+
+    let open: Vec<PullRequest> = send(
+        request(reqwest::Method::GET, "pulls").query(&[
+            ("head", format!("{}:{}", target.user, PR_BRANCH)),
+            ("base", target.base_branch.to_string()),
+            ("state", "open".to_string()),
+        ]),
+        cancel,
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+    if let Some(pull_request) = open.into_iter().next() {
+        return Ok(Some(pull_request.html_url));
+    }
+
+    let pull_request: PullRequest = send(request(reqwest::Method::POST, "pulls").json(&json!({
+        "title": "Update the project statistics",
+        "head": PR_BRANCH,
+        "base": target.base_branch,
+        "body": format!(
+            "Updates the section between `{}` and `{}` of the README with the latest statistics.",
+            START_MARKER, END_MARKER
+        ),
+    })), cancel)
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+    Ok(Some(pull_request.html_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::ClocData;
+    use crate::readme::{find_readme, stats_section, update_section};
+
+    #[test]
+    fn test_update_section() {
+        let readme = "# Project\n\n<!-- pstatool:start -->\nold\n<!-- pstatool:end -->\n\nMore\n";
+        assert_eq!(
+            update_section(readme, "new\n").unwrap(),
+            "# Project\n\n<!-- pstatool:start -->\nnew\n<!-- pstatool:end -->\n\nMore\n"
+        );
+        assert_eq!(
+            update_section("a\r\n<!-- pstatool:start --><!-- pstatool:end -->", "b\nc").unwrap(),
+            "a\r\n<!-- pstatool:start -->\r\nb\r\nc\r\n<!-- pstatool:end -->"
+        );
+        assert!(update_section("# Project\n", "new").is_none());
+        assert!(update_section("<!-- pstatool:end --><!-- pstatool:start -->", "new").is_none());
+    }
+
+    #[test]
+    fn test_stats_section() {
+        let cloc: ClocData = serde_json::from_str(
+            r#"{
+                "header": {"cloc_version": "2.00"},
+                "Rust": {"nFiles": 4, "blank": 100, "comment": 50, "code": 850},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 1500}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            stats_section(
+                "Project",
+                &cloc,
+                Some("https://example.com/user/project.svg")
+            ),
+            "![Stats for Project](https://example.com/user/project.svg)\n\n\
+             | Language | Files | Lines | Share |\n\
+             | --- | ---: | ---: | ---: |\n\
+             | Swift | 1 | 1,500 | 60.0% |\n\
+             | Rust | 4 | 1,000 | 40.0% |\n\
+             \n\
+             2,500 lines in total\n"
+        );
+        assert!(stats_section("Project", &cloc, None).starts_with("| Language |"));
+    }
+
+    #[test]
+    fn test_find_readme() {
+        let root = std::env::temp_dir().join("pstatool-readme");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("readme.d")).unwrap();
+        assert_eq!(find_readme(&root), None);
+        std::fs::write(root.join("README.rst"), "").unwrap();
+        std::fs::write(root.join("Readme.md"), "").unwrap();
+        assert_eq!(find_readme(&root).as_deref(), Some("Readme.md"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
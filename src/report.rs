@@ -0,0 +1,330 @@
+use crate::events::{EventObserver, PipelineEvent};
+use crate::output::write_to_output_dir;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tera::{Context, Tera};
+
+/// Name of the report in the SVG folder
+pub const REPORT_FILE: &str = "report.html";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectStatus {
+    Ok,
+    /// Processed, but with warnings like a missing branch
+    Warning,
+    Failed,
+    Cancelled,
+}
+
+impl ProjectStatus {
+    /// Name of the status, as it is stored with the runs in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectStatus::Ok => "ok",
+            ProjectStatus::Warning => "warning",
+            ProjectStatus::Failed => "failed",
+            ProjectStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Outcome of a single project of a run
+#[derive(Debug, Clone)]
+pub struct ProjectReport {
+    /// Formatted as `user/project_name`
+    pub project: String,
+    pub status: ProjectStatus,
+    /// Time from the start of the project to its last event
+    pub duration: Duration,
+    pub total_lines: Option<u64>,
+    /// Difference with the total lines of the previous run
+    pub delta: Option<i64>,
+    /// Whether the stats were saved to the database
+    pub saved: bool,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+    /// Cards and other files written for the project
+    pub cards: Vec<PathBuf>,
+}
+
+/// Collects the events of a run into a report per project
+pub struct ReportObserver {
+    started_at: DateTime<Utc>,
+    start: Instant,
+    projects: Mutex<Vec<(Instant, ProjectReport)>>,
+}
+
+impl ReportObserver {
+    pub fn new() -> Self {
+        ReportObserver {
+            started_at: Utc::now(),
+            start: Instant::now(),
+            projects: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The reports in the order the projects were started
+    pub fn reports(&self) -> Vec<ProjectReport> {
+        let projects = self.projects.lock().unwrap();
+        projects.iter().map(|(_, report)| report.clone()).collect()
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Default for ReportObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventObserver for ReportObserver {
+    fn on_event(&self, event: &PipelineEvent) {
+        let mut projects = self.projects.lock().unwrap();
+
+        if let PipelineEvent::ProjectStarted { project } = event {
+            projects.push((
+                Instant::now(),
+                ProjectReport {
+                    project: project.clone(),
+                    status: ProjectStatus::Ok,
+                    duration: Duration::ZERO,
+                    total_lines: None,
+                    delta: None,
+                    saved: false,
+                    warnings: Vec::new(),
+                    errors: Vec::new(),
+                    cards: Vec::new(),
+                },
+            ));
+            return;
+        }
+
+        let name = match event {
+            PipelineEvent::ProjectStarted { project }
+            | PipelineEvent::CloneProgress { project, .. }
+            | PipelineEvent::Counted { project, .. }
+            | PipelineEvent::Rendered { project, .. }
+            | PipelineEvent::Preview { project, .. }
+            | PipelineEvent::Saved { project }
+            | PipelineEvent::StageFinished { project, .. }
+            | PipelineEvent::Unchanged { project, .. }
+            | PipelineEvent::Warning { project, .. }
+            | PipelineEvent::Failed { project, .. }
+            | PipelineEvent::Cancelled { project, .. }
+            | PipelineEvent::ProjectFinished { project } => project,
+        };
+        let Some((started, report)) = projects.iter_mut().rev().find(|(_, r)| &r.project == name)
+        else {
+            return;
+        };
+
+        // Not every failure finishes the project, so the last event is taken as the end
+        report.duration = started.elapsed();
+        match event {
+            PipelineEvent::Counted { total_lines, .. } => report.total_lines = Some(*total_lines),
+            PipelineEvent::Rendered { path, .. } => report.cards.push(path.clone()),
+            PipelineEvent::Saved { .. } => report.saved = true,
+            PipelineEvent::Warning { message, .. } => {
+                report.warnings.push(message.clone());
+                if report.status == ProjectStatus::Ok {
+                    report.status = ProjectStatus::Warning;
+                }
+            }
+            PipelineEvent::Failed { stage, error, .. } => {
+                report.errors.push(format!("{:?}: {}", stage, error));
+                if report.status != ProjectStatus::Cancelled {
+                    report.status = ProjectStatus::Failed;
+                }
+            }
+            PipelineEvent::Cancelled { .. } => report.status = ProjectStatus::Cancelled,
+            _ => {}
+        }
+    }
+}
+
+/// A project as shown in the report
+#[derive(Serialize)]
+struct ReportRow<'a> {
+    project: &'a str,
+    status: ProjectStatus,
+    duration: String,
+    total_lines: Option<u64>,
+    delta: Option<i64>,
+    warnings: &'a [String],
+    errors: &'a [String],
+    /// Links relative to the report
+    cards: Vec<String>,
+}
+
+#[derive(Serialize, Default)]
+struct StatusCounts {
+    ok: usize,
+    warning: usize,
+    failed: usize,
+    cancelled: usize,
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds < 60.0 {
+        format!("{:.1}s", seconds)
+    } else {
+        format!(
+            "{}m {:02}s",
+            duration.as_secs() / 60,
+            duration.as_secs() % 60
+        )
+    }
+}
+
+/// Render the report of a run, links to the cards are relative to `svg_folder`
+pub fn render_report(
+    started_at: DateTime<Utc>,
+    duration: Duration,
+    reports: &[ProjectReport],
+    svg_folder: &Path,
+) -> Result<String, tera::Error> {
+    let mut counts = StatusCounts::default();
+    let rows: Vec<ReportRow> = reports
+        .iter()
+        .map(|report| {
+            match report.status {
+                ProjectStatus::Ok => counts.ok += 1,
+                ProjectStatus::Warning => counts.warning += 1,
+                ProjectStatus::Failed => counts.failed += 1,
+                ProjectStatus::Cancelled => counts.cancelled += 1,
+            }
+            ReportRow {
+                project: &report.project,
+                status: report.status,
+                duration: format_duration(report.duration),
+                total_lines: report.total_lines,
+                delta: report.delta,
+                warnings: &report.warnings,
+                errors: &report.errors,
+                cards: report
+                    .cards
+                    .iter()
+                    .map(|card| {
+                        card.strip_prefix(svg_folder)
+                            .unwrap_or(card)
+                            .to_string_lossy()
+                            .to_string()
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert(
+        "started_at",
+        &started_at.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+    );
+    context.insert("duration", &format_duration(duration));
+    context.insert("counts", &counts);
+    context.insert("projects", &rows);
+
+    // Escaped, as errors can contain anything
+    Tera::one_off(include_str!("../assets/report.html"), &context, true)
+}
+
+/// Write the report to the root of the SVG folder
+pub fn write_report(svg_folder: &Path, html: &str) -> io::Result<PathBuf> {
+    write_to_output_dir(svg_folder, "", REPORT_FILE, html.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{EventObserver, PipelineEvent, Stage};
+    use crate::report::{render_report, ProjectStatus, ReportObserver};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    fn project(name: &str) -> String {
+        format!("user/{}", name)
+    }
+
+    #[test]
+    fn test_status_names() {
+        for status in [
+            ProjectStatus::Ok,
+            ProjectStatus::Warning,
+            ProjectStatus::Failed,
+            ProjectStatus::Cancelled,
+        ] {
+            // The JSON of the reports uses the same names as the database
+            assert_eq!(serde_json::to_value(status).unwrap(), status.as_str());
+        }
+    }
+
+    #[test]
+    fn test_report_observer() {
+        let observer = ReportObserver::new();
+        for name in ["ok", "warned", "failed", "cancelled"] {
+            observer.on_event(&PipelineEvent::ProjectStarted {
+                project: project(name),
+            });
+        }
+        observer.on_event(&PipelineEvent::Counted {
+            project: project("ok"),
+            languages: 2,
+            total_files: 10,
+            total_lines: 1200,
+        });
+        observer.on_event(&PipelineEvent::Rendered {
+            project: project("ok"),
+            path: PathBuf::from("/svg/user/ok.svg"),
+        });
+        observer.on_event(&PipelineEvent::Warning {
+            project: project("warned"),
+            message: "Branch main not found".to_string(),
+        });
+        observer.on_event(&PipelineEvent::Failed {
+            project: project("failed"),
+            stage: Stage::Clone,
+            error: "<not found>".to_string(),
+        });
+        observer.on_event(&PipelineEvent::Cancelled {
+            project: project("cancelled"),
+            stage: Stage::Count,
+        });
+
+        let reports = observer.reports();
+        let statuses: Vec<ProjectStatus> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ProjectStatus::Ok,
+                ProjectStatus::Warning,
+                ProjectStatus::Failed,
+                ProjectStatus::Cancelled
+            ]
+        );
+        assert_eq!(reports[0].total_lines, Some(1200));
+
+        let html = render_report(
+            observer.started_at(),
+            Duration::from_secs(75),
+            &reports,
+            Path::new("/svg"),
+        )
+        .unwrap();
+        assert!(html.contains("took 1m 15s"));
+        assert!(html.contains(r#"<a href="user&#x2F;ok.svg">"#));
+        assert!(html.contains("Clone: &lt;not found&gt;"));
+        assert!(html.contains("1 ok, 1 with warnings, 1 failed, 1 cancelled"));
+    }
+}
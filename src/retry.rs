@@ -0,0 +1,235 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How often a failed operation is tried again, the delay doubles after every attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt failed, 0 disables retrying
+    pub retries: u32,
+    /// Delay before the first retry
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, starting at 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Errors of operations that can be retried
+pub trait Transient {
+    /// Whether another attempt may succeed, like after a timeout or a dropped connection. A missing
+    /// repository, rejected credentials or an invalid ref fail the same way every time
+    fn is_transient(&self) -> bool;
+}
+
+/// Whether a request that was answered with the HTTP status may succeed another time
+fn is_transient_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+impl Transient for git2::Error {
+    fn is_transient(&self) -> bool {
+        use git2::ErrorCode;
+        if matches!(
+            self.code(),
+            ErrorCode::Auth
+                | ErrorCode::Certificate
+                | ErrorCode::NotFound
+                | ErrorCode::InvalidSpec
+                | ErrorCode::Invalid
+                | ErrorCode::User
+        ) {
+            return false;
+        }
+        // Refused requests are reported like "unexpected http status code: 404"
+        match self.message().split_once("status code: ") {
+            Some((_, status)) => status.trim().parse().is_ok_and(is_transient_status),
+            None => true,
+        }
+    }
+}
+
+impl Transient for sqlx::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => true,
+            // Connection exceptions, serialization failures and deadlocks, too many connections
+            // and a starting server
+            sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+                code.starts_with("08") || ["40001", "40P01", "53300", "57P03"].contains(&&*code)
+            }),
+            _ => false,
+        }
+    }
+}
+
+impl Transient for reqwest::Error {
+    fn is_transient(&self) -> bool {
+        match self.status() {
+            Some(status) => is_transient_status(status.as_u16()),
+            None => self.is_timeout() || self.is_connect() || self.is_request() || self.is_body(),
+        }
+    }
+}
+
+/// Run `operation` until it succeeds or the retries are exhausted, returns the last error
+///
+/// Failed attempts are logged as warnings, the caller handles the final error. Retrying stops early
+/// when `cancel` is triggered or the error isn't [Transient]
+pub async fn retry<T, E, F, Fut>(
+    policy: RetryPolicy,
+    what: &str,
+    cancel: &CancellationToken,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Display + Transient,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut retry = 0;
+    loop {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if retry >= policy.retries || cancel.is_cancelled() || !error.is_transient() {
+            return Err(error);
+        }
+
+        let delay = policy.delay(retry);
+        log::warn!(
+            "{} failed (attempt {} of {}), retrying in {:?}: {}",
+            what,
+            retry + 1,
+            policy.retries + 1,
+            delay,
+            error
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.cancelled() => return Err(error),
+        }
+        retry += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retry::{retry, RetryPolicy, Transient};
+    use std::cell::Cell;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        retries: 2,
+        backoff: Duration::from_millis(1),
+    };
+
+    impl Transient for &str {
+        fn is_transient(&self) -> bool {
+            *self != "not found"
+        }
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        use git2::{ErrorClass, ErrorCode};
+        let git = |code, class, message| git2::Error::new(code, class, message);
+        assert!(git(ErrorCode::GenericError, ErrorClass::Net, "connection reset").is_transient());
+        assert!(git(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 503"
+        )
+        .is_transient());
+        assert!(!git(
+            ErrorCode::GenericError,
+            ErrorClass::Http,
+            "unexpected http status code: 404"
+        )
+        .is_transient());
+        assert!(!git(ErrorCode::Auth, ErrorClass::Http, "authentication required").is_transient());
+        assert!(!git(
+            ErrorCode::NotFound,
+            ErrorClass::Reference,
+            "Branch x not found"
+        )
+        .is_transient());
+        assert!(!sqlx::Error::RowNotFound.is_transient());
+        assert!(sqlx::Error::PoolTimedOut.is_transient());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(4));
+        // Large retry counts are capped instead of overflowing
+        assert_eq!(policy.delay(40), Duration::from_secs(u32::MAX as u64));
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let cancel = CancellationToken::new();
+
+        // Succeeds on the last attempt
+        let attempts = Cell::new(0);
+        let result = retry(POLICY, "test", &cancel, || {
+            attempts.set(attempts.get() + 1);
+            async {
+                if attempts.get() < 3 {
+                    Err("timeout")
+                } else {
+                    Ok(attempts.get())
+                }
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+
+        // Gives up after the retries
+        attempts.set(0);
+        let result: Result<(), _> = retry(POLICY, "test", &cancel, || {
+            attempts.set(attempts.get() + 1);
+            async { Err("timeout") }
+        })
+        .await;
+        assert_eq!(result, Err("timeout"));
+        assert_eq!(attempts.get(), 3);
+
+        // Errors that fail the same way every time aren't retried
+        attempts.set(0);
+        let result: Result<(), _> = retry(POLICY, "test", &cancel, || {
+            attempts.set(attempts.get() + 1);
+            async { Err("not found") }
+        })
+        .await;
+        assert_eq!(result, Err("not found"));
+        assert_eq!(attempts.get(), 1);
+
+        // Not retried once cancelled
+        cancel.cancel();
+        attempts.set(0);
+        let result: Result<(), _> = retry(POLICY, "test", &cancel, || {
+            attempts.set(attempts.get() + 1);
+            async { Err("cancelled") }
+        })
+        .await;
+        assert_eq!(result, Err("cancelled"));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::PstatoolError;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Variables passed to a counter in a sandbox, all others are removed
+const KEPT_ENV: &[&str] = &["PATH", "LANG", "LC_ALL"];
+
+/// Isolation of the counter from the rest of the system, as it runs over untrusted repositories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sandbox {
+    /// Run the counter as any other program
+    #[default]
+    None,
+    /// Run the counter in the repository with only the variables of [KEPT_ENV]
+    Env,
+    /// Run the counter with bubblewrap: read-only filesystem, empty /tmp and home, no network
+    Bwrap,
+}
+
+impl FromStr for Sandbox {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Sandbox::None),
+            "env" => Ok(Sandbox::Env),
+            "bwrap" | "bubblewrap" => Ok(Sandbox::Bwrap),
+            _ => Err(format!("Unknown sandbox: {}", s)),
+        }
+    }
+}
+
+/// Command to run a counter over the repository, `readable` files stay available in the sandbox
+pub fn counter_command(
+    program: &str,
+    sandbox: Sandbox,
+    repo: &Path,
+    readable: &[&Path],
+) -> Command {
+    let mut command = match sandbox {
+        Sandbox::None => return Command::new(program),
+        Sandbox::Env => Command::new(program),
+        Sandbox::Bwrap => {
+            let mut command = Command::new("bwrap");
+            command
+                .args(["--ro-bind", "/", "/"])
+                .args(["--dev", "/dev"])
+                .args(["--proc", "/proc"])
+                .args(["--tmpfs", "/tmp"])
+                .args(["--tmpfs", "/root"])
+                .args(["--tmpfs", "/home"])
+                .arg("--unshare-all")
+                .arg("--die-with-parent")
+                .arg("--new-session");
+            // The repository and files like the exclude list can be in the hidden folders
+            for path in std::iter::once(repo).chain(readable.iter().copied()) {
+                command.arg("--ro-bind").arg(path).arg(path);
+            }
+            command.arg("--chdir").arg(repo).args(["--", program]);
+            command
+        }
+    };
+
+    command.env_clear().current_dir(repo);
+    for name in KEPT_ENV {
+        if let Some(value) = std::env::var_os(name) {
+            command.env(name, value);
+        }
+    }
+    command
+}
+
+/// Run a counter without blocking the runtime and return its output. The counter is killed when it
+/// runs longer than `timeout`
+pub async fn run_counter(
+    command: Command,
+    program: &str,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, PstatoolError> {
+    let mut command = tokio::process::Command::from(command);
+    command.stdin(Stdio::null()).kill_on_drop(true);
+
+    let output = command.output();
+    let output = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, output).await.map_err(|_| {
+            PstatoolError::Counter(format!("{} timed out after {:?}", program, timeout))
+        })?,
+        None => output.await,
+    }?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(PstatoolError::Counter(format!(
+            "{} failed ({}): {}",
+            program,
+            output.status,
+            stderr.trim()
+        )));
+    }
+    // Warnings, e.g. about files that couldn't be read
+    if !stderr.trim().is_empty() {
+        log::debug!("{}: {}", program, stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sandbox::{counter_command, run_counter, Sandbox};
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::Duration;
+
+    fn args(sandbox: Sandbox) -> Vec<String> {
+        let repo = Path::new("/tmp/pstatool/project");
+        let list = Path::new("/tmp/exclude.txt");
+        let command = counter_command("cloc", sandbox, repo, &[list]);
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_sandbox() {
+        assert_eq!(args(Sandbox::None), vec!["cloc"]);
+        let command = counter_command("cloc", Sandbox::None, Path::new("/repo"), &[]);
+        assert_eq!(command.get_current_dir(), None);
+    }
+
+    #[test]
+    fn test_env_sandbox() {
+        std::env::set_var("PSTATOOL_SANDBOX_SECRET", "secret");
+        let command = counter_command("cloc", Sandbox::Env, Path::new("/repo"), &[]);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/repo")));
+
+        // Cleared variables are listed without a value
+        let secret = command
+            .get_envs()
+            .find(|(name, _)| *name == OsStr::new("PSTATOOL_SANDBOX_SECRET"));
+        assert!(secret.is_none());
+    }
+
+    #[test]
+    fn test_bwrap_sandbox() {
+        let args = args(Sandbox::Bwrap);
+        assert_eq!(args[0], "bwrap");
+        assert!(args.contains(&"--unshare-all".to_string()));
+        let joined = args.join(" ");
+        assert!(joined.contains("--ro-bind /tmp/pstatool/project /tmp/pstatool/project"));
+        assert!(joined.contains("--ro-bind /tmp/exclude.txt /tmp/exclude.txt"));
+        assert!(joined.ends_with("--chdir /tmp/pstatool/project -- cloc"));
+    }
+
+    #[test]
+    fn test_parse_sandbox() {
+        assert_eq!("bubblewrap".parse(), Ok(Sandbox::Bwrap));
+        assert!("docker".parse::<Sandbox>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_counter() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo counted; echo warning >&2"]);
+        let output = run_counter(command, "sh", None).await.unwrap();
+        assert_eq!(output, b"counted\n");
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo 'no such directory' >&2; exit 2"]);
+        let error = run_counter(command, "sh", None).await.unwrap_err();
+        assert!(error.to_string().ends_with("): no such directory"));
+
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let error = run_counter(command, "sleep", Some(Duration::from_millis(100)))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "sleep timed out after 100ms");
+    }
+}
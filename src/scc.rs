@@ -0,0 +1,196 @@
+use crate::error::PstatoolError;
+use crate::model::{
+    ClocConfig, ClocData, ClocFileData, CocomoEstimate, FileStats, LanguageStats, SymlinkPolicy,
+};
+use crate::sandbox::{counter_command, run_counter};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Output of scc with `--format json2`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SccOutput {
+    language_summary: Vec<SccLanguage>,
+    estimated_cost: f64,
+    estimated_schedule_months: f64,
+    estimated_people: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SccLanguage {
+    name: String,
+    code: u64,
+    comment: u64,
+    blank: u64,
+    /// Number of files
+    count: u64,
+    #[serde(default)]
+    files: Option<Vec<SccFile>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SccFile {
+    location: String,
+    code: u64,
+    comment: u64,
+    blank: u64,
+}
+
+async fn execute_scc(config: &ClocConfig, by_file: bool) -> Result<SccOutput, PstatoolError> {
+    // scc never follows links
+    if config.symlinks == SymlinkPolicy::Follow {
+        log::warn!("scc doesn't follow symbolic links, they are skipped");
+    }
+
+    let mut command = counter_command("scc", config.sandbox, &config.path, &[]);
+    command.arg("--format=json2");
+    if by_file {
+        command.arg("--by-file");
+    }
+    if !config.ignored_dirs.is_empty() {
+        command.arg(format!("--exclude-dir={}", config.ignored_dirs.join(",")));
+    }
+
+    command.arg(
+        config
+            .path
+            .to_str()
+            .ok_or_else(|| PstatoolError::Counter("Invalid repository path".to_string()))?,
+    );
+    let output = run_counter(command, "scc", config.timeout).await?;
+
+    parse_scc(&output, config)
+}
+
+/// Parse the output of scc, without the ignored languages as scc can't exclude languages itself
+fn parse_scc(output: &[u8], config: &ClocConfig) -> Result<SccOutput, PstatoolError> {
+    let mut result: SccOutput = serde_json::from_slice(output)?;
+    result.language_summary.retain(|language| {
+        !config
+            .ignored_langs
+            .iter()
+            .any(|ignored| ignored.eq_ignore_ascii_case(&language.name))
+    });
+    Ok(result)
+}
+
+/// Count the lines of code with scc, in the same format as cloc
+pub async fn run_scc(config: &ClocConfig) -> Result<ClocData, PstatoolError> {
+    Ok(to_cloc_data(execute_scc(config, false).await?))
+}
+
+fn to_cloc_data(output: SccOutput) -> ClocData {
+    let mut cloc_data = ClocData {
+        estimate: Some(CocomoEstimate {
+            cost: output.estimated_cost,
+            schedule_months: output.estimated_schedule_months,
+            people: output.estimated_people,
+        }),
+        ..ClocData::default()
+    };
+    for language in output.language_summary {
+        cloc_data.languages.insert(
+            language.name,
+            LanguageStats {
+                n_files: language.count,
+                blank: language.blank,
+                comment: language.comment,
+                code: language.code,
+            },
+        );
+    }
+    cloc_data
+}
+
+/// Count the lines of code per file with scc, in the same format as cloc
+pub async fn run_scc_by_file(config: &ClocConfig) -> Result<ClocFileData, PstatoolError> {
+    Ok(to_cloc_file_data(execute_scc(config, true).await?))
+}
+
+fn to_cloc_file_data(output: SccOutput) -> ClocFileData {
+    let mut files = HashMap::new();
+    for language in output.language_summary {
+        for file in language.files.unwrap_or_default() {
+            files.insert(
+                file.location,
+                FileStats {
+                    blank: file.blank,
+                    comment: file.comment,
+                    code: file.code,
+                    language: language.name.clone(),
+                },
+            );
+        }
+    }
+    ClocFileData {
+        header: Default::default(),
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{ClocConfig, Counter, SymlinkPolicy};
+    use crate::sandbox::Sandbox;
+    use crate::scc::{parse_scc, to_cloc_data, to_cloc_file_data};
+    use std::path::{Path, PathBuf};
+
+    const SCC_OUTPUT: &str = r#"{
+        "languageSummary": [
+            {"Name": "Rust", "Bytes": 5000, "Lines": 130, "Code": 100, "Comment": 10, "Blank": 20,
+             "Complexity": 5, "Count": 2, "WeightedComplexity": 0,
+             "Files": [
+                {"Language": "Rust", "Filename": "main.rs", "Location": "/repo/src/main.rs",
+                 "Lines": 80, "Code": 60, "Comment": 5, "Blank": 15},
+                {"Language": "Rust", "Filename": "lib.rs", "Location": "/repo/src/lib.rs",
+                 "Lines": 50, "Code": 40, "Comment": 5, "Blank": 5}
+             ]},
+            {"Name": "Markdown", "Bytes": 100, "Lines": 12, "Code": 10, "Comment": 0, "Blank": 2,
+             "Complexity": 0, "Count": 1, "WeightedComplexity": 0, "Files": []}
+        ],
+        "estimatedCost": 2742.0,
+        "estimatedScheduleMonths": 1.5,
+        "estimatedPeople": 0.2
+    }"#;
+
+    fn test_config() -> ClocConfig {
+        ClocConfig {
+            path: PathBuf::from("/repo"),
+            ignored_langs: vec!["markdown".to_string()],
+            ignored_dirs: vec![],
+            symlinks: SymlinkPolicy::Skip,
+            counter: Counter::Scc,
+            sandbox: Sandbox::None,
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_scc_to_cloc_data() {
+        let output = parse_scc(SCC_OUTPUT.as_bytes(), &test_config()).unwrap();
+        let cloc_data = to_cloc_data(output);
+
+        assert_eq!(cloc_data.languages.len(), 1);
+        let rust = &cloc_data.languages["Rust"];
+        assert_eq!(rust.n_files, 2);
+        assert_eq!(rust.total_lines(), 130);
+
+        let estimate = cloc_data.estimate.unwrap();
+        assert_eq!(estimate.cost, 2742.0);
+        assert!((estimate.effort_months() - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_scc_by_file() {
+        let output = parse_scc(SCC_OUTPUT.as_bytes(), &test_config()).unwrap();
+        let file_data = to_cloc_file_data(output);
+
+        assert_eq!(file_data.files.len(), 2);
+        assert_eq!(file_data.files["/repo/src/main.rs"].total_lines(), 80);
+        let dirs = file_data.directory_stats(Path::new("/repo"));
+        assert_eq!(dirs[0].name, "src/");
+        assert_eq!(dirs[0].lines, 130);
+    }
+}
@@ -0,0 +1,164 @@
+//! Graceful shutdown of the server on SIGINT or SIGTERM
+//!
+//! The first signal stops new work: the server stops accepting connections and refuses to
+//! register projects, and the refresh queue and discovery stop. Projects in progress can finish
+//! during the grace period, after it (or on the second signal) they are cancelled between their
+//! stages. Their temp folders are removed and their database writes complete either way
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    /// Cancelled by the first signal, no new work is started
+    stopping: CancellationToken,
+    /// Cancelled when the grace period ends, projects in progress stop between their stages
+    abort: CancellationToken,
+    /// Number of projects and discoveries in progress
+    active: Arc<AtomicUsize>,
+    idle: Arc<Notify>,
+}
+
+/// Work in progress, the shutdown waits until it is dropped
+#[derive(Debug)]
+pub struct WorkGuard {
+    shutdown: Shutdown,
+}
+
+impl Drop for WorkGuard {
+    fn drop(&mut self) {
+        if self.shutdown.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shutdown.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    /// Start new work, `None` once the shutdown started
+    pub fn begin(&self) -> Option<WorkGuard> {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        let guard = WorkGuard {
+            shutdown: self.clone(),
+        };
+        // Checked after counting the work, so `wait_idle` can't miss it
+        (!self.is_stopping()).then_some(guard)
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping.is_cancelled()
+    }
+
+    /// Resolves when the shutdown starts
+    pub async fn stopping(&self) {
+        self.stopping.cancelled().await
+    }
+
+    /// Cancellation of the projects in progress, cancelled when the grace period ends
+    pub fn abort_token(&self) -> &CancellationToken {
+        &self.abort
+    }
+
+    /// Stop new work
+    pub fn stop(&self) {
+        self.stopping.cancel();
+    }
+
+    /// Cancel the projects in progress
+    pub fn abort(&self) {
+        self.stop();
+        self.abort.cancel();
+    }
+
+    /// Wait until the work in progress finished
+    pub async fn wait_idle(&self) {
+        loop {
+            // Registered before the check, so a notification in between isn't lost
+            let idle = self.idle.notified();
+            if self.active.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Stop on SIGINT or SIGTERM, abort after `grace` or on the second signal. The process exits
+    /// right away on the third signal
+    pub async fn listen(self, grace: Duration) {
+        wait_for_signal().await;
+        log::info!(
+            "Shutting down, waiting up to {:?} for {} projects in progress",
+            grace,
+            self.active.load(Ordering::SeqCst)
+        );
+        self.stop();
+
+        tokio::select! {
+            _ = tokio::time::sleep(grace) => log::warn!("Grace period ended, cancelling the projects in progress"),
+            _ = wait_for_signal() => log::warn!("Cancelling the projects in progress"),
+            _ = self.wait_idle() => return,
+        }
+        self.abort();
+
+        wait_for_signal().await;
+        log::warn!("Exiting without cleaning up, temp folders may be left behind");
+        std::process::exit(130);
+    }
+}
+
+/// Resolves on SIGINT (Ctrl-C) or SIGTERM
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => log::warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shutdown::Shutdown;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_for_work() {
+        let shutdown = Shutdown::new();
+        let guard = shutdown.begin().unwrap();
+
+        shutdown.stop();
+        assert!(shutdown.begin().is_none());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), shutdown.wait_idle())
+                .await
+                .is_err()
+        );
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            drop(guard);
+        });
+        tokio::time::timeout(Duration::from_secs(5), shutdown.wait_idle())
+            .await
+            .unwrap();
+        assert!(!shutdown.abort_token().is_cancelled());
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Runs at most one task per key at a time, concurrent callers with the same key await the
+/// result of the running task instead of starting their own
+pub struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `task` unless a task for `key` is already running, in which case its result is returned.
+    /// When the running task is dropped before finishing, one of the waiting callers takes over
+    pub async fn run<F, Fut>(&self, key: &str, task: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(OnceCell::new()))
+            .clone();
+
+        let result = cell.get_or_init(task).await.clone();
+
+        // Callers arriving after this point start a new task instead of getting a stale result
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(key);
+        }
+
+        result
+    }
+}
+
+impl<T: Clone> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::singleflight::SingleFlight;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_runs_are_deduplicated() {
+        let flights = SingleFlight::new();
+        let runs = AtomicUsize::new(0);
+        let task = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            42
+        };
+
+        let (a, b, c) = tokio::join!(
+            flights.run("user/project", task),
+            flights.run("user/project", task),
+            flights.run("user/project", task)
+        );
+
+        assert_eq!((a, b, c), (42, 42, 42));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_and_later_runs() {
+        let flights = SingleFlight::new();
+        let runs = AtomicUsize::new(0);
+        let task = || async { runs.fetch_add(1, Ordering::SeqCst) };
+
+        let (a, b) = tokio::join!(flights.run("user/a", task), flights.run("user/b", task));
+        assert_ne!(a, b);
+
+        // A finished run is not reused
+        assert_eq!(flights.run("user/a", task).await, 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
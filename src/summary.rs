@@ -0,0 +1,243 @@
+use crate::events::{EventObserver, PipelineEvent, Stage};
+use crate::model::ClocData;
+use crate::report::{format_duration, ProjectStatus};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stages shown in the summary table, in the order they run
+const STAGES: [Stage; 5] = [
+    Stage::Clone,
+    Stage::Count,
+    Stage::Render,
+    Stage::Save,
+    Stage::Cleanup,
+];
+
+/// Outcome of processing a single project
+#[derive(Debug, Clone)]
+pub struct ProcessReport {
+    /// Formatted as `user/project_name`
+    pub project: String,
+    pub status: ProjectStatus,
+    pub started_at: DateTime<Utc>,
+    /// Time spent per stage, stages that didn't run are missing
+    pub durations: Vec<(Stage, Duration)>,
+    pub duration: Duration,
+    pub total_lines: Option<u64>,
+    pub errors: Vec<String>,
+    /// The counted stats, `None` when the project wasn't counted
+    pub stats: Option<ClocData>,
+}
+
+impl ProcessReport {
+    pub fn stage_duration(&self, stage: Stage) -> Option<Duration> {
+        self.durations
+            .iter()
+            .filter(|(s, _)| *s == stage)
+            .map(|(_, duration)| *duration)
+            .reduce(|a, b| a + b)
+    }
+}
+
+/// Forwards the events of a single project and collects them into a report
+pub struct ReportCollector<'a> {
+    inner: &'a dyn EventObserver,
+    start: Instant,
+    report: Mutex<ProcessReport>,
+}
+
+impl<'a> ReportCollector<'a> {
+    pub fn new(project: &str, inner: &'a dyn EventObserver) -> Self {
+        ReportCollector {
+            inner,
+            start: Instant::now(),
+            report: Mutex::new(ProcessReport {
+                project: project.to_string(),
+                status: ProjectStatus::Ok,
+                started_at: Utc::now(),
+                durations: Vec::new(),
+                duration: Duration::ZERO,
+                total_lines: None,
+                errors: Vec::new(),
+                stats: None,
+            }),
+        }
+    }
+
+    /// The report of the events so far
+    pub fn snapshot(&self) -> ProcessReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub fn finish(self, stats: Option<ClocData>) -> ProcessReport {
+        let mut report = self.report.into_inner().unwrap();
+        report.duration = self.start.elapsed();
+        report.stats = stats;
+        report
+    }
+}
+
+impl EventObserver for ReportCollector<'_> {
+    fn on_event(&self, event: &PipelineEvent) {
+        self.inner.on_event(event);
+
+        let mut report = self.report.lock().unwrap();
+        match event {
+            PipelineEvent::Counted { total_lines, .. } => report.total_lines = Some(*total_lines),
+            PipelineEvent::StageFinished {
+                stage, duration, ..
+            } => report.durations.push((*stage, *duration)),
+            PipelineEvent::Warning { .. } if report.status == ProjectStatus::Ok => {
+                report.status = ProjectStatus::Warning
+            }
+            PipelineEvent::Failed { stage, error, .. } => {
+                report.errors.push(format!("{:?}: {}", stage, error));
+                if report.status != ProjectStatus::Cancelled {
+                    report.status = ProjectStatus::Failed;
+                }
+            }
+            PipelineEvent::Cancelled { .. } => report.status = ProjectStatus::Cancelled,
+            _ => {}
+        }
+    }
+}
+
+/// Reports of all projects of a run
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub reports: Vec<ProcessReport>,
+}
+
+impl RunSummary {
+    pub fn count(&self, status: ProjectStatus) -> usize {
+        self.reports.iter().filter(|r| r.status == status).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.count(ProjectStatus::Failed) > 0
+    }
+
+    /// A table with a row per project, followed by the errors of the failed projects
+    pub fn table(&self) -> String {
+        let width = self
+            .reports
+            .iter()
+            .map(|r| r.project.len())
+            .chain(["Project".len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut table = format!("{:<width$}  {:<9}  {:>10}", "Project", "Status", "Lines");
+        for stage in STAGES {
+            let _ = write!(table, "  {:>7}", format!("{:?}", stage));
+        }
+        let _ = write!(table, "  {:>7}", "Total");
+
+        for report in &self.reports {
+            let status = report.status.as_str();
+            let lines = report
+                .total_lines
+                .map(|lines| lines.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let _ = write!(
+                table,
+                "\n{:<width$}  {:<9}  {:>10}",
+                report.project, status, lines
+            );
+            for stage in STAGES {
+                let duration = report
+                    .stage_duration(stage)
+                    .map(format_duration)
+                    .unwrap_or_else(|| "-".to_string());
+                let _ = write!(table, "  {:>7}", duration);
+            }
+            let _ = write!(table, "  {:>7}", format_duration(report.duration));
+        }
+
+        let _ = write!(
+            table,
+            "\n{} ok, {} with warnings, {} failed, {} cancelled",
+            self.count(ProjectStatus::Ok),
+            self.count(ProjectStatus::Warning),
+            self.count(ProjectStatus::Failed),
+            self.count(ProjectStatus::Cancelled)
+        );
+        for report in self.reports.iter().filter(|r| !r.errors.is_empty()) {
+            for error in &report.errors {
+                let _ = write!(table, "\n{}: {}", report.project, error);
+            }
+        }
+        table
+    }
+
+    pub fn log(&self) {
+        for line in self.table().lines() {
+            log::info!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{EventObserver, NoopObserver, PipelineEvent, Stage};
+    use crate::report::ProjectStatus;
+    use crate::summary::{ReportCollector, RunSummary};
+    use std::time::Duration;
+
+    fn collect(name: &str, events: &[PipelineEvent]) -> crate::summary::ProcessReport {
+        let collector = ReportCollector::new(name, &NoopObserver);
+        for event in events {
+            collector.on_event(event);
+        }
+        collector.finish(None)
+    }
+
+    #[test]
+    fn test_run_summary() {
+        let ok = collect(
+            "user/ok",
+            &[
+                PipelineEvent::StageFinished {
+                    project: "user/ok".to_string(),
+                    stage: Stage::Clone,
+                    duration: Duration::from_millis(1500),
+                },
+                PipelineEvent::Counted {
+                    project: "user/ok".to_string(),
+                    languages: 2,
+                    total_files: 10,
+                    total_lines: 1200,
+                },
+            ],
+        );
+        assert_eq!(ok.status, ProjectStatus::Ok);
+        assert_eq!(ok.total_lines, Some(1200));
+        assert_eq!(
+            ok.stage_duration(Stage::Clone),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(ok.stage_duration(Stage::Count), None);
+
+        let failed = collect(
+            "user/failed",
+            &[PipelineEvent::Failed {
+                project: "user/failed".to_string(),
+                stage: Stage::Clone,
+                error: "Repository not found".to_string(),
+            }],
+        );
+        assert_eq!(failed.status, ProjectStatus::Failed);
+
+        let summary = RunSummary {
+            reports: vec![ok, failed],
+        };
+        assert!(summary.has_failures());
+        let table = summary.table();
+        assert!(table.starts_with("Project      Status"));
+        assert!(table.contains("\nuser/ok      ok               1200     1.5s"));
+        assert!(table.contains("1 ok, 0 with warnings, 1 failed, 0 cancelled"));
+        assert!(table.contains("user/failed: Clone: Repository not found"));
+    }
+}
@@ -1,80 +1,256 @@
-use crate::model::{ClocData, Language};
-use once_cell::sync::Lazy;
-use sqlx::Error;
+use crate::compare::Comparison;
+use crate::error::PstatoolError;
+use crate::model::{
+    CardMetadata, CardOptions, CardStyle, ClocData, DirectoryStats, Language, NumberFormat, Theme,
+};
+use chrono::Utc;
+use once_cell::sync::{Lazy, OnceCell};
+use resvg::{tiny_skia, usvg};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+use tera::{Context, Tera};
 
-static LANGUAGE_COLORS: Lazy<HashMap<String, String>> = Lazy::new(|| {
-    let yaml_str = include_str!("../assets/langs.yml");
-    load_language_colors(yaml_str)
+/// Colors of the languages by their cloc name
+static LANGUAGE_COLORS: OnceCell<HashMap<String, String>> = OnceCell::new();
+
+/// Display names of languages per locale
+pub type LanguageNames = HashMap<String, HashMap<String, String>>;
+
+static LANGUAGE_NAMES: OnceCell<LanguageNames> = OnceCell::new();
+
+/// Layout of the cards with the header and footer, the card templates fill its `body` block
+pub const BASE_TEMPLATE: &str = "base.svg";
+/// Macros with the colors and fonts of the cards
+pub const THEME_TEMPLATE: &str = "theme.svg";
+/// Theme partials of the themes other than the default `theme.svg`
+const THEMES: &[(Theme, &str)] = &[(Theme::Light, include_str!("../assets/themes/light.svg"))];
+pub const CARD_TEMPLATE: &str = "card.svg";
+pub const TREEMAP_TEMPLATE: &str = "treemap.svg";
+pub const DONUT_TEMPLATE: &str = "donut.svg";
+pub const BADGE_TEMPLATE: &str = "badge.svg";
+pub const COMPARE_TEMPLATE: &str = "compare.svg";
+pub const HISTORY_TEMPLATE: &str = "history.svg";
+pub const DIRECTORIES_TEMPLATE: &str = "directories.svg";
+/// Languages shown on a comparison card, more don't fit
+const MAX_COMPARE_LANGUAGES: usize = 5;
+/// Width of the longest bar on a comparison card
+const COMPARE_BAR_WIDTH: f64 = 120.0;
+const HISTORY_WIDTH: f64 = 250.0;
+const HISTORY_HEIGHT: f64 = 90.0;
+const DONUT_RADIUS: f64 = 40.0;
+/// Rows of the directory card, the smaller directories are grouped as "Other"
+const MAX_DIRECTORY_ROWS: usize = 6;
+/// Width of the names and percentages next to the bars of the directory card
+const DIRECTORY_LABELS_WIDTH: f64 = 130.0;
+const MAX_TREEMAP_DIRECTORIES: usize = 12;
+/// Height of the header above the treemap and the footer below it
+const TREEMAP_MARGIN: u32 = 90;
+/// Space between the border of a card and its content
+const CARD_MARGIN: u32 = 25;
+pub const OTHER_LANGUAGE: &str = "Other";
+/// Color of the bucket of the languages that didn't make the top languages
+const OTHER_COLOR: &str = "#cccccc";
+
+static TEMPLATES: OnceCell<Templates> = OnceCell::new();
+
+/// The templates of all cards, and of the projects with their own templates
+struct Templates {
+    default: Tera,
+    /// The default templates with the theme partial of the other themes
+    themes: HashMap<Theme, Tera>,
+    /// By `user/project`
+    projects: HashMap<String, Tera>,
+}
+
+static RASTER_OPTIONS: Lazy<usvg::Options<'static>> = Lazy::new(|| {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    options
 });
 
-#[derive(Debug)]
+/// Context exposed to the card templates, see the README for a description of every field
+#[derive(Debug, Serialize)]
 pub struct SvgTemplateData {
-    total_lines: u64,
-    total_files: u64,
+    pub total_lines: u64,
+    pub total_files: u64,
+    /// The totals in the number format of the card, e.g. `128,734`
+    pub formatted_lines: String,
+    pub formatted_files: String,
+    /// Percentage of comments in the code and comment lines, `None` without comments
+    pub comment_density: Option<f64>,
+    pub languages: Vec<LanguageShare>,
+    /// Width of the language bar, see [bar_width]
+    pub bar_width: f64,
+    /// Summary of the totals and languages for screen readers, the `<desc>` of the card
+    pub description: String,
     bar: String,
     left_block: String,
     right_block: String,
 }
 
-pub fn cloc_to_svg_template_data(cloc: &ClocData) -> SvgTemplateData {
-    let default_color = "#cccccc";
+#[derive(Debug, Serialize)]
+pub struct LanguageShare {
+    pub name: String,
+    pub lines: u64,
+    pub files: u64,
+    pub percentage: f64,
+    pub color: String,
+    /// Position in the 250px language bar
+    pub x: f64,
+    pub width: f64,
+    /// GitHub code search for the language in the repository, escaped for use in an attribute
+    pub link: Option<String>,
+    /// Text of the language in the legend, e.g. `Rust 61.30%` or `Rust 45.2k (61.3%)`
+    pub legend: String,
+}
+
+/// Percent-encode a value for the query string of a URL
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Link to the GitHub code search for the files of `language` in `repository` (`user/project`)
+pub fn language_search_url(repository: &str, language: &str) -> String {
+    // Languages with spaces or symbols like `C++` have to be quoted
+    let query = format!("repo:{} language:\"{}\"", repository, language);
+    format!(
+        "https://github.com/search?q={}&amp;type=code",
+        url_encode(&query)
+    )
+}
+
+/// Width of the language bar and the other content of a card
+pub fn bar_width(options: &CardOptions) -> f64 {
+    options.width.saturating_sub(2 * CARD_MARGIN) as f64
+}
+
+pub fn cloc_to_svg_template_data(cloc: &ClocData, options: &CardOptions) -> SvgTemplateData {
     let total_loc: u64 = cloc
         .languages
         .values()
-        .map(|stats| stats.total_lines())
+        .map(|stats| stats.counted_lines(options.code_only))
         .sum();
     let total_files: u64 = cloc.languages.values().map(|stats| stats.n_files).sum();
+    let link_repository = options
+        .repository
+        .as_deref()
+        .filter(|_| options.language_links);
 
     if total_loc == 0 {
         return SvgTemplateData {
             total_lines: 0,
             total_files: 0,
+            formatted_lines: "0".to_string(),
+            formatted_files: "0".to_string(),
+            comment_density: None,
+            languages: Vec::new(),
+            bar_width: bar_width(options),
+            description: "No code found".to_string(),
             bar: "<svg><!-- No code found --></svg>".to_string(),
             left_block: String::new(),
             right_block: String::new(),
         };
     }
 
-    let mut lang_data: Vec<(String, u64, f64, f64)> = cloc
-        .languages
-        .iter()
+    let mut languages: Vec<LanguageShare> = cloc
+        .ranked_languages()
+        .into_iter()
         .map(|(lang, stats)| {
-            let pct = (stats.total_lines() as f64 / total_loc as f64) * 100.0;
-            let width = (pct / 100.0) * 250.0;
-            (lang.clone(), stats.total_lines(), pct, width)
+            let lines = stats.counted_lines(options.code_only);
+            let pct = (lines as f64 / total_loc as f64) * 100.0;
+            let width = (pct / 100.0) * bar_width(options);
+            let color = language_color(lang);
+            LanguageShare {
+                name: display_name(lang, options.locale.as_deref()),
+                lines,
+                files: stats.n_files,
+                percentage: pct,
+                color,
+                x: 0.0,
+                width,
+                link: link_repository.map(|repository| language_search_url(repository, lang)),
+                legend: String::new(),
+            }
         })
         .collect();
+    if options.code_only {
+        // Stable, languages with the same number of code lines stay sorted by name
+        languages.sort_by_key(|lang| std::cmp::Reverse(lang.lines));
+    }
 
-    lang_data.sort_by(|a, b| b.1.cmp(&a.1));
-    lang_data.truncate(6);
+    // The largest language is always shown
+    let shown = languages
+        .iter()
+        .take(options.top_languages)
+        .enumerate()
+        .take_while(|(i, lang)| *i == 0 || lang.percentage >= options.min_share)
+        .count();
+    if languages.len() > shown {
+        let rest = languages.split_off(shown);
+        languages.push(LanguageShare {
+            name: display_name(OTHER_LANGUAGE, options.locale.as_deref()),
+            lines: rest.iter().map(|lang| lang.lines).sum(),
+            files: rest.iter().map(|lang| lang.files).sum(),
+            percentage: rest.iter().map(|lang| lang.percentage).sum(),
+            color: OTHER_COLOR.to_string(),
+            x: 0.0,
+            width: rest.iter().map(|lang| lang.width).sum(),
+            link: None,
+            legend: String::new(),
+        });
+    }
 
     let mut rects = String::new();
     let mut cumulative_x = 0.0;
-    for (lang, _code, _pct, width) in &lang_data {
-        let default_color_owned = default_color.to_string();
-        let color = LANGUAGE_COLORS.get(lang).unwrap_or(&default_color_owned);
+    for lang in languages.iter_mut() {
+        lang.legend = match options.legend_lines {
+            true => format!(
+                "{} {} ({:.1}%)",
+                lang.name,
+                compact_number(lang.lines),
+                lang.percentage
+            ),
+            false => format!("{} {:.2}%", lang.name, lang.percentage),
+        };
+        lang.x = cumulative_x;
         rects.push_str(&format!(
-            r#"<rect mask="url(#rect-mask)" x="{:.2}" y="0" width="{:.2}" height="8" fill="{}"/>"#,
-            cumulative_x, width, color
+            r#"<rect x="{:.2}" y="0" width="{:.2}" height="8" fill="{}"/>"#,
+            lang.x, lang.width, lang.color
         ));
-        cumulative_x += width;
+        cumulative_x += lang.width;
     }
 
     let mut left_labels = Vec::new();
     let mut right_labels = Vec::new();
 
-    for (i, (lang, _code, pct, _width)) in lang_data.iter().enumerate() {
-        let default_color_owned = default_color.to_string();
-        let color = LANGUAGE_COLORS.get(lang).unwrap_or(&default_color_owned);
+    for (i, lang) in languages.iter().enumerate() {
         let delay = 450 + (i as u32 % 3) * 150;
         let label = format!(
-            r#"<g class="stagger" style="animation-delay: {}ms">
+            r##"<g class="stagger" style="animation-delay: {}ms">
     <circle cx="5" cy="6" r="5" fill="{}"/>
-    <text x="15" y="10" class="lang-name">{} {:.2}%</text>
-</g>"#,
-            delay, color, lang, pct
+    <text x="15" y="10" class="lang-name">{}</text>
+</g>"##,
+            delay,
+            lang.color,
+            escape_xml(&lang.legend),
         );
+        let label = match &lang.link {
+            Some(link) => format!(
+                r#"<a href="{}" target="_blank">{}</a>"#,
+                escape_xml(link),
+                label
+            ),
+            None => label,
+        };
         if i % 2 == 0 {
             left_labels.push(label);
         } else {
@@ -96,85 +272,1886 @@ pub fn cloc_to_svg_template_data(cloc: &ClocData) -> SvgTemplateData {
         .collect::<Vec<_>>()
         .join("\n");
 
+    let formatted_lines =
+        format_number(total_loc, options.number_format, options.locale.as_deref());
+    let formatted_files = format_number(
+        total_files,
+        options.number_format,
+        options.locale.as_deref(),
+    );
+    let shares: Vec<String> = languages
+        .iter()
+        .map(|lang| format!("{} {:.1}%", lang.name, lang.percentage))
+        .collect();
+    let description = format!(
+        "{} lines of code in {} files: {}",
+        formatted_lines,
+        formatted_files,
+        shares.join(", ")
+    );
+
     SvgTemplateData {
         total_lines: total_loc,
-        total_files: total_files,
+        total_files,
+        formatted_lines,
+        formatted_files,
+        comment_density: cloc.comment_density(),
+        languages,
+        bar_width: bar_width(options),
+        description,
         bar: rects,
         left_block: left_group,
         right_block: right_group,
     }
 }
 
-pub fn generate_svg(project_name: &str, cloc: &ClocData) -> Result<String, Error> {
-    let data = cloc_to_svg_template_data(&cloc);
+#[derive(Debug, Serialize)]
+pub struct DonutSegment {
+    name: String,
+    legend: String,
+    link: Option<String>,
+    color: String,
+    percentage: f64,
+    /// Length of the segment on the circumference of the donut
+    length: f64,
+    /// Distance from the start of the circle to the start of the segment
+    offset: f64,
+}
+
+pub fn languages_to_donut_segments(languages: &[LanguageShare]) -> Vec<DonutSegment> {
+    let circumference = 2.0 * std::f64::consts::PI * DONUT_RADIUS;
+    let mut offset = 0.0;
+
+    languages
+        .iter()
+        .map(|lang| {
+            let length = lang.percentage / 100.0 * circumference;
+            let segment = DonutSegment {
+                name: lang.name.clone(),
+                legend: lang.legend.clone(),
+                link: lang.link.clone(),
+                color: lang.color.clone(),
+                percentage: lang.percentage,
+                length,
+                offset,
+            };
+            offset += length;
+            segment
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreemapTile {
+    name: String,
+    language: String,
+    lines: u64,
+    files: u64,
+    percentage: f64,
+    color: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    show_label: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Squarified treemap layout, `values` should be positive and sorted from large to small
+pub fn squarify(values: &[f64], bounds: Rect) -> Vec<Rect> {
+    let total: f64 = values.iter().sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+
+    let scale = bounds.width * bounds.height / total;
+    let areas: Vec<f64> = values.iter().map(|v| v * scale).collect();
+
+    let mut result = Vec::with_capacity(areas.len());
+    let mut free = bounds;
+    let mut start = 0;
+
+    while start < areas.len() {
+        let side = free.width.min(free.height);
+        let mut end = start + 1;
+        while end < areas.len()
+            && worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side)
+        {
+            end += 1;
+        }
+
+        let row = &areas[start..end];
+        let row_area: f64 = row.iter().sum();
+
+        if free.width >= free.height {
+            // Fill a column on the left side of the free space
+            let width = row_area / free.height;
+            let mut y = free.y;
+            for area in row {
+                let height = area / width;
+                result.push(Rect {
+                    x: free.x,
+                    y,
+                    width,
+                    height,
+                });
+                y += height;
+            }
+            free.x += width;
+            free.width -= width;
+        } else {
+            // Fill a row on the top of the free space
+            let height = row_area / free.width;
+            let mut x = free.x;
+            for area in row {
+                let width = area / height;
+                result.push(Rect {
+                    x,
+                    y: free.y,
+                    width,
+                    height,
+                });
+                x += width;
+            }
+            free.y += height;
+            free.height -= height;
+        }
+
+        start = end;
+    }
+
+    result
+}
+
+fn worst_ratio(row: &[f64], side: f64) -> f64 {
+    let sum: f64 = row.iter().sum();
+    let max = row.iter().cloned().fold(f64::MIN, f64::max);
+    let min = row.iter().cloned().fold(f64::MAX, f64::min);
+    let side_sq = side * side;
+    let sum_sq = sum * sum;
+    (side_sq * max / sum_sq).max(sum_sq / (side_sq * min))
+}
+
+pub fn directories_to_treemap_tiles(
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+) -> Vec<TreemapTile> {
+    let locale = options.locale.as_deref();
+    let total_lines: u64 = directories.iter().map(|dir| dir.lines).sum();
+    let mut dirs: Vec<&DirectoryStats> = directories.iter().filter(|dir| dir.lines > 0).collect();
+    dirs.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+
+    let mut entries: Vec<(String, String, u64, u64)> = dirs
+        .iter()
+        .take(MAX_TREEMAP_DIRECTORIES)
+        .map(|dir| {
+            let language = dir.dominant_language().unwrap_or_default().to_string();
+            (dir.name.clone(), language, dir.lines, dir.files)
+        })
+        .collect();
+
+    if dirs.len() > MAX_TREEMAP_DIRECTORIES {
+        let rest = &dirs[MAX_TREEMAP_DIRECTORIES..];
+        entries.push((
+            OTHER_LANGUAGE.to_string(),
+            String::new(),
+            rest.iter().map(|dir| dir.lines).sum(),
+            rest.iter().map(|dir| dir.files).sum(),
+        ));
+    }
+
+    let values: Vec<f64> = entries.iter().map(|entry| entry.2 as f64).collect();
+    let bounds = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: bar_width(options),
+        height: options.height.saturating_sub(TREEMAP_MARGIN) as f64,
+    };
+
+    entries
+        .into_iter()
+        .zip(squarify(&values, bounds))
+        .map(|((name, language, lines, files), rect)| {
+            let color = language_color(&language);
+            TreemapTile {
+                name,
+                language: display_name(&language, locale),
+                lines,
+                files,
+                percentage: lines as f64 / total_lines as f64 * 100.0,
+                color: color.to_string(),
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+                show_label: rect.width >= 45.0 && rect.height >= 16.0,
+            }
+        })
+        .collect()
+}
+
+/// Load the card templates, templates found in `template_dir` override the built-in ones
+pub fn load_templates(template_dir: Option<&Path>) -> Result<Tera, tera::Error> {
+    let mut tera = match template_dir {
+        Some(dir) => {
+            if !dir.is_dir() {
+                return Err(tera::Error::msg(format!(
+                    "Template folder {} does not exist",
+                    dir.display()
+                )));
+            }
+            // Parsed without resolving `extends`, custom templates can extend the built-in ones
+            let glob = dir.join("**").join("*.svg");
+            let custom = Tera::parse(&glob.to_string_lossy())?;
+            for name in custom.get_template_names() {
+                log::debug!("Loaded custom template {}", name);
+            }
+            custom
+        }
+        None => Tera::default(),
+    };
+
+    // Custom templates take precedence over the built-in ones
+    let mut builtin = Tera::default();
+    builtin.add_raw_templates(vec![
+        (BASE_TEMPLATE, include_str!("../assets/base.svg")),
+        (THEME_TEMPLATE, include_str!("../assets/theme.svg")),
+        (CARD_TEMPLATE, include_str!("../assets/template.svg")),
+        (TREEMAP_TEMPLATE, include_str!("../assets/treemap.svg")),
+        (DONUT_TEMPLATE, include_str!("../assets/donut.svg")),
+        (BADGE_TEMPLATE, include_str!("../assets/badge.svg")),
+        (COMPARE_TEMPLATE, include_str!("../assets/compare.svg")),
+        (HISTORY_TEMPLATE, include_str!("../assets/history.svg")),
+        (
+            DIRECTORIES_TEMPLATE,
+            include_str!("../assets/directories.svg"),
+        ),
+    ])?;
+    tera.extend(&builtin)?;
+    escape_values(&mut tera);
+    Ok(tera)
+}
+
+/// Values are escaped, blocks of SVG built by pstatool are marked `safe` in the templates
+fn escape_values(tera: &mut Tera) {
+    tera.autoescape_on(vec![".svg"]);
+    tera.set_escape_fn(escape_xml);
+}
+
+/// Load the templates in the `<user>/<project>` folders of `template_dir`, they override the
+/// templates with the same name for that project only
+pub fn load_project_templates(
+    template_dir: &Path,
+    templates: &Tera,
+) -> Result<HashMap<String, Tera>, PstatoolError> {
+    let mut projects = HashMap::new();
+    for user in std::fs::read_dir(template_dir)? {
+        let user = user?;
+        if !user.file_type()?.is_dir() {
+            continue;
+        }
+        for project in std::fs::read_dir(user.path())? {
+            let project = project?;
+            if !project.file_type()?.is_dir() {
+                continue;
+            }
+            let glob = project.path().join("*.svg");
+            let mut tera = Tera::parse(&glob.to_string_lossy())?;
+            if tera.get_template_names().next().is_none() {
+                continue;
+            }
+            tera.extend(templates)?;
+            escape_values(&mut tera);
+
+            let name = format!(
+                "{}/{}",
+                user.file_name().to_string_lossy(),
+                project.file_name().to_string_lossy()
+            );
+            log::debug!("Loaded the templates of {}", name);
+            projects.insert(name, tera);
+        }
+    }
+    Ok(projects)
+}
+
+/// The default templates with the theme partial replaced by the partial of every theme but the
+/// default one
+pub fn load_theme_templates(templates: &Tera) -> Result<HashMap<Theme, Tera>, tera::Error> {
+    let mut themes = HashMap::new();
+    for (theme, source) in THEMES {
+        let mut tera = templates.clone();
+        tera.add_raw_template(THEME_TEMPLATE, source)?;
+        themes.insert(*theme, tera);
+    }
+    Ok(themes)
+}
+
+/// Set the templates used by `generate_svg`, can only be called once
+pub fn init_templates(template_dir: Option<&Path>) -> Result<(), PstatoolError> {
+    let default = load_templates(template_dir)?;
+    let themes = load_theme_templates(&default)?;
+    let projects = match template_dir {
+        Some(dir) => load_project_templates(dir, &default)?,
+        None => HashMap::new(),
+    };
+    let templates = Templates {
+        default,
+        themes,
+        projects,
+    };
+    if TEMPLATES.set(templates).is_err() {
+        log::warn!("Templates were already initialized");
+    }
+    Ok(())
+}
+
+/// The templates of the project, `user/project`, the templates of the theme or the default
+/// templates. Projects with their own templates use the theme partial of their templates
+fn templates(repository: Option<&str>, theme: Theme) -> &'static Tera {
+    let templates = TEMPLATES.get_or_init(|| {
+        let default = load_templates(None).expect("Failed to parse built-in template");
+        Templates {
+            themes: load_theme_templates(&default).expect("Failed to parse built-in themes"),
+            default,
+            projects: HashMap::new(),
+        }
+    });
+    repository
+        .and_then(|repository| templates.projects.get(repository))
+        .or_else(|| templates.themes.get(&theme))
+        .unwrap_or(&templates.default)
+}
+
+/// Escape a value for use in the text or an attribute of an SVG
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render a template of the project, `user/project`, and check that the result is a valid XML
+/// document
+fn render(
+    repository: Option<&str>,
+    theme: Theme,
+    template: &str,
+    context: &Context,
+) -> Result<String, tera::Error> {
+    let svg = templates(repository, theme).render(template, context)?;
+    if let Err(e) = usvg::roxmltree::Document::parse(&svg) {
+        return Err(tera::Error::msg(format!(
+            "Template {} rendered invalid XML: {}",
+            template, e
+        )));
+    }
+    Ok(svg)
+}
+
+/// Load the display names of languages, names in `path` override the built-in ones
+pub fn load_language_names(path: Option<&Path>) -> Result<LanguageNames, PstatoolError> {
+    let mut names: LanguageNames =
+        serde_yaml::from_str(include_str!("../assets/language_names.yml"))?;
+
+    if let Some(path) = path {
+        let custom: LanguageNames = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        for (locale, custom_names) in custom {
+            names
+                .entry(locale.to_lowercase())
+                .or_default()
+                .extend(custom_names);
+        }
+    }
+
+    Ok(names)
+}
+
+/// Set the display names used when rendering, can only be called once
+pub fn init_language_names(path: Option<&Path>) -> Result<(), PstatoolError> {
+    let names = load_language_names(path)?;
+    if LANGUAGE_NAMES.set(names).is_err() {
+        log::warn!("Language names were already initialized");
+    }
+    Ok(())
+}
+
+fn language_names() -> &'static LanguageNames {
+    LANGUAGE_NAMES
+        .get_or_init(|| load_language_names(None).expect("Failed to parse built-in language names"))
+}
 
-    let template = include_str!("../assets/template.svg");
+/// Name of the language as shown on the card, `de-AT` falls back to the names of `de`
+pub fn display_name(language: &str, locale: Option<&str>) -> String {
+    let Some(locale) = locale.map(str::to_lowercase) else {
+        return language.to_string();
+    };
+    let primary = locale.split(['-', '_']).next().unwrap_or_default();
+
+    [locale.as_str(), primary]
+        .iter()
+        .filter_map(|locale| language_names().get(*locale))
+        .find_map(|names| names.get(language))
+        .cloned()
+        .unwrap_or_else(|| language.to_string())
+}
 
+fn base_context(
+    project_name: &str,
+    total_lines: u64,
+    total_files: u64,
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Context {
+    let locale = options.locale.as_deref();
+    let formatted_lines = format_number(total_lines, options.number_format, locale);
+    let formatted_files = format_number(total_files, options.number_format, locale);
     let subheader = format!(
         "{} lines of code in {} files",
-        data.total_lines, data.total_files
+        formatted_lines, formatted_files
     );
-    let header = format!("Stats for {}", project_name);
+    let header = match &options.title_format {
+        Some(format) => format.replace("{title}", project_name),
+        None => format!("Stats for {}", project_name),
+    };
+    let now = Utc::now();
+
+    let mut context = Context::new();
+    context.insert("card_width", &options.width);
+    context.insert("card_height", &options.height);
+    context.insert("bar_width", &bar_width(options));
+    context.insert("title", project_name);
+    context.insert("header", &header);
+    context.insert("subheader", &subheader);
+    context.insert("total_lines", &total_lines);
+    context.insert("total_files", &total_files);
+    context.insert("formatted_lines", &formatted_lines);
+    context.insert("formatted_files", &formatted_files);
+    if let Some(accent) = &options.accent_color {
+        context.insert("accent", accent);
+    }
+    context.insert("generated_at", &now.to_rfc3339());
+    context.insert("generated_date", &now.format("%Y-%m-%d").to_string());
+    if let Some(ranking) = &metadata.ranking {
+        context.insert("ranking", &ranking.describe());
+    }
+    if let Some(estimate) = &metadata.estimate {
+        context.insert("effort_months", &format!("{:.1}", estimate.effort_months()));
+        context.insert(
+            "schedule_months",
+            &format!("{:.1}", estimate.schedule_months),
+        );
+        context.insert("estimated_cost", &format!("{:.0}", estimate.cost));
+    }
+    if let Some(repository) = &metadata.repository {
+        context.insert("stars", &repository.stars);
+        context.insert("forks", &repository.forks);
+        if let Some(pushed_at) = repository.pushed_at {
+            context.insert("pushed_date", &pushed_at.format("%Y-%m-%d").to_string());
+        }
+    }
+    if let Some(churn) = &metadata.churn {
+        context.insert("churn_added", &compact_number(churn.added));
+        context.insert("churn_removed", &compact_number(churn.removed));
+    }
+    if let Some(contributors) = metadata.contributors {
+        context.insert("contributors", &contributors);
+    }
+    if options.show_updated {
+        let updated_at = metadata.updated_at.unwrap_or(now);
+        let mut updated = format!("updated {}", updated_at.format("%Y-%m-%d"));
+        if let Some(commit) = &metadata.commit {
+            updated.push_str(" • ");
+            updated.extend(commit.chars().take(7));
+        }
+        context.insert("updated", &updated);
+    }
+    context
+}
 
-    let svg_content = template
-        .replace("#header#", &header)
-        .replace("#subheader#", &subheader)
-        .replace("#bar_rects#", &data.bar)
-        .replace("#left_block#", &data.left_block)
-        .replace("#right_block#", &data.right_block);
+/// The animated card and a static snapshot of it, rendered from the same data
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardVariants {
+    pub animated: String,
+    /// Without CSS animations or masks, for sanitizers that strip `<style>`
+    pub snapshot: String,
+}
+
+/// Cards of projects with `hide_animation` are static in both variants
+fn render_variants(
+    options: &CardOptions,
+    template: &str,
+    context: &mut Context,
+) -> Result<CardVariants, tera::Error> {
+    let repository = options.repository.as_deref();
+    context.insert("animated", &options.animated);
+    let animated = render(repository, options.theme, template, context)?;
+    context.insert("animated", &false);
+    let snapshot = render(repository, options.theme, template, context)?;
+    Ok(CardVariants { animated, snapshot })
+}
 
-    Ok(svg_content)
+pub fn generate_svg(
+    project_name: &str,
+    cloc: &ClocData,
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<String, tera::Error> {
+    let (template, mut context) = card_context(project_name, cloc, options, metadata)?;
+    context.insert("animated", &options.animated);
+    render(
+        options.repository.as_deref(),
+        options.theme,
+        template,
+        &context,
+    )
 }
 
-pub fn load_language_colors(yaml_str: &str) -> HashMap<String, String> {
-    let parsed: HashMap<String, Language> =
-        serde_yaml::from_str(yaml_str).expect("Failed to parse YAML");
+/// The animated and static card of a bar or donut card
+pub fn generate_svg_variants(
+    project_name: &str,
+    cloc: &ClocData,
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<CardVariants, tera::Error> {
+    let (template, mut context) = card_context(project_name, cloc, options, metadata)?;
+    render_variants(options, template, &mut context)
+}
 
-    parsed
+/// Template and context of a bar or donut card
+fn card_context(
+    project_name: &str,
+    cloc: &ClocData,
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<(&'static str, Context), tera::Error> {
+    let data = cloc_to_svg_template_data(cloc, options);
+
+    let mut context = base_context(
+        project_name,
+        data.total_lines,
+        data.total_files,
+        options,
+        metadata,
+    );
+    context.extend(Context::from_serialize(&data)?);
+    if let Some(density) = data.comment_density {
+        context.insert("comments", &format!("{:.0}%", density));
+    }
+
+    let template = match options.style {
+        CardStyle::Donut => {
+            context.insert("radius", &DONUT_RADIUS);
+            context.insert(
+                "circumference",
+                &(2.0 * std::f64::consts::PI * DONUT_RADIUS),
+            );
+            context.insert("segments", &languages_to_donut_segments(&data.languages));
+            DONUT_TEMPLATE
+        }
+        _ => CARD_TEMPLATE,
+    };
+
+    Ok((template, context))
+}
+
+pub fn generate_treemap_svg(
+    project_name: &str,
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<String, tera::Error> {
+    let mut context = treemap_context(project_name, directories, options, metadata);
+    context.insert("animated", &options.animated);
+    render(
+        options.repository.as_deref(),
+        options.theme,
+        TREEMAP_TEMPLATE,
+        &context,
+    )
+}
+
+/// The animated and static card of a treemap card
+pub fn generate_treemap_svg_variants(
+    project_name: &str,
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<CardVariants, tera::Error> {
+    let mut context = treemap_context(project_name, directories, options, metadata);
+    render_variants(options, TREEMAP_TEMPLATE, &mut context)
+}
+
+fn treemap_context(
+    project_name: &str,
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Context {
+    let total_lines = directories.iter().map(|dir| dir.lines).sum();
+    let total_files = directories.iter().map(|dir| dir.files).sum();
+
+    let mut context = base_context(project_name, total_lines, total_files, options, metadata);
+    let tiles = directories_to_treemap_tiles(directories, options);
+    context.insert("directories", &tiles);
+    context
+}
+
+/// A top level directory on the directory card
+#[derive(Debug, Serialize)]
+pub struct DirectoryRow {
+    name: String,
+    lines: u64,
+    percentage: f64,
+    /// Color of the dominant language of the directory
+    color: String,
+    /// Width of the bar, relative to the largest directory
+    width: f64,
+}
+
+pub fn directories_to_rows(
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+) -> Vec<DirectoryRow> {
+    let locale = options.locale.as_deref();
+    let longest_bar = (bar_width(options) - DIRECTORY_LABELS_WIDTH).max(0.0);
+    let total_lines: u64 = directories.iter().map(|dir| dir.lines).sum();
+    let mut dirs: Vec<&DirectoryStats> = directories.iter().filter(|dir| dir.lines > 0).collect();
+    dirs.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+
+    // The last row is "Other" when there are more directories than rows
+    let shown = match dirs.len() > MAX_DIRECTORY_ROWS {
+        true => MAX_DIRECTORY_ROWS - 1,
+        false => dirs.len(),
+    };
+    let mut entries: Vec<(String, String, u64)> = dirs[..shown]
+        .iter()
+        .map(|dir| {
+            let language = dir.dominant_language().unwrap_or_default();
+            (dir.name.clone(), language_color(language), dir.lines)
+        })
+        .collect();
+    if shown < dirs.len() {
+        entries.push((
+            display_name(OTHER_LANGUAGE, locale),
+            language_color(OTHER_LANGUAGE),
+            dirs[shown..].iter().map(|dir| dir.lines).sum(),
+        ));
+    }
+
+    let longest = entries
+        .iter()
+        .map(|entry| entry.2)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    entries
         .into_iter()
-        .filter_map(|(key, lang)| lang.color.map(|color| (key, color)))
+        .map(|(name, color, lines)| DirectoryRow {
+            name,
+            lines,
+            percentage: lines as f64 / total_lines.max(1) as f64 * 100.0,
+            color,
+            width: lines as f64 / longest * longest_bar,
+        })
         .collect()
 }
 
+/// The animated and static card with the lines per top level directory, written next to the card
+/// of the project with `--directory-card`
+pub fn generate_directories_svg_variants(
+    project_name: &str,
+    directories: &[DirectoryStats],
+    options: &CardOptions,
+    metadata: &CardMetadata,
+) -> Result<CardVariants, tera::Error> {
+    let total_lines = directories.iter().map(|dir| dir.lines).sum();
+    let total_files = directories.iter().map(|dir| dir.files).sum();
+
+    let mut context = base_context(project_name, total_lines, total_files, options, metadata);
+    context.insert("rows", &directories_to_rows(directories, options));
+    render_variants(options, DIRECTORIES_TEMPLATE, &mut context)
+}
+
+/// Language with the most lines and its color, "code" when there are no languages
+fn dominant_language(cloc: &ClocData) -> (&str, String) {
+    let dominant = cloc
+        .languages
+        .iter()
+        .max_by(|a, b| {
+            a.1.total_lines()
+                .cmp(&b.1.total_lines())
+                .then_with(|| b.0.cmp(a.0))
+        })
+        .map(|(lang, _)| lang.as_str())
+        .unwrap_or("code");
+    (dominant, language_color(dominant))
+}
+
+/// Small badge with the total lines of code, colored by the dominant language
+pub fn generate_badge_svg(cloc: &ClocData) -> Result<String, tera::Error> {
+    let total_lines: u64 = cloc.languages.values().map(|s| s.total_lines()).sum();
+    let (dominant, color) = dominant_language(cloc);
+
+    let label = dominant.to_string();
+    let message = format!("{} LOC", compact_number(total_lines));
+    let label_width = badge_text_width(&label);
+    let message_width = badge_text_width(&message);
+
+    let mut context = Context::new();
+    context.insert("label", &label);
+    context.insert("message", &message);
+    context.insert("color", &color);
+    context.insert("text_color", contrast_text_color(&color));
+    context.insert("label_width", &label_width);
+    context.insert("message_width", &message_width);
+    context.insert("width", &(label_width + message_width));
+
+    render(None, Theme::default(), BADGE_TEMPLATE, &context)
+}
+
+/// A language on the comparison card
+#[derive(Debug, Serialize)]
+struct CompareRow {
+    name: String,
+    color: String,
+    before_width: f64,
+    after_width: f64,
+    /// Change in percent, or "new"
+    change: String,
+}
+
+fn format_change(change: Option<f64>) -> String {
+    match change {
+        Some(change) if change >= 0.0 => format!("+{:.0}%", change),
+        Some(change) => format!("−{:.0}%", change.abs()),
+        None => "new".to_string(),
+    }
+}
+
+/// Card with the lines per language of two date ranges
+pub fn generate_compare_svg(
+    title: &str,
+    comparison: &Comparison,
+    options: &CardOptions,
+) -> Result<String, tera::Error> {
+    let longest = comparison
+        .languages
+        .iter()
+        .map(|l| l.before.max(l.after))
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let rows: Vec<CompareRow> = comparison
+        .languages
+        .iter()
+        .take(options.top_languages.min(MAX_COMPARE_LANGUAGES))
+        .map(|l| CompareRow {
+            name: display_name(&l.language, options.locale.as_deref()),
+            color: language_color(&l.language),
+            before_width: l.before as f64 / longest * COMPARE_BAR_WIDTH,
+            after_width: l.after as f64 / longest * COMPARE_BAR_WIDTH,
+            change: format_change(l.change),
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("title", title);
+    context.insert(
+        "header",
+        &format!(
+            "{}: {} vs {}",
+            title, comparison.before.label, comparison.after.label
+        ),
+    );
+    context.insert(
+        "subheader",
+        &format!(
+            "{} → {} lines ({})",
+            compact_number(comparison.before.total_lines),
+            compact_number(comparison.after.total_lines),
+            format_change(comparison.change())
+        ),
+    );
+    context.insert("before_label", &comparison.before.label);
+    context.insert("after_label", &comparison.after.label);
+    context.insert("rows", &rows);
+
+    context.insert("animated", &true);
+    render(None, Theme::default(), COMPARE_TEMPLATE, &context)
+}
+
+/// Total lines at a moment on the history chart
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryPoint {
+    /// Shown below the first and last point, e.g. `2024-06`
+    pub label: String,
+    pub total_lines: u64,
+}
+
+/// Line chart of the total lines over time, colored by the dominant language of `latest`
+pub fn generate_history_svg(
+    title: &str,
+    points: &[HistoryPoint],
+    latest: &ClocData,
+) -> Result<String, tera::Error> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return Err(tera::Error::msg("A history chart needs at least one point"));
+    };
+    let highest = points
+        .iter()
+        .map(|p| p.total_lines)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let step = HISTORY_WIDTH / points.len().saturating_sub(1).max(1) as f64;
+    let line: Vec<String> = points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| {
+            let y = HISTORY_HEIGHT - point.total_lines as f64 / highest * HISTORY_HEIGHT;
+            format!("{:.1},{:.1}", i as f64 * step, y)
+        })
+        .collect();
+    let line = line.join(" ");
+    let end = points.len().saturating_sub(1) as f64 * step;
+    let area = format!("0,{1:.1} {0} {2:.1},{1:.1}", line, HISTORY_HEIGHT, end);
+
+    let mut context = Context::new();
+    context.insert("title", title);
+    context.insert("header", &format!("Stats for {}", title));
+    context.insert(
+        "subheader",
+        &format!(
+            "{} → {} lines of code",
+            compact_number(first.total_lines),
+            compact_number(last.total_lines)
+        ),
+    );
+    context.insert("line", &line);
+    context.insert("area", &area);
+    context.insert("color", &dominant_language(latest).1);
+    context.insert("chart_width", &HISTORY_WIDTH);
+    context.insert("chart_height", &HISTORY_HEIGHT);
+    context.insert("first_label", &first.label);
+    context.insert("last_label", &last.label);
+
+    context.insert("animated", &true);
+    render(None, Theme::default(), HISTORY_TEMPLATE, &context)
+}
+
+/// Response of a shields.io endpoint badge, see https://shields.io/badges/endpoint-badge
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShieldsEndpoint {
+    pub schema_version: u8,
+    pub label: String,
+    pub message: String,
+    pub color: String,
+}
+
+/// Endpoint badge with the total lines of code, colored by the dominant language
+pub fn shields_endpoint(cloc: &ClocData) -> ShieldsEndpoint {
+    let total_lines: u64 = cloc.languages.values().map(|s| s.total_lines()).sum();
+    let (_, color) = dominant_language(cloc);
+
+    ShieldsEndpoint {
+        schema_version: 1,
+        label: "lines of code".to_string(),
+        message: compact_number(total_lines),
+        color: color.trim_start_matches('#').to_string(),
+    }
+}
+
+/// Render the SVG to a PNG image, `scale` 1.0 matches the size of the SVG in pixels
+pub fn rasterize_svg(svg: &str, scale: f32) -> Result<Vec<u8>, PstatoolError> {
+    let tree = usvg::Tree::from_str(svg, &RASTER_OPTIONS)
+        .map_err(|e| PstatoolError::Svg(e.to_string()))?;
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or_else(|| PstatoolError::Svg(format!("Invalid PNG scale: {}", scale)))?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| PstatoolError::Svg("Failed to allocate PNG image".to_string()))?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .encode_png()
+        .map_err(|e| PstatoolError::Svg(e.to_string()))
+}
+
+/// Approximate width of the text in the 11px badge font, including padding
+fn badge_text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * 7 + 12
+}
+
+/// Black or white, whichever is more readable on the background color
+pub fn contrast_text_color(background: &str) -> &'static str {
+    let hex = background.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .unwrap_or(0) as f64
+    };
+    let luminance = 0.299 * channel(0) + 0.587 * channel(2) + 0.114 * channel(4);
+    if luminance > 160.0 {
+        "#151515"
+    } else {
+        "#ffffff"
+    }
+}
+
+/// Format a number as 999, 12.4k or 1.2M
+pub fn compact_number(value: u64) -> String {
+    match value {
+        0..=999 => value.to_string(),
+        1_000..=999_949 => format!("{:.1}k", value as f64 / 1_000.0),
+        _ => format!("{:.1}M", value as f64 / 1_000_000.0),
+    }
+}
+
+/// Thousands separator of a locale, `,` for English and unknown locales
+fn group_separator(locale: Option<&str>) -> &'static str {
+    let language = locale
+        .and_then(|locale| locale.split(['-', '_']).next())
+        .map(|language| language.to_ascii_lowercase());
+    match language.as_deref() {
+        Some("de" | "nl" | "es" | "it" | "pt" | "da" | "id" | "tr") => ".",
+        // Narrow no-break space
+        Some("fr" | "sv" | "nb" | "no" | "fi" | "cs" | "pl" | "ru" | "uk") => "\u{202f}",
+        _ => ",",
+    }
+}
+
+/// Write a number in the format of a card, e.g. `128,734` or `128.7k`
+pub fn format_number(value: u64, format: NumberFormat, locale: Option<&str>) -> String {
+    match format {
+        NumberFormat::Plain => value.to_string(),
+        NumberFormat::Compact => compact_number(value),
+        NumberFormat::Grouped => {
+            let digits = value.to_string();
+            let mut grouped = String::new();
+            for (i, digit) in digits.chars().enumerate() {
+                if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                    grouped.push_str(group_separator(locale));
+                }
+                grouped.push(digit);
+            }
+            grouped
+        }
+    }
+}
+
+/// The built-in linguist colors, extended with the downloaded linguist languages in `cached` and the colors of
+/// the config file
+pub fn load_cached_language_colors(
+    cached: Option<&Path>,
+    overrides: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut colors = load_language_colors(include_str!("../assets/langs.yml"))
+        .expect("Failed to parse built-in language colors");
+
+    if let Some(path) = cached.filter(|path| path.exists()) {
+        match std::fs::read_to_string(path)
+            .map_err(PstatoolError::from)
+            .and_then(|yaml| Ok(load_language_colors(&yaml)?))
+        {
+            Ok(downloaded) => colors.extend(downloaded),
+            Err(e) => log::warn!("Ignoring the languages in {}: {}", path.display(), e),
+        }
+    }
+
+    for (language, color) in overrides {
+        if is_hex_color(color) {
+            colors.insert(language.clone(), color.clone());
+        } else {
+            log::warn!("Ignoring invalid color {} of {}", color, language);
+        }
+    }
+    colors
+}
+
+/// Set the colors used when rendering, can only be called once
+pub fn init_language_colors(cached: Option<&Path>, overrides: &HashMap<String, String>) {
+    if LANGUAGE_COLORS
+        .set(load_cached_language_colors(cached, overrides))
+        .is_err()
+    {
+        log::warn!("Language colors were already initialized");
+    }
+}
+
+fn language_colors() -> &'static HashMap<String, String> {
+    LANGUAGE_COLORS.get_or_init(|| load_cached_language_colors(None, &HashMap::new()))
+}
+
+/// `#rgb` or `#rrggbb`
+pub fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Color of a language: the configured color, the linguist color or a color derived from the name
+pub fn language_color(language: &str) -> String {
+    language_colors()
+        .get(language)
+        .cloned()
+        .unwrap_or_else(|| fallback_color(language))
+}
+
+/// Stable color for languages without a linguist color, the hue is picked by a hash of the name
+pub fn fallback_color(language: &str) -> String {
+    // FNV-1a, unlike the std hasher it's the same in every build
+    let hash = language.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+    let hue = (hash % 360) as f64;
+    let (saturation, lightness) = (0.6, 0.6);
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0_f64).abs()) * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f64| ((value + m) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(r), channel(g), channel(b))
+}
+
+/// Colors of the languages in a linguist `languages.yml`, languages without a color are skipped.
+/// The colors end up in the cards, so a downloaded color that isn't a hex color is skipped too
+pub fn load_language_colors(yaml_str: &str) -> Result<HashMap<String, String>, serde_yaml::Error> {
+    let parsed: HashMap<String, Language> = serde_yaml::from_str(yaml_str)?;
+
+    Ok(parsed
+        .into_iter()
+        .filter_map(|(key, lang)| lang.color.map(|color| (key, color)))
+        .filter(|(key, color)| {
+            let valid = is_hex_color(color);
+            if !valid {
+                log::warn!("Ignoring invalid color {} of {}", color, key);
+            }
+            valid
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::compare::compare;
+    use crate::model::{
+        CardMetadata, CardOptions, CardStyle, Churn, ClocConfig, ClocData, CocomoEstimate, Counter,
+        DirectoryStats, NumberFormat, ProjectRanking, RepositoryMeta, SymlinkPolicy, Theme,
+    };
+    use crate::run_cloc;
+    use crate::sandbox::Sandbox;
+    use crate::svg::{
+        cloc_to_svg_template_data, compact_number, contrast_text_color, display_name, escape_xml,
+        fallback_color, format_number, generate_badge_svg, generate_compare_svg,
+        generate_directories_svg_variants, generate_history_svg, generate_svg,
+        generate_svg_variants, generate_treemap_svg, is_hex_color, language_color,
+        language_search_url, languages_to_donut_segments, load_language_colors,
+        load_language_names, load_project_templates, load_templates, rasterize_svg,
+        shields_endpoint, squarify, HistoryPoint, Rect, CARD_TEMPLATE, OTHER_LANGUAGE,
+        THEME_TEMPLATE,
+    };
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
     use std::fs::OpenOptions;
     use std::io::Write;
     use std::path::Path;
-    use crate::model::ClocConfig;
-    use crate::run_cloc;
-    use crate::svg::{generate_svg, load_language_colors};
+    use tera::Context;
+
+    fn test_cloc_data() -> ClocData {
+        serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 4, "blank": 10, "comment": 5, "code": 85},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 90}
+            }"#,
+        )
+        .unwrap()
+    }
 
     #[test]
     fn test_get_lang_color() {
         let yaml = include_str!("../assets/langs.yml");
-        let map = load_language_colors(&yaml);
+        let map = load_language_colors(yaml).unwrap();
         assert!(map.contains_key("Rust"));
         assert!(map.contains_key("Swift"));
         println!("{:?}", map);
     }
 
     #[test]
-    fn test_svg_gen() {
-        let ignored = [
-            "target",
-            ".idea",
-            "*.framework",
-            "*.xcodeproj",
-            "GStreamer.framework",
-            "assets",
-            "pkg",
+    fn test_default_template() {
+        let svg = generate_svg(
+            "Test Project",
+            &test_cloc_data(),
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(svg.contains("Stats for Test Project"));
+        assert!(svg.contains("190 lines of code in 5 files"));
+        assert!(svg.contains("Rust 52.63%"));
+    }
+
+    #[test]
+    fn test_fallback_color() {
+        assert_eq!(language_color("Rust"), "#dea584");
+
+        // Unknown languages get a stable color, different per language
+        let color = language_color("Frobnicate");
+        assert_eq!(color, fallback_color("Frobnicate"));
+        assert_ne!(color, fallback_color("Wibble"));
+        assert!(is_hex_color(&color) && color.len() == 7);
+    }
+
+    #[test]
+    fn test_compact_number() {
+        assert_eq!(compact_number(0), "0");
+        assert_eq!(compact_number(999), "999");
+        assert_eq!(compact_number(1_000), "1.0k");
+        assert_eq!(compact_number(12_400), "12.4k");
+        assert_eq!(compact_number(999_949), "999.9k");
+        assert_eq!(compact_number(999_950), "1.0M");
+        assert_eq!(compact_number(1_250_000), "1.2M");
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(
+            format_number(128_734, NumberFormat::Grouped, None),
+            "128,734"
+        );
+        assert_eq!(format_number(999, NumberFormat::Grouped, None), "999");
+        assert_eq!(
+            format_number(1_234_567, NumberFormat::Grouped, Some("de-AT")),
+            "1.234.567"
+        );
+        assert_eq!(
+            format_number(128_734, NumberFormat::Grouped, Some("fr")),
+            "128\u{202f}734"
+        );
+        assert_eq!(
+            format_number(128_734, NumberFormat::Plain, Some("de")),
+            "128734"
+        );
+        assert_eq!(
+            format_number(128_734, NumberFormat::Compact, None),
+            "128.7k"
+        );
+    }
+
+    #[test]
+    fn test_badge_gen() {
+        let badge = generate_badge_svg(&test_cloc_data()).unwrap();
+        assert!(badge.contains("190 LOC"));
+        assert!(badge.contains(">Rust<"));
+        assert!(badge.contains("#dea584"));
+        assert_eq!(contrast_text_color("#dea584"), "#151515");
+        assert_eq!(contrast_text_color("#00add8"), "#ffffff");
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let badge = generate_badge_svg(&test_cloc_data()).unwrap();
+        let png = rasterize_svg(&badge, 2.0).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+
+        // Width and height are stored as big endian at byte 16 and 20 of the IHDR chunk
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        assert_eq!(height, 40);
+
+        assert!(rasterize_svg(&badge, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_ranking_line() {
+        let metadata = CardMetadata {
+            ranking: Some(ProjectRanking { rank: 3, total: 27 }),
+            ..CardMetadata::default()
+        };
+        let svg = generate_svg(
+            "Test",
+            &test_cloc_data(),
+            &CardOptions::default(),
+            &metadata,
+        )
+        .unwrap();
+        assert!(svg.contains("3rd largest of 27 projects"));
+
+        let svg = generate_svg(
+            "Test",
+            &test_cloc_data(),
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(!svg.contains("largest of"));
+    }
+
+    #[test]
+    fn test_updated_line() {
+        let metadata = CardMetadata {
+            ranking: Some(ProjectRanking { rank: 3, total: 27 }),
+            updated_at: Some(Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+            commit: Some("a1b2c3d4e5f6".to_string()),
+            ..CardMetadata::default()
+        };
+        let options = CardOptions {
+            show_updated: true,
+            ..CardOptions::default()
+        };
+        let svg = generate_svg("Test", &test_cloc_data(), &options, &metadata).unwrap();
+        assert!(svg.contains(r##"y="180" class="footer" fill="#6f6f6f" font-size="10">updated 2024-06-01 • a1b2c3d<"##));
+        // The ranking moves up a line
+        assert!(
+            svg.contains(r##"y="168" class="footer" fill="#6f6f6f" font-size="10">3rd largest"##)
+        );
+
+        let svg = generate_svg(
+            "Test",
+            &test_cloc_data(),
+            &CardOptions::default(),
+            &metadata,
+        )
+        .unwrap();
+        assert!(!svg.contains("updated"));
+        assert!(
+            svg.contains(r##"y="180" class="footer" fill="#6f6f6f" font-size="10">3rd largest"##)
+        );
+    }
+
+    #[test]
+    fn test_estimate_line() {
+        let mut metadata = CardMetadata {
+            estimate: Some(CocomoEstimate {
+                cost: 52000.0,
+                schedule_months: 3.0,
+                people: 1.4,
+            }),
+            ..CardMetadata::default()
+        };
+        let options = CardOptions::default();
+        let svg = generate_svg("Test", &test_cloc_data(), &options, &metadata).unwrap();
+        assert!(svg.contains("Estimated effort: 4.2 person-months"));
+
+        // Shorter next to the ranking
+        metadata.ranking = Some(ProjectRanking { rank: 3, total: 27 });
+        let svg = generate_svg("Test", &test_cloc_data(), &options, &metadata).unwrap();
+        assert!(svg.contains("3rd largest of 27 projects"));
+        assert!(svg.contains("≈ 4.2 person-months"));
+    }
+
+    #[test]
+    fn test_repository_line() {
+        let metadata = CardMetadata {
+            repository: Some(RepositoryMeta {
+                stars: 42,
+                forks: 7,
+                pushed_at: Some("2026-10-01T12:30:00Z".parse().unwrap()),
+            }),
+            ..CardMetadata::default()
+        };
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let svg = generate_svg("Test", &test_cloc_data(), &options, &metadata).unwrap();
+            assert!(svg.contains("★ 42"));
+            assert!(svg.contains("⑂ 7"));
+            assert!(svg.contains("pushed 2026-10-01"));
+        }
+
+        let svg = generate_svg(
+            "Test",
+            &test_cloc_data(),
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(!svg.contains("★"));
+    }
+
+    #[test]
+    fn test_compare_card() {
+        let before: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 4, "blank": 0, "comment": 0, "code": 1000},
+                "Java": {"nFiles": 2, "blank": 0, "comment": 0, "code": 500}
+            }"#,
+        )
+        .unwrap();
+        let after: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 5, "blank": 0, "comment": 0, "code": 1500},
+                "Kotlin": {"nFiles": 8, "blank": 0, "comment": 0, "code": 2000}
+            }"#,
+        )
+        .unwrap();
+        let comparison = compare(
+            &"2023".parse().unwrap(),
+            &before,
+            &"2024".parse().unwrap(),
+            &after,
+        );
+
+        let svg = generate_compare_svg("user", &comparison, &CardOptions::default()).unwrap();
+        assert!(svg.contains("user: 2023 vs 2024"));
+        assert!(svg.contains("1.5k → 3.5k lines (+133%)"));
+        assert!(svg.contains(">new<"));
+        assert!(svg.contains(">+50%<"));
+        assert!(svg.contains(">−100%<"));
+    }
+
+    #[test]
+    fn test_language_links() {
+        let url = language_search_url("wdudokvanheel/pstatool", "C++");
+        assert_eq!(
+            url,
+            "https://github.com/search?q=repo%3Awdudokvanheel%2Fpstatool%20language%3A%22C%2B%2B%22&amp;type=code"
+        );
+
+        let mut options = CardOptions {
+            repository: Some("wdudokvanheel/pstatool".to_string()),
+            ..CardOptions::default()
+        };
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            options.style = style;
+            options.language_links = false;
+            let svg = generate_svg(
+                "Test",
+                &test_cloc_data(),
+                &options,
+                &CardMetadata::default(),
+            )
+            .unwrap();
+            assert!(!svg.contains("<a href"));
+
+            options.language_links = true;
+            let svg = generate_svg(
+                "Test",
+                &test_cloc_data(),
+                &options,
+                &CardMetadata::default(),
+            )
+            .unwrap();
+            assert!(svg.contains("language%3A%22Rust%22"));
+            assert!(svg.contains("language%3A%22Swift%22"));
+        }
+    }
+
+    #[test]
+    fn test_churn_line() {
+        let metadata = CardMetadata {
+            churn: Some(Churn {
+                added: 1260,
+                removed: 145,
+                modified: 30,
+            }),
+            ..CardMetadata::default()
+        };
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let svg = generate_svg("Test", &test_cloc_data(), &options, &metadata).unwrap();
+            assert!(svg.contains("+1.3k / −145 this week"));
+        }
+    }
+
+    #[test]
+    fn test_contributors_line() {
+        let card = |contributors| {
+            let metadata = CardMetadata {
+                contributors: Some(contributors),
+                ..CardMetadata::default()
+            };
+            generate_svg(
+                "Test",
+                &test_cloc_data(),
+                &CardOptions::default(),
+                &metadata,
+            )
+            .unwrap()
+        };
+        assert!(card(12).contains(">12 contributors</text>"));
+        assert!(card(1).contains(">1 contributor</text>"));
+    }
+
+    #[test]
+    fn test_other_language_bucket() {
+        let cloc: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 1, "blank": 0, "comment": 0, "code": 50},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 30},
+                "Lua": {"nFiles": 1, "blank": 0, "comment": 0, "code": 15},
+                "Perl": {"nFiles": 2, "blank": 0, "comment": 0, "code": 5}
+            }"#,
+        )
+        .unwrap();
+        let options = CardOptions {
+            top_languages: 2,
+            ..CardOptions::default()
+        };
+
+        let data = cloc_to_svg_template_data(&cloc, &options);
+        let names: Vec<&str> = data.languages.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["Rust", "Swift", OTHER_LANGUAGE]);
+
+        let other = data.languages.last().unwrap();
+        assert_eq!(other.lines, 20);
+        assert_eq!(other.files, 3);
+
+        let width: f64 = data.languages.iter().map(|l| l.width).sum();
+        assert!((width - 250.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_min_share() {
+        let cloc: ClocData = serde_json::from_str(
+            r#"{
+                "header": {},
+                "Rust": {"nFiles": 1, "blank": 0, "comment": 0, "code": 980},
+                "Swift": {"nFiles": 1, "blank": 0, "comment": 0, "code": 12},
+                "Lua": {"nFiles": 1, "blank": 0, "comment": 0, "code": 5},
+                "Perl": {"nFiles": 2, "blank": 0, "comment": 0, "code": 3}
+            }"#,
+        )
+        .unwrap();
+        let names = |options: &CardOptions| -> Vec<String> {
+            let data = cloc_to_svg_template_data(&cloc, options);
+            data.languages.into_iter().map(|l| l.name).collect()
+        };
+
+        let options = CardOptions {
+            min_share: 1.0,
+            ..CardOptions::default()
+        };
+        assert_eq!(names(&options), ["Rust", "Swift", OTHER_LANGUAGE]);
+        let data = cloc_to_svg_template_data(&cloc, &options);
+        assert_eq!(data.languages[2].lines, 8);
+
+        // The largest language is always shown
+        let options = CardOptions {
+            min_share: 99.0,
+            ..CardOptions::default()
+        };
+        assert_eq!(names(&options), ["Rust", OTHER_LANGUAGE]);
+        assert_eq!(names(&CardOptions::default()).len(), 4);
+    }
+
+    #[test]
+    fn test_donut_segments() {
+        let data = cloc_to_svg_template_data(&test_cloc_data(), &CardOptions::default());
+        let segments = languages_to_donut_segments(&data.languages);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].offset, 0.0);
+        assert_eq!(segments[1].offset, segments[0].length);
+
+        let circumference = 2.0 * std::f64::consts::PI * 40.0;
+        let length: f64 = segments.iter().map(|s| s.length).sum();
+        assert!((length - circumference).abs() < 0.001);
+
+        let options = CardOptions {
+            style: CardStyle::Donut,
+            ..CardOptions::default()
+        };
+        let svg = generate_svg(
+            "Test",
+            &test_cloc_data(),
+            &options,
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(svg.contains("stroke-dasharray"));
+        assert!(svg.contains("Swift 47.37%"));
+    }
+
+    #[test]
+    fn test_squarify() {
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 250.0,
+            height: 100.0,
+        };
+        let rects = squarify(&[60.0, 25.0, 10.0, 5.0], bounds);
+        assert_eq!(rects.len(), 4);
+
+        let area: f64 = rects.iter().map(|r| r.width * r.height).sum();
+        assert!((area - 25000.0).abs() < 0.001);
+        assert!((rects[0].width * rects[0].height - 15000.0).abs() < 0.001);
+
+        for rect in &rects {
+            assert!(rect.x >= 0.0 && rect.x + rect.width <= 250.001);
+            assert!(rect.y >= 0.0 && rect.y + rect.height <= 100.001);
+        }
+    }
+
+    #[test]
+    fn test_treemap_gen() {
+        let dirs = vec![
+            DirectoryStats {
+                name: "src/".to_string(),
+                files: 10,
+                lines: 900,
+                languages: HashMap::from([("Rust".to_string(), 900)]),
+            },
+            DirectoryStats {
+                name: "web/".to_string(),
+                files: 2,
+                lines: 100,
+                languages: HashMap::from([("JavaScript".to_string(), 100)]),
+            },
         ];
 
+        let svg = generate_treemap_svg(
+            "Test",
+            &dirs,
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(svg.contains("1,000 lines of code in 12 files"));
+        assert!(svg.contains("src/ 90%"));
+        assert!(svg.contains("#dea584"));
+    }
+
+    #[test]
+    fn test_directories_gen() {
+        let mut dirs: Vec<DirectoryStats> = ["src/", "web/", "docs/", "/", "a/", "b/", "c/"]
+            .iter()
+            .zip([620, 250, 100, 10, 8, 7, 5])
+            .map(|(name, lines)| DirectoryStats {
+                name: name.to_string(),
+                files: 1,
+                lines,
+                languages: HashMap::from([("Rust".to_string(), lines)]),
+            })
+            .collect();
+
+        let card = generate_directories_svg_variants(
+            "Test",
+            &dirs,
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(card.animated.contains("1,000 lines of code in 7 files"));
+        assert!(card.animated.contains(">src/</text>"));
+        assert!(card.animated.contains("62%"));
+        // The width of the largest bar
+        assert!(card.animated.contains(r#"width="120""#));
+        // The three smallest directories are grouped
+        assert!(card.animated.contains(">Other</text>"));
+        assert!(card.animated.contains("2%"));
+        assert!(!card.animated.contains(">c/</text>"));
+        assert!(!card.snapshot.contains("@keyframes"));
+
+        dirs.truncate(3);
+        let card = generate_directories_svg_variants(
+            "Test",
+            &dirs,
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(card.animated.contains(">docs/</text>"));
+        assert!(!card.animated.contains("Other"));
+    }
+
+    #[test]
+    fn test_static_variant() {
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let card = generate_svg_variants(
+                "Test",
+                &test_cloc_data(),
+                &options,
+                &CardMetadata::default(),
+            )
+            .unwrap();
+            assert!(card.animated.contains("@keyframes fadeInAnimation"));
+            assert!(card
+                .animated
+                .contains("@media (prefers-reduced-motion: no-preference)"));
+            assert!(!card.snapshot.contains("@keyframes"));
+            assert!(!card.snapshot.contains("prefers-reduced-motion"));
+            assert!(!card.snapshot.contains("opacity: 0"));
+            assert!(!card.snapshot.contains("mask"));
+
+            // The text is still visible when a sanitizer strips the styles
+            assert!(card.snapshot.contains(r##"fill="#9f9f9f" font-size="11""##));
+            assert!(card.snapshot.contains(r##"class="header" fill="#fff""##));
+        }
+    }
+
+    #[test]
+    fn test_card_settings() {
+        let options = CardOptions {
+            theme: Theme::Light,
+            accent_color: Some("#58a6ff".to_string()),
+            animated: false,
+            title_format: Some("{title} in numbers".to_string()),
+            ..CardOptions::default()
+        };
+        let card = generate_svg_variants(
+            "Test",
+            &test_cloc_data(),
+            &options,
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert_eq!(card.animated, card.snapshot);
+        assert!(card.animated.contains("Test in numbers"));
+        assert!(card.animated.contains(r##"class="header" fill="#58a6ff""##));
+        assert!(card.animated.contains(r##"fill="#fffefe""##));
+    }
+
+    #[test]
+    fn test_code_only() {
+        let cloc = test_cloc_data();
+        let data = cloc_to_svg_template_data(&cloc, &CardOptions::default());
+        assert_eq!(data.total_lines, 190);
+        assert_eq!(data.languages[0].name, "Rust");
+
+        let options = CardOptions {
+            code_only: true,
+            ..CardOptions::default()
+        };
+        let data = cloc_to_svg_template_data(&cloc, &options);
+        assert_eq!(data.total_lines, 175);
+        // Swift has more code lines than Rust
+        assert_eq!(data.languages[0].name, "Swift");
+        assert_eq!(data.languages[0].lines, 90);
+
+        let svg = generate_svg("Test", &cloc, &options, &CardMetadata::default()).unwrap();
+        assert!(svg.contains("175 lines of code"));
+        assert!(svg.contains("comments: 3%"));
+    }
+
+    #[test]
+    fn test_card_size() {
+        let options = CardOptions {
+            width: 400,
+            height: 220,
+            ..CardOptions::default()
+        };
+        let data = cloc_to_svg_template_data(&test_cloc_data(), &options);
+        assert_eq!(data.bar_width, 350.0);
+        let width: f64 = data.languages.iter().map(|lang| lang.width).sum();
+        assert!((width - 350.0).abs() < 0.01);
+
+        let metadata = CardMetadata {
+            contributors: Some(3),
+            ..CardMetadata::default()
+        };
+        let card = generate_svg_variants("Test", &test_cloc_data(), &options, &metadata).unwrap();
+        assert!(card.animated.contains(r#"viewBox="0 0 400 220""#));
+        assert!(card
+            .animated
+            .contains(r#"<rect x="0" y="0" width="350" height="8""#));
+        assert!(card
+            .animated
+            .contains(r#"<g transform="translate(210, 0)">"#));
+        assert!(card
+            .animated
+            .contains(r#"<text x="375" y="210" text-anchor="end""#));
+        assert!(!card.snapshot.contains("mask"));
+    }
+
+    #[test]
+    fn test_legend_lines() {
+        let mut cloc = test_cloc_data();
+        cloc.languages.get_mut("Rust").unwrap().code = 45_100;
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let svg = generate_svg("Test", &cloc, &options, &CardMetadata::default()).unwrap();
+            assert!(svg.contains(">Rust 99.80%<"));
+
+            let options = CardOptions {
+                legend_lines: true,
+                ..options
+            };
+            let svg = generate_svg("Test", &cloc, &options, &CardMetadata::default()).unwrap();
+            assert!(svg.contains(">Rust 45.1k (99.8%)<"));
+            assert!(svg.contains(">Swift 90 (0.2%)<"));
+        }
+    }
+
+    #[test]
+    fn test_accessible_svg() {
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let svg = generate_svg(
+                "Tom & Jerry",
+                &test_cloc_data(),
+                &options,
+                &CardMetadata::default(),
+            )
+            .unwrap();
+            assert!(svg.contains(r#"role="img""#));
+            assert!(svg.contains(r#"aria-label="Stats for Tom &amp; Jerry""#));
+            assert!(svg.contains(r#"<title id="titleId">Stats for Tom &amp; Jerry</title>"#));
+            assert!(svg.contains(
+                r#"<desc id="descId">190 lines of code in 5 files: Rust 52.6%, Swift 47.4%</desc>"#
+            ));
+        }
+
+        let empty: ClocData = serde_json::from_str(r#"{"header": {}}"#).unwrap();
+        let svg = generate_svg(
+            "Test",
+            &empty,
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        )
+        .unwrap();
+        assert!(svg.contains(r#"<desc id="descId">No code found</desc>"#));
+    }
+
+    #[test]
+    fn test_escape_values() {
+        let mut cloc = test_cloc_data();
+        let rust = cloc.languages.remove("Rust").unwrap();
+        cloc.languages.insert("C<&>".to_string(), rust);
+
+        for style in [CardStyle::Bar, CardStyle::Donut] {
+            let options = CardOptions {
+                style,
+                ..CardOptions::default()
+            };
+            let svg = generate_svg(
+                "Tom & \"Jerry\" <3",
+                &cloc,
+                &options,
+                &CardMetadata::default(),
+            )
+            .unwrap();
+            assert!(svg.contains("Stats for Tom &amp; &quot;Jerry&quot; &lt;3"));
+            assert!(svg.contains("C&lt;&amp;&gt;"));
+        }
+        assert_eq!(escape_xml("it's <b>"), "it&apos;s &lt;b&gt;");
+    }
+
+    #[test]
+    fn test_custom_template() {
+        let dir = std::env::temp_dir().join("pstatool-test-templates");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(CARD_TEMPLATE),
+            "{{ title }}:{{ total_lines }}{% for lang in languages %};{{ lang.name }}={{ lang.lines }}{% endfor %}",
+        )
+        .unwrap();
+
+        let tera = load_templates(Some(&dir)).unwrap();
+        let data = cloc_to_svg_template_data(&test_cloc_data(), &CardOptions::default());
+        let mut context = Context::from_serialize(data).unwrap();
+        context.insert("title", "Test");
+
+        let result = tera.render(CARD_TEMPLATE, &context).unwrap();
+        assert_eq!(result, "Test:190;Rust=100;Swift=90");
+    }
+
+    #[test]
+    fn test_project_templates() {
+        let dir = std::env::temp_dir().join("pstatool-test-project-templates");
+        let project = dir.join("wdudokvanheel").join("pstatool");
+        std::fs::create_dir_all(&project).unwrap();
+        // A light theme for one project, and a card body for all projects
+        std::fs::write(
+            project.join(THEME_TEMPLATE),
+            include_str!("../assets/theme.svg").replace("#151515", "#ffffff"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(CARD_TEMPLATE),
+            r#"{% extends "base.svg" %}{% block body %}<text>{{ total_lines }}</text>{% endblock body %}"#,
+        )
+        .unwrap();
+
+        let tera = load_templates(Some(&dir)).unwrap();
+        let projects = load_project_templates(&dir, &tera).unwrap();
+        let data = cloc_to_svg_template_data(&test_cloc_data(), &CardOptions::default());
+        let mut context = Context::from_serialize(data).unwrap();
+        context.insert("header", "Stats for Test");
+        context.insert("subheader", "");
+
+        let card = tera.render(CARD_TEMPLATE, &context).unwrap();
+        assert!(card.contains(r##"fill="#151515""##));
+        assert!(card.contains("<text>190</text>"));
+        assert!(card.contains("Stats for Test"));
+
+        let card = projects["wdudokvanheel/pstatool"]
+            .render(CARD_TEMPLATE, &context)
+            .unwrap();
+        assert!(card.contains(r##"fill="#ffffff""##));
+        assert!(card.contains("<text>190</text>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_svg_gen() {
         let dest = Path::new("/Users/wesley/workspace/chip8/");
-        let config = ClocConfig{
+        let config = ClocConfig {
             path: dest.to_path_buf(),
             ignored_langs: vec![],
             ignored_dirs: vec![],
+            symlinks: SymlinkPolicy::Skip,
+            counter: Counter::Cloc,
+            sandbox: Sandbox::None,
+            timeout: None,
         };
-        let result = run_cloc(config).unwrap();
+        let result = run_cloc(config).await.unwrap();
 
-        let svg = generate_svg("SleepStream", &result);
+        let svg = generate_svg(
+            "SleepStream",
+            &result,
+            &CardOptions::default(),
+            &CardMetadata::default(),
+        );
         assert!(svg.is_ok());
         let svg_content = svg.unwrap();
 
@@ -188,4 +2165,68 @@ mod tests {
         file.write_all(svg_content.as_bytes())
             .expect("Unable to write data");
     }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("Assembly", None), "Assembly");
+        assert_eq!(display_name("Assembly", Some("fr")), "Assembleur");
+        assert_eq!(display_name("Assembly", Some("de-AT")), "Assembler");
+        assert_eq!(display_name("Rust", Some("de")), "Rust");
+        assert_eq!(display_name("Assembly", Some("xx")), "Assembly");
+    }
+
+    #[test]
+    fn test_localized_other_bucket() {
+        let options = CardOptions {
+            top_languages: 1,
+            locale: Some("nl".to_string()),
+            ..CardOptions::default()
+        };
+        let data = cloc_to_svg_template_data(&test_cloc_data(), &options);
+
+        assert_eq!(data.languages[1].name, "Overig");
+        // Colors are looked up with the canonical name
+        assert_ne!(data.languages[0].color, "#cccccc");
+    }
+
+    #[test]
+    fn test_custom_language_names() {
+        let dir = std::env::temp_dir().join("pstatool-language-names");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("names.yml");
+        std::fs::write(&path, "DE:\n  Rust: Rost\n").unwrap();
+
+        let names = load_language_names(Some(&path)).unwrap();
+        assert_eq!(names["de"]["Rust"], "Rost");
+        assert_eq!(names["de"]["Other"], "Andere");
+    }
+
+    #[test]
+    fn test_shields_endpoint() {
+        let endpoint = shields_endpoint(&test_cloc_data());
+        let json = serde_json::to_value(&endpoint).unwrap();
+
+        assert_eq!(json["schemaVersion"], 1);
+        assert_eq!(json["label"], "lines of code");
+        assert_eq!(json["message"], "190");
+        assert_eq!(json["color"], "dea584");
+    }
+
+    #[test]
+    fn test_history_svg() {
+        let point = |label: &str, total_lines| HistoryPoint {
+            label: label.to_string(),
+            total_lines,
+        };
+        let svg = generate_history_svg(
+            "wdudokvanheel",
+            &[point("2026-08", 1000), point("2026-09", 2000)],
+            &test_cloc_data(),
+        )
+        .unwrap();
+        assert!(svg.contains("1.0k → 2.0k lines of code"));
+        assert!(svg.contains(r#"points="0.0,45.0 250.0,0.0""#));
+        assert!(svg.contains("2026-08"));
+        assert!(generate_history_svg("empty", &[], &test_cloc_data()).is_err());
+    }
 }
@@ -0,0 +1,146 @@
+use crate::model::SymlinkPolicy;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A symbolic link in a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symlink {
+    pub path: PathBuf,
+    /// Whether the link points to an existing file or directory inside the repository
+    pub inside: bool,
+}
+
+/// Find all symbolic links in a repository, without following them
+pub fn find_symlinks(root: &Path) -> io::Result<Vec<Symlink>> {
+    walk_symlinks(root, false)
+}
+
+/// Find the symbolic links in a repository. With `follow` the links to directories inside the
+/// repository are walked like the counter follows them, so their links are found by the path the
+/// counter sees. Links to a directory the link is in are walked once
+fn walk_symlinks(root: &Path, follow: bool) -> io::Result<Vec<Symlink>> {
+    let canonical_root = root.canonicalize()?;
+    let mut links = Vec::new();
+    // Every directory with the canonical directories it is in, to stop at links to those
+    let mut dirs = vec![(root.to_path_buf(), vec![canonical_root.clone()])];
+
+    while let Some((dir, parents)) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                let target = entry.path().canonicalize().ok();
+                let inside = target
+                    .as_ref()
+                    .is_some_and(|target| target.starts_with(&canonical_root));
+                links.push(Symlink {
+                    path: entry.path(),
+                    inside,
+                });
+
+                match target {
+                    Some(target)
+                        if follow && inside && target.is_dir() && !parents.contains(&target) =>
+                    {
+                        let mut parents = parents.clone();
+                        parents.push(target);
+                        dirs.push((entry.path(), parents));
+                    }
+                    _ => {}
+                }
+            } else if file_type.is_dir() && entry.file_name() != ".git" {
+                let mut parents = parents.clone();
+                parents.push(entry.path().canonicalize()?);
+                dirs.push((entry.path(), parents));
+            }
+        }
+    }
+
+    links.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(links)
+}
+
+/// The links that must not be counted with the policy, links outside the repository are never counted
+pub fn excluded_symlinks(root: &Path, policy: SymlinkPolicy) -> io::Result<Vec<PathBuf>> {
+    Ok(walk_symlinks(root, policy == SymlinkPolicy::Follow)?
+        .into_iter()
+        .filter(|link| policy == SymlinkPolicy::Skip || !link.inside)
+        .map(|link| link.path)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::SymlinkPolicy;
+    use crate::symlinks::{excluded_symlinks, find_symlinks};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    fn test_repo() -> PathBuf {
+        let base = std::env::temp_dir().join("pstatool-symlinks");
+        let _ = fs::remove_dir_all(&base);
+        let repo = base.join("repo");
+        fs::create_dir_all(repo.join("src")).unwrap();
+        fs::write(repo.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(base.join("secret.rs"), "const SECRET: &str = \"\";").unwrap();
+
+        symlink(repo.join("src/main.rs"), repo.join("copy.rs")).unwrap();
+        symlink(repo.join("src"), repo.join("source")).unwrap();
+        symlink(base.join("secret.rs"), repo.join("src/escape.rs")).unwrap();
+        symlink(repo.join("missing.rs"), repo.join("dangling.rs")).unwrap();
+        repo.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_symlink_policy() {
+        let repo = test_repo();
+
+        let links = find_symlinks(&repo).unwrap();
+        assert_eq!(links.len(), 4);
+        let inside: Vec<bool> = links.iter().map(|link| link.inside).collect();
+        // copy.rs, dangling.rs, source, src/escape.rs
+        assert_eq!(inside, vec![true, false, true, false]);
+
+        let skipped = excluded_symlinks(&repo, SymlinkPolicy::Skip).unwrap();
+        assert_eq!(skipped.len(), 4);
+
+        // The escaping link is also excluded by the path through the followed source link
+        let followed = excluded_symlinks(&repo, SymlinkPolicy::Follow).unwrap();
+        assert_eq!(
+            followed,
+            vec![
+                repo.join("dangling.rs"),
+                repo.join("source/escape.rs"),
+                repo.join("src/escape.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_symlinks() {
+        let base = std::env::temp_dir().join("pstatool-nested-symlinks");
+        let _ = fs::remove_dir_all(&base);
+        let repo = base.join("repo");
+        fs::create_dir_all(repo.join("a/b")).unwrap();
+        fs::write(base.join("secret.rs"), "const SECRET: &str = \"\";").unwrap();
+        symlink(base.join("secret.rs"), repo.join("a/b/escape.rs")).unwrap();
+        // A link to a link, and a link to a directory it is in
+        symlink(repo.join("a"), repo.join("first")).unwrap();
+        symlink(repo.join("first"), repo.join("second")).unwrap();
+        symlink(repo.join("a"), repo.join("a/b/loop")).unwrap();
+        let repo = repo.canonicalize().unwrap();
+
+        let followed = excluded_symlinks(&repo, SymlinkPolicy::Follow).unwrap();
+        assert_eq!(
+            followed,
+            vec![
+                repo.join("a/b/escape.rs"),
+                repo.join("first/b/escape.rs"),
+                repo.join("second/b/escape.rs")
+            ]
+        );
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
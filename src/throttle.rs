@@ -0,0 +1,252 @@
+//! Limits of the remote operations, so runs with many projects aren't throttled by the forges
+//!
+//! The limits are shared by every project of the process: the server, the refresh queue and the
+//! discovery all take their clones and GitHub requests from the same [Throttle]
+
+use once_cell::sync::OnceCell;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Longest wait for a rate limit to reset, GitHub resets its limits every hour
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60 * 60);
+
+static THROTTLE: OnceCell<Throttle> = OnceCell::new();
+
+/// Limits of the clones and API requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    /// Clones running at the same time, 0 doesn't limit them
+    pub max_clones: usize,
+    /// Time between two requests to the GitHub API
+    pub request_delay: Duration,
+    /// Random time up to this duration added to the delay, so parallel runs don't line up
+    pub request_jitter: Duration,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        ThrottlePolicy {
+            max_clones: 4,
+            request_delay: Duration::ZERO,
+            request_jitter: Duration::ZERO,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Throttle {
+    policy: ThrottlePolicy,
+    clones: Semaphore,
+    /// Earliest time of the next API request
+    next_request: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub fn new(policy: ThrottlePolicy) -> Self {
+        let permits = match policy.max_clones {
+            0 => Semaphore::MAX_PERMITS,
+            max => max,
+        };
+        Throttle {
+            policy,
+            clones: Semaphore::new(permits),
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until a clone may start, the clone is counted until the permit is dropped
+    pub async fn clone_permit(&self) -> SemaphorePermit<'_> {
+        self.clones
+            .acquire()
+            .await
+            .expect("The clone semaphore is never closed")
+    }
+
+    /// Wait until the next API request may be sent, returns false when the wait was cancelled
+    ///
+    /// The lock is only held to check the time, the time is checked again after waiting as the
+    /// requests can be postponed in the meantime
+    pub async fn request_slot(&self, cancel: &CancellationToken) -> bool {
+        loop {
+            let wait = {
+                let mut next = self.next_request.lock().await;
+                let now = Instant::now();
+                if *next <= now {
+                    *next = now + self.policy.request_delay + jitter(self.policy.request_jitter);
+                    return true;
+                }
+                *next
+            };
+            tokio::select! {
+                _ = tokio::time::sleep_until(wait) => {}
+                _ = cancel.cancelled() => return false,
+            }
+        }
+    }
+
+    /// Hold back the API requests for `wait`, e.g. until the rate limit resets
+    pub async fn postpone(&self, wait: Duration) {
+        let mut next = self.next_request.lock().await;
+        *next = (*next).max(Instant::now() + wait);
+    }
+}
+
+/// Random duration up to `max`
+fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().hash_one(SystemTime::now());
+    Duration::from_nanos(random % max.as_nanos().max(1) as u64)
+}
+
+/// Time until the rate limit of the GitHub API resets, `None` while requests are left
+///
+/// Uses `Retry-After` of a rejected request, or `X-RateLimit-Reset` when `X-RateLimit-Remaining`
+/// is 0. Waits are capped at [MAX_RATE_LIMIT_WAIT]
+pub fn rate_limit_wait(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: SystemTime,
+) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+
+    let rejected = matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    );
+    let wait = match header("retry-after") {
+        Some(seconds) if rejected => Duration::from_secs(seconds),
+        _ if header("x-ratelimit-remaining") == Some(0) => {
+            let reset = UNIX_EPOCH + Duration::from_secs(header("x-ratelimit-reset")?);
+            // A second extra, the reset time is rounded down
+            reset.duration_since(now).unwrap_or_default() + Duration::from_secs(1)
+        }
+        _ => return None,
+    };
+    Some(wait.min(MAX_RATE_LIMIT_WAIT))
+}
+
+/// Set the limits of the process, can only be called once
+pub fn init_throttle(policy: ThrottlePolicy) {
+    if THROTTLE.set(Throttle::new(policy)).is_err() {
+        log::warn!("Throttle was already initialized");
+    }
+}
+
+/// The limits of the process, the default limits when they weren't initialized
+pub fn throttle() -> &'static Throttle {
+    THROTTLE.get_or_init(|| Throttle::new(ThrottlePolicy::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::throttle::{rate_limit_wait, Throttle, ThrottlePolicy, MAX_RATE_LIMIT_WAIT};
+    use reqwest::header::HeaderMap;
+    use reqwest::StatusCode;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+
+        let remaining = headers(&[
+            ("x-ratelimit-remaining", "12"),
+            ("x-ratelimit-reset", "1060"),
+        ]);
+        assert_eq!(rate_limit_wait(StatusCode::OK, &remaining, now), None);
+
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1060"),
+        ]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &exhausted, now),
+            Some(Duration::from_secs(61))
+        );
+        // Already reset
+        assert_eq!(
+            rate_limit_wait(StatusCode::OK, &exhausted, now + Duration::from_secs(120)),
+            Some(Duration::from_secs(1))
+        );
+
+        let secondary = headers(&[("retry-after", "30")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::TOO_MANY_REQUESTS, &secondary, now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(rate_limit_wait(StatusCode::OK, &secondary, now), None);
+
+        let far = headers(&[("retry-after", "86400")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &far, now),
+            Some(MAX_RATE_LIMIT_WAIT)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_delay() {
+        let throttle = Throttle::new(ThrottlePolicy {
+            request_delay: Duration::from_millis(20),
+            ..ThrottlePolicy::default()
+        });
+
+        let cancel = CancellationToken::new();
+        let started = tokio::time::Instant::now();
+        assert!(throttle.request_slot(&cancel).await);
+        assert!(throttle.request_slot(&cancel).await);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        throttle.postpone(Duration::from_millis(50)).await;
+        assert!(throttle.request_slot(&cancel).await);
+        assert!(started.elapsed() >= Duration::from_millis(70));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_rate_limit_wait() {
+        let throttle = Throttle::new(ThrottlePolicy::default());
+        throttle.postpone(Duration::from_secs(60)).await;
+
+        // Waiting requests don't hold back postponing or cancelling
+        let cancel = CancellationToken::new();
+        let started = tokio::time::Instant::now();
+        let (slot, _) = tokio::join!(throttle.request_slot(&cancel), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            throttle.postpone(Duration::from_secs(120)).await;
+            cancel.cancel();
+        });
+        assert!(!slot);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_max_clones() {
+        let throttle = Throttle::new(ThrottlePolicy {
+            max_clones: 1,
+            ..ThrottlePolicy::default()
+        });
+
+        let permit = throttle.clone_permit().await;
+        assert!(throttle.clones.try_acquire().is_err());
+        drop(permit);
+        assert!(throttle.clones.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::create_cloc_config;
+use crate::error::PstatoolError;
+use crate::events::EventObserver;
+use crate::local::{self, LocalOptions};
+use crate::model::{ClocConfig, Project};
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use std::path::{Component, Path};
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+
+/// Settings of the watch command
+pub struct WatchOptions {
+    pub local: LocalOptions,
+    /// Time to wait for more changes before counting again
+    pub debounce: Duration,
+}
+
+/// Count the repository at `path` and render its card to `out`, again every time a file changes
+pub async fn watch(
+    path: &Path,
+    out: &Path,
+    options: &WatchOptions,
+    observer: &dyn EventObserver,
+) -> Result<(), PstatoolError> {
+    let path = path.canonicalize()?;
+    let project = local::directory_project(&path);
+
+    let (sender, mut receiver) = unbounded_channel();
+    let mut debouncer = new_debouncer(options.debounce, move |result: DebounceEventResult| {
+        let _ = sender.send(result);
+    })?;
+    debouncer.watcher().watch(&path, RecursiveMode::Recursive)?;
+
+    log::info!("Watching {} for changes", path.display());
+    update_card(&project, &path, out, options, observer).await;
+
+    while let Some(result) = receiver.recv().await {
+        match result {
+            Ok(events) => {
+                // The config of every update, the ignored directories can change with .pstatool.yml
+                let config = create_cloc_config(&local::resolve(&project, &path), &path);
+                let changed = events
+                    .iter()
+                    .any(|event| is_relevant(&event.path, &config, out));
+                if changed {
+                    update_card(&project, &path, out, options, observer).await;
+                }
+            }
+            Err(e) => log::error!("Failed to watch {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+async fn update_card(
+    project: &Project,
+    path: &Path,
+    out: &Path,
+    options: &WatchOptions,
+    observer: &dyn EventObserver,
+) {
+    match local::write_card(project, path, out, &options.local, observer).await {
+        Ok(_) => log::info!("Updated {}", out.display()),
+        Err(e) => log::error!("Failed to update {}: {}", out.display(), e),
+    }
+}
+
+/// Whether a changed file can change the card, ignored directories and the card itself are skipped
+fn is_relevant(changed: &Path, config: &ClocConfig, out: &Path) -> bool {
+    if out.canonicalize().is_ok_and(|out| out == changed) {
+        return false;
+    }
+
+    let relative = changed.strip_prefix(&config.path).unwrap_or(changed);
+    !relative.components().any(|component| match component {
+        Component::Normal(name) => config
+            .ignored_dirs
+            .iter()
+            .any(|dir| name.to_string_lossy() == dir.as_str()),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::create_cloc_config;
+    use crate::model::Project;
+    use crate::watch::is_relevant;
+    use std::path::Path;
+
+    #[test]
+    fn test_relevant_changes() {
+        let project = Project {
+            ignored_dirs: Some("docs".to_string()),
+            ..Project::default()
+        };
+        let config = create_cloc_config(&project, Path::new("/repo"));
+        let out = Path::new("/nonexistent/card.svg");
+
+        assert!(is_relevant(Path::new("/repo/src/main.rs"), &config, out));
+        assert!(is_relevant(Path::new("/repo/.pstatool.yml"), &config, out));
+        assert!(!is_relevant(Path::new("/repo/.git/index"), &config, out));
+        assert!(!is_relevant(
+            Path::new("/repo/target/debug/pstatool"),
+            &config,
+            out
+        ));
+        assert!(!is_relevant(Path::new("/repo/docs/index.md"), &config, out));
+    }
+
+    #[test]
+    fn test_card_is_not_relevant() {
+        let dir = std::env::temp_dir().join("pstatool-watch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("card.svg");
+        std::fs::write(&out, "").unwrap();
+
+        let config = create_cloc_config(&Project::default(), &dir);
+        let out_path = out.canonicalize().unwrap();
+        assert!(!is_relevant(&out_path, &config, &out));
+        assert!(is_relevant(&dir.join("lib.rs"), &config, &out));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::compare::{language_lines, total_lines};
+use crate::model::{ClocData, SCHEMA_VERSION};
+use serde::Serialize;
+
+/// Change of the lines of a language since the previous run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LanguageChange {
+    pub language: String,
+    pub before: u64,
+    pub after: u64,
+    pub delta: i64,
+}
+
+/// What changed since the previous run of a project
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatsDiff {
+    pub total_before: u64,
+    pub total_after: u64,
+    pub total_delta: i64,
+    /// Languages with a different number of lines, the largest change first
+    pub languages: Vec<LanguageChange>,
+    pub added_languages: Vec<String>,
+    pub removed_languages: Vec<String>,
+}
+
+fn delta(before: u64, after: u64) -> i64 {
+    after as i64 - before as i64
+}
+
+impl StatsDiff {
+    /// Compare the stats of the previous run with the new stats, `before` is empty for a new project
+    pub fn between(before: &ClocData, after: &ClocData) -> Self {
+        let lines = language_lines(before, after);
+        let missing_from = |stats: &ClocData| {
+            lines
+                .iter()
+                .filter(|(language, _, _)| !stats.languages.contains_key(language))
+                .map(|(language, _, _)| language.clone())
+                .collect()
+        };
+        let added_languages = missing_from(before);
+        let removed_languages = missing_from(after);
+
+        let mut languages: Vec<LanguageChange> = lines
+            .into_iter()
+            .map(|(language, before, after)| LanguageChange {
+                language,
+                before,
+                after,
+                delta: delta(before, after),
+            })
+            .filter(|change| change.delta != 0)
+            .collect();
+        languages.sort_by_key(|change| std::cmp::Reverse(change.delta.abs()));
+
+        let (total_before, total_after) = (total_lines(before), total_lines(after));
+        StatsDiff {
+            total_before,
+            total_after,
+            total_delta: delta(total_before, total_after),
+            languages,
+            added_languages,
+            removed_languages,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+}
+
+/// Body of the POST sent after the stats of a project were saved
+#[derive(Debug, Serialize)]
+pub struct ProjectUpdated<'a> {
+    pub schema_version: u32,
+    /// Always `project.updated`
+    pub event: &'static str,
+    /// Formatted as `user/project_name`
+    pub project: &'a str,
+    pub commit: Option<&'a str>,
+    /// Whether any language changed, receivers can skip the other updates
+    pub changed: bool,
+    pub changes: &'a StatsDiff,
+}
+
+impl<'a> ProjectUpdated<'a> {
+    pub fn new(project: &'a str, commit: Option<&'a str>, changes: &'a StatsDiff) -> Self {
+        ProjectUpdated {
+            schema_version: SCHEMA_VERSION,
+            event: "project.updated",
+            project,
+            commit,
+            changed: !changes.is_empty(),
+            changes,
+        }
+    }
+}
+
+/// POST the payload as JSON to the webhook. The URL of a webhook often holds its secret, so it's
+/// left out of the errors
+pub async fn send(url: &str, payload: &impl Serialize) -> Result<(), reqwest::Error> {
+    let sent = async {
+        reqwest::Client::builder()
+            .user_agent(concat!("pstatool/", env!("CARGO_PKG_VERSION")))
+            .build()?
+            .post(url)
+            .json(payload)
+            .send()
+            .await?
+            .error_for_status()
+    };
+    sent.await.map(|_| ()).map_err(reqwest::Error::without_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::tests::stats;
+    use crate::webhook::{send, ProjectUpdated, StatsDiff};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn test_stats_diff() {
+        let diff = StatsDiff::between(
+            &stats(&[("Rust", 1000), ("Java", 500), ("Shell", 20)]),
+            &stats(&[("Rust", 1100), ("Kotlin", 700), ("Shell", 20)]),
+        );
+        assert_eq!(diff.total_delta, 300);
+        let changes: Vec<(&str, i64)> = diff
+            .languages
+            .iter()
+            .map(|change| (change.language.as_str(), change.delta))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![("Kotlin", 700), ("Java", -500), ("Rust", 100)]
+        );
+        assert_eq!(diff.added_languages, vec!["Kotlin"]);
+        assert_eq!(diff.removed_languages, vec!["Java"]);
+
+        let same = stats(&[("Rust", 1000)]);
+        assert!(StatsDiff::between(&same, &same).is_empty());
+    }
+
+    /// The fields of the first schema version, receivers rely on these
+    #[test]
+    fn test_payload_schema() {
+        let diff = StatsDiff::between(&stats(&[("Java", 5)]), &stats(&[("Rust", 10)]));
+        let payload = ProjectUpdated::new("user/project", Some("abc123"), &diff);
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            json!({
+                "schema_version": 1,
+                "event": "project.updated",
+                "project": "user/project",
+                "commit": "abc123",
+                "changed": true,
+                "changes": {
+                    "total_before": 5,
+                    "total_after": 10,
+                    "total_delta": 5,
+                    "languages": [
+                        {"language": "Rust", "before": 0, "after": 10, "delta": 10},
+                        {"language": "Java", "before": 5, "after": 0, "delta": -5}
+                    ],
+                    "added_languages": ["Rust"],
+                    "removed_languages": ["Java"]
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (sender, mut receiver) = unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| async move {
+                sender.send(body).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let diff = StatsDiff::between(&stats(&[]), &stats(&[("Rust", 10)]));
+        send(
+            &url,
+            &ProjectUpdated::new("user/project", Some("abc123"), &diff),
+        )
+        .await
+        .unwrap();
+
+        let body = receiver.recv().await.unwrap();
+        assert_eq!(body["event"], "project.updated");
+        assert_eq!(body["changed"], true);
+        assert_eq!(body["changes"]["added_languages"][0], "Rust");
+
+        let missing = format!("{}-secret", url);
+        let error = send(&missing, &json!({})).await.unwrap_err();
+        assert!(!error.to_string().contains("secret"));
+    }
+}